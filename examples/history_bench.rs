@@ -0,0 +1,54 @@
+//! Times large history reads against a seeded database.
+//!
+//! cargo run --release --example history_bench -- [snapshots]
+
+use anyhow::Result;
+use chrono::{Duration, Utc};
+use coinbase_scraper::{CoinRow, Database};
+use std::time::Instant;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let snapshots: i64 = std::env::args().nth(1).and_then(|n| n.parse().ok()).unwrap_or(200_000);
+    let path = std::env::temp_dir().join("history_bench.db");
+    let _ = std::fs::remove_file(&path);
+    let db = Database::new(&format!("sqlite:{}?mode=rwc", path.display())).await?;
+
+    // One BTC snapshot per minute, going back from now
+    let start = Utc::now() - Duration::minutes(snapshots);
+    let rows: Vec<CoinRow> = (0..snapshots)
+        .map(|i| CoinRow {
+            id: 1,
+            rank: Some(1),
+            name: "Bitcoin".to_string(),
+            symbol: "BTC".to_string(),
+            price_usd: Some(60_000.0 + i as f64),
+            market_cap_usd: Some(1.2e12),
+            chg24h_pct: Some(0.5),
+            scraped_at: start + Duration::minutes(i),
+        })
+        .collect();
+    let seed = Instant::now();
+    for chunk in rows.chunks(10_000) {
+        db.save_coins(chunk).await?;
+    }
+    println!("Seeded {} snapshots in {:.2}s", snapshots, seed.elapsed().as_secs_f64());
+
+    const RUNS: u32 = 5;
+    let full = Instant::now();
+    let mut points = 0;
+    for _ in 0..RUNS {
+        points = db.get_coin_history("BTC", None, None, None).await?.len();
+    }
+    println!("Full history ({} points): {:.1}ms avg", points, full.elapsed().as_secs_f64() * 1000.0 / RUNS as f64);
+
+    let since = Utc::now() - Duration::days(7);
+    let ranged = Instant::now();
+    for _ in 0..RUNS {
+        points = db.get_coin_history("BTC", Some(since), None, None).await?.len();
+    }
+    println!("Last 7 days ({} points): {:.1}ms avg", points, ranged.elapsed().as_secs_f64() * 1000.0 / RUNS as f64);
+
+    let _ = std::fs::remove_file(&path);
+    Ok(())
+}
@@ -0,0 +1,30 @@
+-- Hourly/daily rollups written by `db compact` (OHLC of price, last-seen rest)
+CREATE TABLE IF NOT EXISTS snapshots_hourly (
+    coin_id INTEGER NOT NULL,            -- FK to coins.id
+    bucket_ts TEXT NOT NULL,             -- Bucket start, ISO 8601
+    open_usd REAL,
+    high_usd REAL,
+    low_usd REAL,
+    close_usd REAL,
+    cmc_rank INTEGER,                    -- Last rank in the bucket
+    market_cap_usd REAL,                 -- Last market cap in the bucket
+    change_24h REAL,                     -- Last 24h change in the bucket
+    samples INTEGER NOT NULL,            -- Raw snapshots rolled into the bucket
+    PRIMARY KEY (coin_id, bucket_ts),
+    FOREIGN KEY (coin_id) REFERENCES coins (id)
+);
+
+CREATE TABLE IF NOT EXISTS snapshots_daily (
+    coin_id INTEGER NOT NULL,
+    bucket_ts TEXT NOT NULL,
+    open_usd REAL,
+    high_usd REAL,
+    low_usd REAL,
+    close_usd REAL,
+    cmc_rank INTEGER,
+    market_cap_usd REAL,
+    change_24h REAL,
+    samples INTEGER NOT NULL,
+    PRIMARY KEY (coin_id, bucket_ts),
+    FOREIGN KEY (coin_id) REFERENCES coins (id)
+);
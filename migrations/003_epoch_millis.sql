@@ -0,0 +1,94 @@
+-- Store timestamps as integer Unix epoch milliseconds instead of RFC 3339 text.
+-- Rows whose timestamp cannot be parsed are moved to snapshots_quarantine
+-- rather than dropped.
+
+CREATE TABLE IF NOT EXISTS snapshots_quarantine (
+    id INTEGER PRIMARY KEY,              -- Original snapshots.id
+    coin_id INTEGER,
+    ts_utc,                              -- Whatever was stored, untyped
+    cmc_rank INTEGER,
+    price_usd REAL,
+    market_cap_usd REAL,
+    change_24h REAL,
+    reason TEXT NOT NULL,
+    quarantined_at INTEGER NOT NULL      -- Unix epoch milliseconds
+);
+
+INSERT INTO snapshots_quarantine (id, coin_id, ts_utc, cmc_rank, price_usd, market_cap_usd, change_24h, reason, quarantined_at)
+SELECT id, coin_id, ts_utc, cmc_rank, price_usd, market_cap_usd, change_24h,
+       'unparsable timestamp', CAST(unixepoch('now', 'subsec') * 1000 AS INTEGER)
+FROM snapshots
+WHERE unixepoch(ts_utc, 'subsec') IS NULL;
+
+CREATE TABLE snapshots_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    coin_id INTEGER NOT NULL,            -- FK to coins.id
+    ts_utc INTEGER NOT NULL,             -- Unix epoch milliseconds
+    cmc_rank INTEGER,                    -- NULL if unranked
+    price_usd REAL,                      -- NULL if no price
+    market_cap_usd REAL,                 -- NULL if no market cap
+    change_24h REAL,                     -- NULL if no change data
+    FOREIGN KEY (coin_id) REFERENCES coins (id)
+);
+
+INSERT INTO snapshots_new (id, coin_id, ts_utc, cmc_rank, price_usd, market_cap_usd, change_24h)
+SELECT id, coin_id, CAST(ROUND(unixepoch(ts_utc, 'subsec') * 1000) AS INTEGER),
+       cmc_rank, price_usd, market_cap_usd, change_24h
+FROM snapshots
+WHERE unixepoch(ts_utc, 'subsec') IS NOT NULL;
+
+DROP TABLE snapshots;
+ALTER TABLE snapshots_new RENAME TO snapshots;
+
+CREATE INDEX IF NOT EXISTS idx_snapshots_coin_id ON snapshots(coin_id);
+CREATE INDEX IF NOT EXISTS idx_snapshots_ts_utc ON snapshots(ts_utc);
+CREATE INDEX IF NOT EXISTS idx_snapshots_coin_ts ON snapshots(coin_id, ts_utc);
+
+-- Rollup buckets are always whole hours/days, so second precision is exact
+CREATE TABLE snapshots_hourly_new (
+    coin_id INTEGER NOT NULL,
+    bucket_ts INTEGER NOT NULL,          -- Bucket start, Unix epoch milliseconds
+    open_usd REAL,
+    high_usd REAL,
+    low_usd REAL,
+    close_usd REAL,
+    cmc_rank INTEGER,
+    market_cap_usd REAL,
+    change_24h REAL,
+    samples INTEGER NOT NULL,
+    PRIMARY KEY (coin_id, bucket_ts),
+    FOREIGN KEY (coin_id) REFERENCES coins (id)
+);
+
+INSERT INTO snapshots_hourly_new
+SELECT coin_id, unixepoch(bucket_ts) * 1000, open_usd, high_usd, low_usd, close_usd,
+       cmc_rank, market_cap_usd, change_24h, samples
+FROM snapshots_hourly
+WHERE unixepoch(bucket_ts) IS NOT NULL;
+
+DROP TABLE snapshots_hourly;
+ALTER TABLE snapshots_hourly_new RENAME TO snapshots_hourly;
+
+CREATE TABLE snapshots_daily_new (
+    coin_id INTEGER NOT NULL,
+    bucket_ts INTEGER NOT NULL,          -- Bucket start, Unix epoch milliseconds
+    open_usd REAL,
+    high_usd REAL,
+    low_usd REAL,
+    close_usd REAL,
+    cmc_rank INTEGER,
+    market_cap_usd REAL,
+    change_24h REAL,
+    samples INTEGER NOT NULL,
+    PRIMARY KEY (coin_id, bucket_ts),
+    FOREIGN KEY (coin_id) REFERENCES coins (id)
+);
+
+INSERT INTO snapshots_daily_new
+SELECT coin_id, unixepoch(bucket_ts) * 1000, open_usd, high_usd, low_usd, close_usd,
+       cmc_rank, market_cap_usd, change_24h, samples
+FROM snapshots_daily
+WHERE unixepoch(bucket_ts) IS NOT NULL;
+
+DROP TABLE snapshots_daily;
+ALTER TABLE snapshots_daily_new RENAME TO snapshots_daily;
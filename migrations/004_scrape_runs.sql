@@ -0,0 +1,14 @@
+-- One row per scrape run, so runs can be identified, audited and listed.
+CREATE TABLE IF NOT EXISTS scrape_runs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    started_at INTEGER NOT NULL,         -- Unix epoch milliseconds
+    finished_at INTEGER,                 -- NULL while running
+    ts_utc INTEGER,                      -- scraped_at of the saved snapshots
+    status TEXT NOT NULL,                -- running, ok, partial, failed
+    pages_requested INTEGER NOT NULL,
+    pages_failed INTEGER NOT NULL DEFAULT 0,
+    coins_saved INTEGER NOT NULL DEFAULT 0,
+    error TEXT                           -- First failure, if any
+);
+
+CREATE INDEX IF NOT EXISTS idx_scrape_runs_started_at ON scrape_runs(started_at);
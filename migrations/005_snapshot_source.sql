@@ -0,0 +1,3 @@
+-- Provenance of each snapshot: scrape, import or backfill. Existing rows were
+-- all written by scrapes or imports of scraped CSVs.
+ALTER TABLE snapshots ADD COLUMN source TEXT NOT NULL DEFAULT 'scrape';
@@ -0,0 +1,24 @@
+-- Whole-market figures per scrape, computed when snapshots are saved so they
+-- survive compaction of the raw rows they came from. Existing scrapes are
+-- summarized on connect, once the table is in place.
+CREATE TABLE IF NOT EXISTS market_summaries (
+    ts_utc INTEGER PRIMARY KEY,          -- Unix epoch milliseconds of the scrape
+    total_market_cap_usd REAL,           -- NULL if no coin had a market cap
+    change_24h_pct REAL,                 -- Implied by each coin's 24h change
+    coins INTEGER NOT NULL,
+    btc_dominance_pct REAL,              -- NULL if Bitcoin was missing
+    eth_dominance_pct REAL,              -- NULL if Ethereum was missing
+    stablecoin_share_pct REAL,
+    advancers INTEGER NOT NULL,
+    decliners INTEGER NOT NULL,
+    unchanged INTEGER NOT NULL
+);
+
+-- Symbols counted towards the stablecoin share; add rows to extend it.
+CREATE TABLE IF NOT EXISTS stablecoins (
+    symbol TEXT PRIMARY KEY
+);
+
+INSERT OR IGNORE INTO stablecoins (symbol) VALUES
+    ('USDT'), ('USDC'), ('DAI'), ('USDS'), ('USDE'), ('FDUSD'), ('TUSD'), ('USDP'),
+    ('PYUSD'), ('USDD'), ('BUSD'), ('FRAX'), ('GUSD'), ('LUSD'), ('USD1'), ('RLUSD');
@@ -0,0 +1,26 @@
+-- Give quarantined rows their own ids. 003 restarted the snapshots id
+-- sequence from the rows it kept, so an id it quarantined can come back on a
+-- newer snapshot that later fails a check too. The snapshot's id and source
+-- are kept alongside.
+CREATE TABLE snapshots_quarantine_new (
+    id INTEGER PRIMARY KEY,
+    original_id INTEGER,                 -- snapshots.id the row had
+    coin_id INTEGER,
+    ts_utc,                              -- Whatever was stored, untyped
+    cmc_rank INTEGER,
+    price_usd REAL,
+    market_cap_usd REAL,
+    change_24h REAL,
+    source TEXT,                         -- NULL if quarantined before sources were kept
+    reason TEXT NOT NULL,
+    quarantined_at INTEGER NOT NULL      -- Unix epoch milliseconds
+);
+
+INSERT INTO snapshots_quarantine_new
+    (original_id, coin_id, ts_utc, cmc_rank, price_usd, market_cap_usd, change_24h, reason, quarantined_at)
+SELECT id, coin_id, ts_utc, cmc_rank, price_usd, market_cap_usd, change_24h, reason, quarantined_at
+FROM snapshots_quarantine
+ORDER BY id;
+
+DROP TABLE snapshots_quarantine;
+ALTER TABLE snapshots_quarantine_new RENAME TO snapshots_quarantine;
@@ -0,0 +1,4 @@
+-- Look up rollups by time alone, as past scrapes and movers do; the primary
+-- keys lead with coin_id.
+CREATE INDEX IF NOT EXISTS idx_snapshots_hourly_bucket_ts ON snapshots_hourly(bucket_ts);
+CREATE INDEX IF NOT EXISTS idx_snapshots_daily_bucket_ts ON snapshots_daily(bucket_ts);
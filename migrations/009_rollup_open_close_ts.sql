@@ -0,0 +1,7 @@
+-- When each rollup's open and close were seen, so compacting late rows into
+-- an existing bucket only replaces them with older or newer values. NULL for
+-- buckets written before these were kept.
+ALTER TABLE snapshots_hourly ADD COLUMN open_ts INTEGER;
+ALTER TABLE snapshots_hourly ADD COLUMN close_ts INTEGER;
+ALTER TABLE snapshots_daily ADD COLUMN open_ts INTEGER;
+ALTER TABLE snapshots_daily ADD COLUMN close_ts INTEGER;
@@ -10,15 +10,106 @@ CREATE TABLE IF NOT EXISTS coins (
 CREATE TABLE IF NOT EXISTS snapshots (
     id INTEGER PRIMARY KEY AUTOINCREMENT,
     coin_id INTEGER NOT NULL,            -- FK to coins.id
-    ts_utc TEXT NOT NULL,                -- ISO 8601 timestamp
+    ts_utc INTEGER NOT NULL,             -- Unix epoch milliseconds
     cmc_rank INTEGER,                    -- NULL if unranked
     price_usd REAL,                      -- NULL if no price
     market_cap_usd REAL,                 -- NULL if no market cap
     change_24h REAL,                     -- NULL if no change data
+    source TEXT NOT NULL DEFAULT 'scrape', -- scrape, import or backfill
     FOREIGN KEY (coin_id) REFERENCES coins (id)
 );
 
 -- Indexes for common queries
 CREATE INDEX IF NOT EXISTS idx_snapshots_coin_id ON snapshots(coin_id);
 CREATE INDEX IF NOT EXISTS idx_snapshots_ts_utc ON snapshots(ts_utc);
-CREATE INDEX IF NOT EXISTS idx_snapshots_coin_ts ON snapshots(coin_id, ts_utc);
\ No newline at end of file
+CREATE INDEX IF NOT EXISTS idx_snapshots_coin_ts ON snapshots(coin_id, ts_utc);
+-- Hourly/daily rollups written by `db compact` (OHLC of price, last-seen rest)
+CREATE TABLE IF NOT EXISTS snapshots_hourly (
+    coin_id INTEGER NOT NULL,            -- FK to coins.id
+    bucket_ts INTEGER NOT NULL,          -- Bucket start, Unix epoch milliseconds
+    open_usd REAL,
+    high_usd REAL,
+    low_usd REAL,
+    close_usd REAL,
+    cmc_rank INTEGER,                    -- Last rank in the bucket
+    market_cap_usd REAL,                 -- Last market cap in the bucket
+    change_24h REAL,                     -- Last 24h change in the bucket
+    samples INTEGER NOT NULL,            -- Raw snapshots rolled into the bucket
+    open_ts INTEGER,                     -- When open_usd was seen; NULL on older buckets
+    close_ts INTEGER,                    -- When close_usd was seen; NULL on older buckets
+    PRIMARY KEY (coin_id, bucket_ts),
+    FOREIGN KEY (coin_id) REFERENCES coins (id)
+);
+
+CREATE TABLE IF NOT EXISTS snapshots_daily (
+    coin_id INTEGER NOT NULL,
+    bucket_ts INTEGER NOT NULL,
+    open_usd REAL,
+    high_usd REAL,
+    low_usd REAL,
+    close_usd REAL,
+    cmc_rank INTEGER,
+    market_cap_usd REAL,
+    change_24h REAL,
+    samples INTEGER NOT NULL,
+    open_ts INTEGER,
+    close_ts INTEGER,
+    PRIMARY KEY (coin_id, bucket_ts),
+    FOREIGN KEY (coin_id) REFERENCES coins (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_snapshots_hourly_bucket_ts ON snapshots_hourly(bucket_ts);
+CREATE INDEX IF NOT EXISTS idx_snapshots_daily_bucket_ts ON snapshots_daily(bucket_ts);
+
+-- Rows set aside by migrations or repairs instead of being deleted
+CREATE TABLE IF NOT EXISTS snapshots_quarantine (
+    id INTEGER PRIMARY KEY,
+    original_id INTEGER,                 -- snapshots.id the row had
+    coin_id INTEGER,
+    ts_utc,                              -- Whatever was stored, untyped
+    cmc_rank INTEGER,
+    price_usd REAL,
+    market_cap_usd REAL,
+    change_24h REAL,
+    source TEXT,                         -- NULL if quarantined before sources were kept
+    reason TEXT NOT NULL,
+    quarantined_at INTEGER NOT NULL      -- Unix epoch milliseconds
+);
+
+-- One row per scrape run
+CREATE TABLE IF NOT EXISTS scrape_runs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    started_at INTEGER NOT NULL,         -- Unix epoch milliseconds
+    finished_at INTEGER,                 -- NULL while running
+    ts_utc INTEGER,                      -- scraped_at of the saved snapshots
+    status TEXT NOT NULL,                -- running, ok, partial, failed
+    pages_requested INTEGER NOT NULL,
+    pages_failed INTEGER NOT NULL DEFAULT 0,
+    coins_saved INTEGER NOT NULL DEFAULT 0,
+    error TEXT                           -- First failure, if any
+);
+
+CREATE INDEX IF NOT EXISTS idx_scrape_runs_started_at ON scrape_runs(started_at);
+
+-- Whole-market figures per scrape, kept through compaction
+CREATE TABLE IF NOT EXISTS market_summaries (
+    ts_utc INTEGER PRIMARY KEY,          -- Unix epoch milliseconds of the scrape
+    total_market_cap_usd REAL,           -- NULL if no coin had a market cap
+    change_24h_pct REAL,                 -- Implied by each coin's 24h change
+    coins INTEGER NOT NULL,
+    btc_dominance_pct REAL,              -- NULL if Bitcoin was missing
+    eth_dominance_pct REAL,              -- NULL if Ethereum was missing
+    stablecoin_share_pct REAL,
+    advancers INTEGER NOT NULL,
+    decliners INTEGER NOT NULL,
+    unchanged INTEGER NOT NULL
+);
+
+-- Symbols counted towards the stablecoin share; add rows to extend it.
+CREATE TABLE IF NOT EXISTS stablecoins (
+    symbol TEXT PRIMARY KEY
+);
+
+INSERT OR IGNORE INTO stablecoins (symbol) VALUES
+    ('USDT'), ('USDC'), ('DAI'), ('USDS'), ('USDE'), ('FDUSD'), ('TUSD'), ('USDP'),
+    ('PYUSD'), ('USDD'), ('BUSD'), ('FRAX'), ('GUSD'), ('LUSD'), ('USD1'), ('RLUSD');
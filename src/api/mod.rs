@@ -1,34 +1,325 @@
+pub mod error;
 pub mod routes;
+pub mod stream;
 
 use axum::{
+    extract::Request,
+    http::HeaderValue,
+    middleware::{self, Next},
+    response::Response,
     routing::get,
-    Router,
+    Json, Router,
 };
 use std::sync::Arc;
 use tower_http::cors::CorsLayer;
+use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
+use utoipa::OpenApi;
+use utoipa_axum::{router::OpenApiRouter, routes};
+use utoipa_redoc::{Redoc, Servable};
 
+use crate::config::ServerConfig;
 use crate::database::Database;
 
 pub type SharedDatabase = Arc<Database>;
 
 pub fn create_router(db: SharedDatabase) -> Router {
+    routes(db).layer(CorsLayer::permissive()) // Allow all origins for dev
+}
+
+/// Prefix of the current API version.
+pub const API_PREFIX: &str = "/v1";
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Coinbase Scraper API",
+        description = "Cryptocurrency rankings and price history scraped from CoinMarketCap.",
+        license(name = "MIT"),
+    ),
+    tags(
+        (name = "coins", description = "Latest rankings, history and movers"),
+        (name = "market", description = "Whole-market totals, dominance and breadth"),
+        (name = "stream", description = "Live updates as scrape runs are saved"),
+        (name = "meta", description = "Server status"),
+    ),
+)]
+struct ApiDoc;
+
+/// The OpenAPI document served at `/openapi.json`, built from the same
+/// route table the server uses.
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    api().split_for_parts().1
+}
+
+fn api() -> OpenApiRouter<SharedDatabase> {
+    OpenApiRouter::with_openapi(ApiDoc::openapi()).nest(API_PREFIX, v1())
+}
+
+fn routes(db: SharedDatabase) -> Router {
+    let (api, openapi) = api().split_for_parts();
+    let aliases: Router<SharedDatabase> = unversioned().into();
+
     Router::new()
-        .route("/health", get(routes::health))
-        .route("/coins", get(routes::get_coins))
-        .route("/coin/:symbol/latest", get(routes::get_coin_latest))
-        .route("/coin/:symbol/history", get(routes::get_coin_history))
+        .merge(api)
+        .merge(aliases.layer(middleware::from_fn(deprecated)))
+        .merge(Redoc::with_url("/docs", openapi.clone()))
+        .route("/openapi.json", get(move || async move { Json(openapi) }))
+        .fallback(error::not_found)
+        .method_not_allowed_fallback(error::method_not_allowed)
+        .layer(middleware::from_fn(error::request_id))
+        .layer(PropagateRequestIdLayer::new(error::REQUEST_ID_HEADER.clone()))
+        .layer(SetRequestIdLayer::new(error::REQUEST_ID_HEADER.clone(), MakeRequestUuid))
         .with_state(db)
-        .layer(CorsLayer::permissive()) // Allow all origins for dev
+}
+
+fn v1() -> OpenApiRouter<SharedDatabase> {
+    unversioned()
+        .routes(routes!(routes::get_coins_diff))
+        .routes(routes!(routes::get_coins_latest, routes::post_coins_latest))
+        .routes(routes!(routes::get_movers))
+        .routes(routes!(routes::get_compare))
+        .routes(routes!(routes::get_market_summary))
+        .routes(routes!(routes::get_market_history))
+        .routes(routes!(stream::stream))
+}
+
+/// The routes served before `/v1`, which keep their unversioned aliases.
+/// Later endpoints only exist under `/v1`.
+fn unversioned() -> OpenApiRouter<SharedDatabase> {
+    OpenApiRouter::new()
+        .routes(routes!(routes::health))
+        .routes(routes!(routes::get_coins))
+        .routes(routes!(routes::get_coin_latest))
+        .routes(routes!(routes::get_coin_history))
+}
+
+/// The unversioned paths predate `/v1` and still work, but point clients at
+/// their replacement.
+async fn deprecated(request: Request, next: Next) -> Response {
+    let successor = format!("<{}{}>; rel=\"successor-version\"", API_PREFIX, request.uri().path());
+    let mut response = next.run(request).await;
+    let headers = response.headers_mut();
+    headers.insert("deprecation", HeaderValue::from_static("true"));
+    if let Ok(link) = HeaderValue::from_str(&successor) {
+        headers.insert("link", link);
+    }
+    response
 }
 
 pub async fn start_server(db: Database, port: u16) -> anyhow::Result<()> {
-    let db = Arc::new(db);
-    let app = create_router(db);
-    
-    let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", port)).await?;
+    let config = ServerConfig {
+        bind: ([0, 0, 0, 0], port).into(),
+        ..ServerConfig::default()
+    };
+    serve(Arc::new(db), &config).await
+}
+
+/// Serve the API from a database shared with other tasks, such as the daemon.
+pub async fn serve(db: SharedDatabase, config: &ServerConfig) -> anyhow::Result<()> {
+    let app = routes(db.clone()).layer(config.cors_layer()?);
+    let listener = tokio::net::TcpListener::bind(config.bind).await?;
+    // Stopped with the server, even when this future is dropped mid-way, so
+    // it doesn't outlive it in a longer-running process
+    let _poller = AbortOnDrop(tokio::spawn(stream::poll_for_scrapes(db)));
     
-    tracing::info!("Server running on http://localhost:{}", port);
+    tracing::info!("Server running on http://{}", config.bind);
     axum::serve(listener, app).await?;
     
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Aborts a spawned task when dropped.
+struct AbortOnDrop<T>(tokio::task::JoinHandle<T>);
+
+impl<T> Drop for AbortOnDrop<T> {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::testing::{coin, temp_db};
+    use axum::body::{to_bytes, Body};
+    use axum::http::StatusCode;
+    use serde_json::{json, Value};
+    use tower::ServiceExt;
+
+    async fn get(app: &Router, uri: &str) -> anyhow::Result<(StatusCode, axum::http::HeaderMap, Value)> {
+        send(app, Request::get(uri).header("x-request-id", "test-1").body(Body::empty())?).await
+    }
+
+    async fn post(app: &Router, uri: &str, body: Value) -> anyhow::Result<(StatusCode, axum::http::HeaderMap, Value)> {
+        let request = Request::post(uri)
+            .header("x-request-id", "test-1")
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))?;
+        send(app, request).await
+    }
+
+    async fn send(app: &Router, request: Request) -> anyhow::Result<(StatusCode, axum::http::HeaderMap, Value)> {
+        let response = app.clone().oneshot(request).await?;
+        let (parts, body) = response.into_parts();
+        let body = to_bytes(body, usize::MAX).await?;
+        let json = if body.is_empty() { Value::Null } else { serde_json::from_slice(&body)? };
+        Ok((parts.status, parts.headers, json))
+    }
+
+    async fn app() -> anyhow::Result<(tempfile::TempDir, Router)> {
+        let (dir, db) = temp_db().await?;
+        let at = "2026-01-01T00:00:00Z";
+        db.save_coins(&[coin(1, "BTC", 1, 100.0, at), coin(1027, "ETH", 2, 10.0, at)]).await?;
+        Ok((dir, create_router(Arc::new(db))))
+    }
+
+    #[tokio::test]
+    async fn dropping_the_server_stops_the_poller() -> anyhow::Result<()> {
+        let (_dir, db) = temp_db().await?;
+        let db: SharedDatabase = Arc::new(db);
+        let config = ServerConfig { bind: ([127, 0, 0, 1], 0).into(), ..ServerConfig::default() };
+
+        // As when the daemon finishes first and `select!` drops the server
+        let served = tokio::time::timeout(std::time::Duration::from_millis(200), serve(db.clone(), &config)).await;
+        assert!(served.is_err(), "server stopped on its own: {:?}", served);
+
+        // The router's clones go with the future; the poller's once it is aborted
+        for _ in 0..50 {
+            if Arc::strong_count(&db) == 1 {
+                return Ok(());
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        panic!("the poller still holds the database after the server was dropped");
+    }
+
+    #[tokio::test]
+    async fn errors_use_the_envelope_and_status() -> anyhow::Result<()> {
+        let (_dir, app) = app().await?;
+
+        let (status, headers, body) = get(&app, "/v1/coins?limit=many").await?;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "bad_request");
+        assert!(body["message"].as_str().is_some_and(|m| m.starts_with("Failed to deserialize query string")), "{}", body);
+        assert_eq!(body["details"], Value::Null);
+        assert_eq!(body["request_id"], "test-1");
+        assert_eq!(headers["x-request-id"], "test-1");
+
+        let (status, _, body) = get(&app, "/v1/coins?min_rank=5&max_rank=1").await?;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body, json!({
+            "code": "unprocessable_entity",
+            "message": "min_rank must not be above max_rank",
+            "details": { "min_rank": 5, "max_rank": 1 },
+            "request_id": "test-1",
+        }));
+
+        for uri in ["/v1/coin/DOGE/latest", "/v1/nowhere"] {
+            let (status, _, body) = get(&app, uri).await?;
+            assert_eq!(status, StatusCode::NOT_FOUND, "{}", uri);
+            assert_eq!(body["code"], "not_found", "{}", uri);
+            assert_eq!(body["request_id"], "test-1", "{}", uri);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn database_failures_are_hidden_behind_a_500() -> anyhow::Result<()> {
+        let (dir, db) = temp_db().await?;
+        let app = create_router(Arc::new(db));
+        // Break the schema behind the server's back
+        let url = format!("sqlite:{}", dir.path().join("test.db").display());
+        let pool = sqlx::SqlitePool::connect(&url).await?;
+        sqlx::query("DROP TABLE snapshots").execute(&pool).await?;
+        pool.close().await;
+
+        let (status, _, body) = get(&app, "/v1/coins").await?;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(body, json!({
+            "code": "internal_error",
+            "message": "Internal server error",
+            "details": null,
+            "request_id": "test-1",
+        }));
+        Ok(())
+    }
+
+    fn found(body: &Value) -> Vec<u64> {
+        body["coins"].as_array().into_iter().flatten().filter_map(|coin| coin["id"].as_u64()).collect()
+    }
+
+    #[tokio::test]
+    async fn batch_lookups_dedupe_and_list_unresolved() -> anyhow::Result<()> {
+        let (_dir, app) = app().await?;
+
+        let (status, _, body) = post(&app, "/v1/coins/latest", json!({
+            "symbols": ["btc", " BTC ", "doge", "DOGE", ""],
+            "ids": [1027, 1027, 1, 424242],
+        })).await?;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(found(&body), vec![1, 1027]);
+        assert_eq!(body["unresolved"], json!(["DOGE"]));
+        assert_eq!(body["unresolved_ids"], json!([424242]));
+
+        let (status, _, body) = get(&app, "/v1/coins/latest?symbols=eth,ETH,,xyz").await?;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(found(&body), vec![1027]);
+        assert_eq!(body["unresolved"], json!(["XYZ"]));
+        assert_eq!(body["unresolved_ids"], json!([]));
+
+        // Duplicates don't count towards the limit
+        let (status, _, _) = post(&app, "/v1/coins/latest", json!({ "ids": vec![1; 600] })).await?;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _, body) = post(&app, "/v1/coins/latest", json!({ "ids": (0..501).collect::<Vec<_>>() })).await?;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["details"], json!({ "symbols": 0, "ids": 501 }));
+
+        let (status, _, body) = post(&app, "/v1/coins/latest", json!({ "names": ["Bitcoin"] })).await?;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["code"], "unprocessable_entity");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn compare_takes_intervals_up_to_the_longest_duration() -> anyhow::Result<()> {
+        let (_dir, app) = app().await?;
+        let range = "since=2025-12-01T00:00:00Z&until=2026-01-02T00:00:00Z";
+
+        // About i64::MAX milliseconds: one bucket from the epoch
+        let (status, _, body) = get(&app, &format!("/v1/compare?symbols=BTC&{}&interval=106751991167d", range)).await?;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(body["timestamps"], json!(["1970-01-01T00:00:00Z"]));
+        assert_eq!(body["series"][0]["values"], json!([100.0]));
+
+        let (status, _, body) = get(&app, &format!("/v1/compare?symbols=BTC&{}&interval=106751991168d", range)).await?;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn only_pre_v1_routes_have_deprecated_aliases() -> anyhow::Result<()> {
+        let (_dir, app) = app().await?;
+
+        let (status, headers, body) = get(&app, "/coin/BTC/latest").await?;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["symbol"], "BTC");
+        assert_eq!(headers["deprecation"], "true");
+        assert_eq!(headers["link"], "</v1/coin/BTC/latest>; rel=\"successor-version\"");
+
+        // Errors from an alias are deprecated too
+        let (status, headers, _) = get(&app, "/coins?limit=many").await?;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(headers["link"], "</v1/coins>; rel=\"successor-version\"");
+
+        let (status, headers, _) = get(&app, "/v1/coin/BTC/latest").await?;
+        assert_eq!(status, StatusCode::OK);
+        assert!(!headers.contains_key("deprecation") && !headers.contains_key("link"));
+
+        for uri in ["/coins/diff", "/coins/latest?symbols=BTC", "/movers", "/compare?symbols=BTC", "/market/summary", "/stream"] {
+            let (status, headers, body) = get(&app, uri).await?;
+            assert_eq!(status, StatusCode::NOT_FOUND, "{}", uri);
+            assert_eq!(body["code"], "not_found", "{}", uri);
+            assert!(!headers.contains_key("deprecation"), "{}", uri);
+        }
+        Ok(())
+    }
+}
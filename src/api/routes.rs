@@ -1,81 +1,505 @@
-use axum::{
-    extract::{Path, Query, State},
-    http::StatusCode,
-    Json, response::Result,
-};
+use axum::{extract::State, http::{HeaderMap, HeaderValue}, Json};
+use chrono::{DateTime, Utc};
 use serde::Deserialize;
-use std::collections::HashMap;
+use serde_json::json;
+use utoipa::{IntoParams, ToSchema};
 
+use crate::api::error::{ApiError, ErrorBody, JsonBody, Path, Query};
 use crate::api::SharedDatabase;
-use crate::model::{HealthResponse, CoinResponse, HistoryResponse};
+use crate::daemon::schedule::parse_duration;
+use crate::database::compare::MAX_BUCKETS;
+use crate::database::{CoinQuery, CoinSort, CompareMode, CompareQuery, MoverDirection, MoversFilter, MoverWindow, SortOrder};
+use crate::model::{HealthResponse, CoinResponse, CoinsDiff, CompareResponse, HistoryResponse, LatestCoinsResponse, MarketSummary, MoversResponse};
 
+#[utoipa::path(
+    get,
+    path = "/health",
+    tag = "meta",
+    responses((status = 200, description = "The server is up", body = HealthResponse)),
+)]
 pub async fn health() -> Json<HealthResponse> {
     Json(HealthResponse { ok: true })
 }
 
-#[derive(Debug, Deserialize)]
+/// Response header with the number of coins matching the filters.
+pub const TOTAL_COUNT_HEADER: &str = "x-total-count";
+/// Response header with the cursor for the next page, when there is one.
+pub const NEXT_CURSOR_HEADER: &str = "x-next-cursor";
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct CoinsQuery {
+    /// List the last scrape at or before this time (RFC 3339) instead of the
+    /// latest; keep it on later pages
+    at: Option<DateTime<Utc>>,
+    /// Number of coins per page, at most 500 (default 100)
     limit: Option<u32>,
+    /// Coins to skip; not combinable with `cursor`
+    offset: Option<u32>,
+    /// `x-next-cursor` from the previous page
+    #[param(example = "303a72616e6b3a6173633a303a313a31")]
+    cursor: Option<String>,
+    /// Sort column (default rank)
+    #[param(inline)]
+    sort: Option<CoinSort>,
+    /// Sort direction (default asc for rank, desc otherwise)
+    #[param(inline)]
+    order: Option<SortOrder>,
+    /// Comma-separated symbols, e.g. `BTC,ETH`
+    symbols: Option<String>,
+    /// Case-insensitive substring of the coin name
+    search: Option<String>,
+    min_market_cap: Option<f64>,
+    max_market_cap: Option<f64>,
+    min_rank: Option<u64>,
+    max_rank: Option<u64>,
+}
+
+impl CoinsQuery {
+    fn into_query(self) -> Result<CoinQuery, ApiError> {
+        if self.cursor.is_some() && self.offset.is_some() {
+            return Err(ApiError::unprocessable("cursor and offset cannot be combined"));
+        }
+        if let (Some(min), Some(max)) = (self.min_market_cap, self.max_market_cap)
+            && min > max {
+            return Err(ApiError::unprocessable("min_market_cap must not be above max_market_cap")
+                .with_details(json!({ "min_market_cap": min, "max_market_cap": max })));
+        }
+        if let (Some(min), Some(max)) = (self.min_rank, self.max_rank)
+            && min > max {
+            return Err(ApiError::unprocessable("min_rank must not be above max_rank")
+                .with_details(json!({ "min_rank": min, "max_rank": max })));
+        }
+
+        let query = CoinQuery {
+            at: self.at,
+            sort: self.sort.unwrap_or_default(),
+            order: self.order,
+            symbols: self.symbols.map(|s| s.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect()),
+            search: self.search.filter(|s| !s.is_empty()),
+            min_market_cap: self.min_market_cap,
+            max_market_cap: self.max_market_cap,
+            min_rank: self.min_rank,
+            max_rank: self.max_rank,
+            limit: self.limit.unwrap_or(CoinQuery::DEFAULT_LIMIT).min(500),
+            offset: self.offset.unwrap_or(0),
+            cursor: self.cursor.map(|c| c.parse()).transpose().map_err(|err: anyhow::Error| ApiError::bad_request(err.to_string()))?,
+        };
+        // A cursor only makes sense in the listing it came from
+        if let Some(cursor) = &query.cursor
+            && (cursor.sort != query.sort || cursor.order != query.order()) {
+            return Err(ApiError::unprocessable(format!(
+                "cursor was issued for sort={}&order={}, not sort={}&order={}",
+                cursor.sort, cursor.order, query.sort, query.order(),
+            )));
+        }
+        Ok(query)
+    }
 }
 
+/// Coins from the latest scrape, or the last one at or before `at`, filtered,
+/// sorted and paginated. Pages come from `offset` or, to stay on the same
+/// scrape while paging, from the cursor in the `x-next-cursor` header.
+#[utoipa::path(
+    get,
+    path = "/coins",
+    tag = "coins",
+    params(CoinsQuery),
+    responses(
+        (status = 200, description = "One page of coins", body = Vec<CoinResponse>, headers(
+            ("x-total-count" = u64, description = "Coins matching the filters across all pages"),
+            ("x-next-cursor" = String, description = "Cursor for the next page; absent on the last page"),
+        )),
+        (status = 400, description = "Malformed query string or cursor", body = ErrorBody),
+        (status = 422, description = "Contradictory parameters", body = ErrorBody),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    ),
+)]
 pub async fn get_coins(
     Query(params): Query<CoinsQuery>,
     State(db): State<SharedDatabase>
-) -> Result<Json<Vec<CoinResponse>>, (StatusCode, String)> {
-    let limit = params.limit.unwrap_or(100).min(500) as i64;
-    
-    match db.get_latest_coins_api(limit).await {
-        Ok(coins) => Ok(Json(coins)),
-        Err(err) => {
-            tracing::error!("Failed to get latest coins: {}", err);
-            Err((StatusCode::INTERNAL_SERVER_ERROR, "Internal server error".to_string()))
+) -> Result<(HeaderMap, Json<Vec<CoinResponse>>), ApiError> {
+    let page = db.list_coins(&params.into_query()?).await?;
+
+    let mut headers = HeaderMap::new();
+    headers.insert(TOTAL_COUNT_HEADER, HeaderValue::from(page.total));
+    if let Some(cursor) = &page.next_cursor
+        && let Ok(value) = HeaderValue::from_str(&cursor.to_string()) {
+        headers.insert(NEXT_CURSOR_HEADER, value);
+    }
+    Ok((headers, Json(page.coins)))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DiffQuery {
+    /// Compare from the last scrape at or before this time, RFC 3339
+    /// (default the scrape before `to`)
+    from: Option<DateTime<Utc>>,
+    /// Compare to the last scrape at or before this time, RFC 3339 (default
+    /// the latest)
+    to: Option<DateTime<Utc>>,
+    /// Size of the ranking compared, at most 500 (default 100)
+    limit: Option<u32>,
+}
+
+/// Coins that entered and left the top N between two scrapes, and rank
+/// changes of those in both.
+#[utoipa::path(
+    get,
+    path = "/coins/diff",
+    tag = "coins",
+    params(DiffQuery),
+    responses(
+        (status = 200, description = "Entries, exits and rank changes", body = CoinsDiff),
+        (status = 400, description = "Malformed query string", body = ErrorBody),
+        (status = 422, description = "from is after to", body = ErrorBody),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    ),
+)]
+pub async fn get_coins_diff(
+    Query(params): Query<DiffQuery>,
+    State(db): State<SharedDatabase>
+) -> Result<Json<CoinsDiff>, ApiError> {
+    if let (Some(from), Some(to)) = (params.from, params.to)
+        && from > to {
+        return Err(ApiError::unprocessable("from must not be after to")
+            .with_details(json!({ "from": from, "to": to })));
+    }
+    let limit = params.limit.unwrap_or(100).min(500);
+
+    Ok(Json(db.diff_coins(params.from, params.to, limit).await?))
+}
+
+/// Most symbols and ids one batch lookup may ask for.
+pub const MAX_BATCH: usize = 500;
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct LatestQuery {
+    /// Comma-separated symbols, e.g. `BTC,ETH`
+    symbols: Option<String>,
+}
+
+/// Symbols and ids to look up in one request.
+#[derive(Debug, Default, Deserialize, ToSchema)]
+#[serde(default, deny_unknown_fields)]
+#[schema(example = json!({ "symbols": ["BTC", "ETH"], "ids": [1027] }))]
+pub struct LatestRequest {
+    symbols: Vec<String>,
+    ids: Vec<u64>,
+}
+
+impl LatestRequest {
+    async fn lookup(self, db: &SharedDatabase) -> Result<Json<LatestCoinsResponse>, ApiError> {
+        let mut symbols: Vec<String> = Vec::new();
+        for symbol in self.symbols.iter().map(|s| s.trim().to_uppercase()).filter(|s| !s.is_empty()) {
+            if !symbols.contains(&symbol) {
+                symbols.push(symbol);
+            }
         }
+        let mut ids = self.ids;
+        ids.sort_unstable();
+        ids.dedup();
+        if symbols.len() + ids.len() > MAX_BATCH {
+            return Err(ApiError::unprocessable(format!("at most {} symbols and ids per request", MAX_BATCH))
+                .with_details(json!({ "symbols": symbols.len(), "ids": ids.len() })));
+        }
+
+        Ok(Json(db.get_coins_latest_api(&symbols, &ids).await?))
     }
 }
 
+/// Latest snapshots of several coins by symbol, in one request.
+#[utoipa::path(
+    get,
+    path = "/coins/latest",
+    tag = "coins",
+    params(LatestQuery),
+    responses(
+        (status = 200, description = "Found coins and unresolved symbols", body = LatestCoinsResponse),
+        (status = 400, description = "Malformed query string", body = ErrorBody),
+        (status = 422, description = "More than 500 symbols", body = ErrorBody),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    ),
+)]
+pub async fn get_coins_latest(
+    Query(params): Query<LatestQuery>,
+    State(db): State<SharedDatabase>
+) -> Result<Json<LatestCoinsResponse>, ApiError> {
+    let symbols = params.symbols.map(|s| s.split(',').map(str::to_string).collect()).unwrap_or_default();
+    LatestRequest { symbols, ids: Vec::new() }.lookup(&db).await
+}
+
+/// Latest snapshots of several coins by symbol or id, for lists too long
+/// for a query string.
+#[utoipa::path(
+    post,
+    path = "/coins/latest",
+    tag = "coins",
+    request_body = LatestRequest,
+    responses(
+        (status = 200, description = "Found coins and unresolved symbols and ids", body = LatestCoinsResponse),
+        (status = 400, description = "Malformed JSON body", body = ErrorBody),
+        (status = 415, description = "Body is not application/json", body = ErrorBody),
+        (status = 422, description = "Wrong body fields, or more than 500 symbols and ids", body = ErrorBody),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    ),
+)]
+pub async fn post_coins_latest(
+    State(db): State<SharedDatabase>,
+    JsonBody(request): JsonBody<LatestRequest>,
+) -> Result<Json<LatestCoinsResponse>, ApiError> {
+    request.lookup(&db).await
+}
+
+/// Latest snapshot of one coin.
+#[utoipa::path(
+    get,
+    path = "/coin/{symbol}/latest",
+    tag = "coins",
+    params(("symbol" = String, Path, description = "Ticker symbol, e.g. BTC")),
+    responses(
+        (status = 200, description = "The coin's latest snapshot", body = CoinResponse),
+        (status = 404, description = "Unknown symbol", body = ErrorBody),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    ),
+)]
 pub async fn get_coin_latest(
     Path(symbol): Path<String>,
     State(db): State<SharedDatabase>
-) -> Result<Json<CoinResponse>, (StatusCode, String)> {
-    match db.get_coin_latest_api(&symbol).await {
-        Ok(Some(coin)) => Ok(Json(coin)),
-        Ok(None) => Err((StatusCode::NOT_FOUND, "Symbol not found".to_string())),
-        Err(err) => {
-            tracing::error!("Failed to get coin {}: {}", symbol, err);
-            Err((StatusCode::INTERNAL_SERVER_ERROR, "Internal server error".to_string()))
-        }
-    }
+) -> Result<Json<CoinResponse>, ApiError> {
+    db.get_coin_latest_api(&symbol).await?
+        .map(Json)
+        .ok_or_else(|| ApiError::not_found(format!("Symbol not found: {}", symbol)))
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct HistoryQuery {
-    since: Option<String>,
+    /// Earliest timestamp, RFC 3339
+    since: Option<DateTime<Utc>>,
+    /// Latest timestamp, RFC 3339
+    until: Option<DateTime<Utc>>,
+    /// Number of points, at most 2000 (default 500)
     limit: Option<u32>,
 }
 
+/// Price history of one coin, including hourly and daily rollups for
+/// compacted ranges.
+#[utoipa::path(
+    get,
+    path = "/coin/{symbol}/history",
+    tag = "coins",
+    params(("symbol" = String, Path, description = "Ticker symbol, e.g. BTC"), HistoryQuery),
+    responses(
+        (status = 200, description = "Price series, oldest first", body = HistoryResponse),
+        (status = 400, description = "Malformed query string", body = ErrorBody),
+        (status = 422, description = "since is after until", body = ErrorBody),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    ),
+)]
 pub async fn get_coin_history(
     Path(symbol): Path<String>,
     Query(params): Query<HistoryQuery>,
     State(db): State<SharedDatabase>
-) -> Result<Json<HistoryResponse>, (StatusCode, String)> {
+) -> Result<Json<HistoryResponse>, ApiError> {
     let limit = params.limit.unwrap_or(500).min(2000);
+    if let (Some(since), Some(until)) = (params.since, params.until)
+        && since > until {
+        return Err(ApiError::unprocessable("since must not be after until")
+            .with_details(json!({ "since": since, "until": until })));
+    }
     
-    // For now, just get all history (ignore 'since' parameter for simplicity)
-    match db.get_coin_history(&symbol).await {
-        Ok(history) => {
-            let series: Vec<_> = history.into_iter()
-                .take(limit as usize)
-                .map(|point| (point.timestamp, point.price_usd))
-                .collect();
-                
-            Ok(Json(HistoryResponse {
-                symbol: symbol.to_uppercase(),
-                series,
-            }))
-        },
-        Err(err) => {
-            tracing::error!("Failed to get history for {}: {}", symbol, err);
-            Err((StatusCode::INTERNAL_SERVER_ERROR, "Internal server error".to_string()))
+    let history = db.get_coin_history(&symbol, params.since, params.until, Some(limit)).await?;
+    Ok(Json(HistoryResponse::new(&symbol, history)))
+}
+
+/// Total market cap, dominance and breadth of the latest scrape.
+#[utoipa::path(
+    get,
+    path = "/market/summary",
+    tag = "market",
+    responses(
+        (status = 200, description = "Summary of the latest scrape", body = MarketSummary),
+        (status = 404, description = "Nothing scraped yet", body = ErrorBody),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    ),
+)]
+pub async fn get_market_summary(State(db): State<SharedDatabase>) -> Result<Json<MarketSummary>, ApiError> {
+    db.market_summary().await?
+        .map(Json)
+        .ok_or_else(|| ApiError::not_found("No scrapes yet"))
+}
+
+/// Market summaries of past scrapes, including compacted ranges.
+#[utoipa::path(
+    get,
+    path = "/market/history",
+    tag = "market",
+    params(HistoryQuery),
+    responses(
+        (status = 200, description = "One summary per scrape, oldest first", body = Vec<MarketSummary>),
+        (status = 400, description = "Malformed query string", body = ErrorBody),
+        (status = 422, description = "since is after until", body = ErrorBody),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    ),
+)]
+pub async fn get_market_history(
+    Query(params): Query<HistoryQuery>,
+    State(db): State<SharedDatabase>
+) -> Result<Json<Vec<MarketSummary>>, ApiError> {
+    let limit = params.limit.unwrap_or(500).min(2000);
+    if let (Some(since), Some(until)) = (params.since, params.until)
+        && since > until {
+        return Err(ApiError::unprocessable("since must not be after until")
+            .with_details(json!({ "since": since, "until": until })));
+    }
+
+    Ok(Json(db.market_history(params.since, params.until, Some(limit)).await?))
+}
+
+/// Most coins one comparison may include.
+pub const MAX_COMPARE: usize = 20;
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CompareParams {
+    /// Comma-separated symbols, e.g. `BTC,ETH,SOL`, at most 20
+    symbols: String,
+    /// Start of the range, RFC 3339 (default 30 days before `until`)
+    since: Option<DateTime<Utc>>,
+    /// End of the range, RFC 3339 (default now)
+    until: Option<DateTime<Utc>>,
+    /// Bucket width such as `15m`, `1h` or `1d` (default 1d); at most 2000 buckets
+    #[param(example = "1d")]
+    interval: Option<String>,
+    /// What to return (default `price`, or `ratio` when `base` is given)
+    #[param(inline)]
+    mode: Option<CompareMode>,
+    /// Coin to divide prices by in `ratio` mode
+    base: Option<String>,
+}
+
+impl CompareParams {
+    fn into_query(self) -> Result<CompareQuery, ApiError> {
+        let mut symbols: Vec<String> = Vec::new();
+        for symbol in self.symbols.split(',').map(|s| s.trim().to_uppercase()).filter(|s| !s.is_empty()) {
+            if !symbols.contains(&symbol) {
+                symbols.push(symbol);
+            }
+        }
+        if symbols.is_empty() || symbols.len() > MAX_COMPARE {
+            return Err(ApiError::unprocessable(format!("symbols must list 1 to {} coins", MAX_COMPARE)));
+        }
+
+        let mode = match (self.mode, &self.base) {
+            (Some(mode), _) => mode,
+            (None, Some(_)) => CompareMode::Ratio,
+            (None, None) => CompareMode::Price,
+        };
+        match (mode, &self.base) {
+            (CompareMode::Ratio, None) => return Err(ApiError::unprocessable("mode=ratio needs a base coin")),
+            (mode, Some(_)) if mode != CompareMode::Ratio => {
+                return Err(ApiError::unprocessable(format!("base only applies to mode=ratio, not mode={}", mode)));
+            }
+            _ => {}
         }
+
+        let interval = match self.interval {
+            Some(interval) => parse_duration(&interval).map_err(|err| ApiError::bad_request(err.to_string()))?,
+            None => std::time::Duration::from_secs(86400),
+        };
+        let interval = chrono::Duration::from_std(interval).map_err(|err| ApiError::bad_request(err.to_string()))?;
+        if interval.num_milliseconds() <= 0 {
+            return Err(ApiError::unprocessable("interval must be positive"));
+        }
+        let until = self.until.unwrap_or_else(Utc::now);
+        let since = self.since.unwrap_or(until - chrono::Duration::days(30));
+        if since >= until {
+            return Err(ApiError::unprocessable("since must be before until")
+                .with_details(json!({ "since": since, "until": until })));
+        }
+
+        let query = CompareQuery { symbols, since, until, interval, mode, base: self.base.map(|b| b.trim().to_uppercase()) };
+        if query.buckets() > MAX_BUCKETS {
+            return Err(ApiError::unprocessable(format!("range spans more than {} buckets; use a wider interval or a shorter range", MAX_BUCKETS))
+                .with_details(json!({ "buckets": query.buckets() })));
+        }
+        Ok(query)
+    }
+}
+
+/// Price series of several coins aligned on one bucket grid, as prices,
+/// normalized to 100 at the start, per-bucket percent returns, or ratios
+/// against a base coin. Each bucket holds its closing price.
+#[utoipa::path(
+    get,
+    path = "/compare",
+    tag = "coins",
+    params(CompareParams),
+    responses(
+        (status = 200, description = "One series per resolved symbol, aligned with timestamps", body = CompareResponse),
+        (status = 400, description = "Malformed query string or interval", body = ErrorBody),
+        (status = 404, description = "Unknown base coin", body = ErrorBody),
+        (status = 422, description = "Contradictory parameters or too many buckets", body = ErrorBody),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    ),
+)]
+pub async fn get_compare(
+    Query(params): Query<CompareParams>,
+    State(db): State<SharedDatabase>
+) -> Result<Json<CompareResponse>, ApiError> {
+    let query = params.into_query()?;
+    let response = db.compare(&query).await?;
+    if let Some(base) = &query.base
+        && response.unresolved.contains(base) {
+        return Err(ApiError::not_found(format!("Symbol not found: {}", base)));
     }
-}
\ No newline at end of file
+    Ok(Json(response))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct MoversQuery {
+    /// Period the change is measured over (default 24h)
+    #[param(inline)]
+    window: Option<MoverWindow>,
+    /// Only gainers (`up`) or only losers (`down`); both by default
+    #[param(inline)]
+    direction: Option<MoverDirection>,
+    /// Number of gainers and of losers, at most 100 (default 10)
+    limit: Option<u32>,
+    min_market_cap: Option<f64>,
+    /// Only consider the N largest coins by market cap
+    top: Option<u32>,
+}
+
+/// Biggest gainers and losers of the latest scrape over a window, measured
+/// against stored snapshots. For `24h`, coins without a snapshot a day old
+/// use the scraped 24h change.
+#[utoipa::path(
+    get,
+    path = "/movers",
+    tag = "coins",
+    params(MoversQuery),
+    responses(
+        (status = 200, description = "Gainers and losers by change over the window", body = MoversResponse),
+        (status = 400, description = "Malformed query string", body = ErrorBody),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    ),
+)]
+pub async fn get_movers(
+    Query(params): Query<MoversQuery>,
+    State(db): State<SharedDatabase>
+) -> Result<Json<MoversResponse>, ApiError> {
+    let filter = MoversFilter {
+        window: params.window.unwrap_or_default(),
+        direction: params.direction,
+        limit: params.limit.unwrap_or(10).min(100),
+        min_market_cap: params.min_market_cap,
+        top: params.top,
+    };
+
+    Ok(Json(db.get_movers(&filter).await?))
+}
@@ -0,0 +1,360 @@
+use axum::{
+    extract::{ws::{Message, WebSocket, WebSocketUpgrade}, State},
+    response::{sse::{Event, Sse}, IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::time::{interval_at, Instant, Interval};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::api::error::{ApiError, ErrorBody, Query};
+use crate::api::SharedDatabase;
+use crate::database::CoinQuery;
+use crate::model::{CoinResponse, ScrapeUpdate};
+
+/// How often a heartbeat is sent when no scrape lands.
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+/// How long a WebSocket client may take to accept a message before it is
+/// disconnected.
+pub const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often `serve` looks for runs saved by another process.
+pub const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Message pushed to stream subscribers, as JSON: an SSE event named after
+/// `type`, or a WebSocket text frame.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StreamMessage {
+    /// Every subscribed coin. Sent first, and again instead of a diff when
+    /// the client fell too far behind to be sent the runs it missed.
+    Snapshot {
+        run_id: Option<i64>,
+        ts_utc: Option<DateTime<Utc>>,
+        coins: Vec<CoinResponse>,
+        /// Whether this replaces diffs that were dropped
+        resync: bool,
+    },
+    /// Changes since the previous message, sent for each new scrape run.
+    Diff {
+        run_id: Option<i64>,
+        ts_utc: DateTime<Utc>,
+        /// Coins that are new to the subscription or whose values changed
+        changed: Vec<CoinResponse>,
+        /// Ids of coins that dropped out of the subscription
+        removed: Vec<u64>,
+    },
+    /// Sent when nothing else was for `HEARTBEAT_INTERVAL`.
+    Heartbeat {
+        ts_utc: DateTime<Utc>,
+    },
+}
+
+impl StreamMessage {
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::Snapshot { .. } => "snapshot",
+            Self::Diff { .. } => "diff",
+            Self::Heartbeat { .. } => "heartbeat",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct StreamQuery {
+    /// Comma-separated symbols to follow, e.g. `BTC,ETH`; not combinable with `top`
+    symbols: Option<String>,
+    /// Follow the top N coins by rank, at most 500 (default 100)
+    top: Option<u32>,
+}
+
+/// Which coins a client follows.
+#[derive(Debug, Clone)]
+enum Subscription {
+    Symbols(HashSet<String>),
+    Top(usize),
+}
+
+impl Subscription {
+    fn from_query(query: StreamQuery) -> Result<Self, ApiError> {
+        match (query.symbols, query.top) {
+            (Some(_), Some(_)) => Err(ApiError::unprocessable("symbols and top cannot be combined")),
+            (Some(symbols), None) => Ok(Self::Symbols(
+                symbols.split(',').map(|s| s.trim().to_uppercase()).filter(|s| !s.is_empty()).collect(),
+            )),
+            (None, top) => Ok(Self::Top(top.unwrap_or(100).min(500) as usize)),
+        }
+    }
+
+    fn coin_query(&self) -> CoinQuery {
+        match self {
+            Self::Symbols(symbols) => CoinQuery { symbols: Some(symbols.iter().cloned().collect()), limit: 500, ..CoinQuery::default() },
+            Self::Top(n) => CoinQuery { limit: *n as u32, ..CoinQuery::default() },
+        }
+    }
+
+    /// The subscribed coins of a run; `coins` are in rank order.
+    fn select(&self, coins: &[CoinResponse]) -> Vec<CoinResponse> {
+        match self {
+            Self::Symbols(symbols) => coins.iter().filter(|c| symbols.contains(&c.symbol.to_uppercase())).cloned().collect(),
+            Self::Top(n) => coins.iter().take(*n).cloned().collect(),
+        }
+    }
+}
+
+/// One client's view of the stream: what it subscribed to and what it was
+/// last sent, so later runs can be sent as diffs.
+struct Watcher {
+    updates: broadcast::Receiver<Arc<ScrapeUpdate>>,
+    subscription: Subscription,
+    sent: HashMap<u64, CoinResponse>,
+    heartbeat: Interval,
+    resync: bool,
+}
+
+impl Watcher {
+    /// Subscribe, then load the current coins, so no run saved in between
+    /// is missed.
+    async fn start(db: &SharedDatabase, subscription: Subscription) -> anyhow::Result<(Self, StreamMessage)> {
+        let updates = db.subscribe();
+        let page = db.list_coins(&subscription.coin_query()).await?;
+        let ts_utc = page.coins.first().map(|coin| coin.ts_utc);
+
+        let mut watcher = Self {
+            updates,
+            subscription,
+            sent: HashMap::new(),
+            heartbeat: interval_at(Instant::now() + HEARTBEAT_INTERVAL, HEARTBEAT_INTERVAL),
+            resync: false,
+        };
+        let first = watcher.snapshot(None, ts_utc, page.coins, false);
+        Ok((watcher, first))
+    }
+
+    fn snapshot(&mut self, run_id: Option<i64>, ts_utc: Option<DateTime<Utc>>, coins: Vec<CoinResponse>, resync: bool) -> StreamMessage {
+        self.sent = coins.iter().map(|coin| (coin.id, coin.clone())).collect();
+        StreamMessage::Snapshot { run_id, ts_utc, coins, resync }
+    }
+
+    fn diff(&mut self, update: &ScrapeUpdate) -> StreamMessage {
+        let selected = self.subscription.select(&update.coins);
+        let ids: HashSet<u64> = selected.iter().map(|coin| coin.id).collect();
+        let mut removed: Vec<u64> = self.sent.keys().filter(|id| !ids.contains(id)).copied().collect();
+        removed.sort_unstable();
+
+        let changed = selected.into_iter()
+            .filter(|coin| self.sent.get(&coin.id).is_none_or(|sent| !same_values(sent, coin)))
+            .collect();
+        self.sent = update.coins.iter().filter(|coin| ids.contains(&coin.id)).map(|coin| (coin.id, coin.clone())).collect();
+        StreamMessage::Diff { run_id: update.run_id, ts_utc: update.ts_utc, changed, removed }
+    }
+
+    /// Wait for the next message to send. Returns `None` once the server
+    /// shuts down.
+    async fn next(&mut self) -> Option<StreamMessage> {
+        loop {
+            tokio::select! {
+                update = self.updates.recv() => match update {
+                    Ok(update) => {
+                        self.heartbeat.reset();
+                        if std::mem::take(&mut self.resync) {
+                            let coins = self.subscription.select(&update.coins);
+                            return Some(self.snapshot(update.run_id, Some(update.ts_utc), coins, true));
+                        }
+                        return Some(self.diff(&update));
+                    }
+                    // Diffs only apply on top of what was sent, so a client that
+                    // missed runs gets the next one in full
+                    Err(RecvError::Lagged(missed)) => {
+                        tracing::debug!("Stream client missed {} runs, resyncing", missed);
+                        self.resync = true;
+                    }
+                    Err(RecvError::Closed) => return None,
+                },
+                _ = self.heartbeat.tick() => return Some(StreamMessage::Heartbeat { ts_utc: Utc::now() }),
+            }
+        }
+    }
+}
+
+/// Whether two snapshots of a coin differ only in their timestamp.
+fn same_values(a: &CoinResponse, b: &CoinResponse) -> bool {
+    a.symbol == b.symbol
+        && a.name == b.name
+        && a.rank == b.rank
+        && a.price_usd == b.price_usd
+        && a.market_cap_usd == b.market_cap_usd
+        && a.change_24h == b.change_24h
+}
+
+/// Push each new scrape run to the client, over Server-Sent Events or, when
+/// the request is a WebSocket upgrade, as WebSocket text frames. The first
+/// message is a snapshot of the subscribed coins and later runs are diffs
+/// against what was sent.
+#[utoipa::path(
+    get,
+    path = "/stream",
+    tag = "stream",
+    params(StreamQuery),
+    responses(
+        (status = 200, description = "Server-Sent Events, one per message, named after its type", content_type = "text/event-stream", body = StreamMessage),
+        (status = 101, description = "WebSocket upgrade; the same messages as text frames"),
+        (status = 400, description = "Malformed query string", body = ErrorBody),
+        (status = 422, description = "Both symbols and top given", body = ErrorBody),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    ),
+)]
+pub async fn stream(
+    ws: Option<WebSocketUpgrade>,
+    Query(params): Query<StreamQuery>,
+    State(db): State<SharedDatabase>,
+) -> Result<Response, ApiError> {
+    let (watcher, first) = Watcher::start(&db, Subscription::from_query(params)?).await?;
+
+    Ok(match ws {
+        Some(ws) => ws.on_upgrade(move |socket| websocket(socket, watcher, first)),
+        None => sse(watcher, first).into_response(),
+    })
+}
+
+fn sse(watcher: Watcher, first: StreamMessage) -> Sse<impl futures::Stream<Item = Result<Event, axum::Error>>> {
+    // The stream is only polled as fast as the client reads, so a slow client
+    // lags on the broadcast channel and is resynced rather than buffered for
+    let events = futures::stream::unfold((Some(first), watcher), |(first, mut watcher)| async move {
+        let message = match first {
+            Some(message) => message,
+            None => watcher.next().await?,
+        };
+        Some((Event::default().event(message.kind()).json_data(&message), (None, watcher)))
+    });
+    Sse::new(events)
+}
+
+async fn websocket(mut socket: WebSocket, mut watcher: Watcher, first: StreamMessage) {
+    let mut next = Some(first);
+    loop {
+        let message = match next.take() {
+            Some(message) => message,
+            None => tokio::select! {
+                message = watcher.next() => match message {
+                    Some(message) => message,
+                    None => break,
+                },
+                // Only closes matter; clients have nothing to send
+                incoming = socket.recv() => match incoming {
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                    Some(Ok(_)) => continue,
+                },
+            },
+        };
+
+        let text = match serde_json::to_string(&message) {
+            Ok(text) => text,
+            Err(err) => {
+                tracing::error!("Failed to encode stream message: {}", err);
+                continue;
+            }
+        };
+        match tokio::time::timeout(SEND_TIMEOUT, socket.send(Message::Text(text))).await {
+            Ok(Ok(())) => {}
+            Ok(Err(_)) => return,
+            Err(_) => {
+                tracing::warn!("Disconnecting stream client that stopped reading");
+                return;
+            }
+        }
+    }
+    let _ = socket.send(Message::Close(None)).await;
+}
+
+/// Publish runs that another process, such as a separate daemon, saves to
+/// the database. Runs on until the server stops.
+pub async fn poll_for_scrapes(db: SharedDatabase) {
+    let mut tick = tokio::time::interval(POLL_INTERVAL);
+    tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    loop {
+        tick.tick().await;
+        if let Err(err) = db.publish_latest().await {
+            tracing::warn!("Failed to check for new scrape runs: {:#}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::testing::{coin, temp_db};
+    use crate::database::updates::UPDATE_BUFFER;
+    use crate::model::CoinRow;
+
+    fn update(run_id: i64, coins: &[CoinRow]) -> ScrapeUpdate {
+        ScrapeUpdate { run_id: Some(run_id), ts_utc: coins[0].scraped_at, coins: coins.iter().map(CoinResponse::from).collect() }
+    }
+
+    fn ids(coins: &[CoinResponse]) -> Vec<u64> {
+        coins.iter().map(|coin| coin.id).collect()
+    }
+
+    #[tokio::test]
+    async fn sends_a_snapshot_then_diffs() -> anyhow::Result<()> {
+        let (_dir, db) = temp_db().await?;
+        let first = "2026-01-01T00:00:00Z";
+        db.save_coins(&[coin(1, "BTC", 1, 100.0, first), coin(1027, "ETH", 2, 10.0, first), coin(52, "XRP", 3, 1.0, first)]).await?;
+        let db: SharedDatabase = Arc::new(db);
+
+        let (mut watcher, message) = Watcher::start(&db, Subscription::Top(2)).await?;
+        let StreamMessage::Snapshot { coins, resync, .. } = message else {
+            panic!("expected a snapshot, got {:?}", message);
+        };
+        assert_eq!(ids(&coins), vec![1, 1027]);
+        assert!(!resync);
+
+        // BTC unchanged, ETH moves, XRP overtakes it out of the top 2
+        let second = "2026-01-01T01:00:00Z";
+        assert!(db.publish(update(2, &[coin(1, "BTC", 1, 100.0, second), coin(52, "XRP", 2, 1.0, second), coin(1027, "ETH", 3, 9.0, second)])));
+        let message = watcher.next().await.expect("an update");
+        let StreamMessage::Diff { run_id, changed, removed, .. } = message else {
+            panic!("expected a diff, got {:?}", message);
+        };
+        assert_eq!(run_id, Some(2));
+        assert_eq!(ids(&changed), vec![52]);
+        assert_eq!(removed, vec![1027]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn resyncs_after_missing_runs() -> anyhow::Result<()> {
+        let (_dir, db) = temp_db().await?;
+        let db: SharedDatabase = Arc::new(db);
+        let (mut watcher, _) = Watcher::start(&db, Subscription::Symbols(HashSet::from(["BTC".to_string()]))).await?;
+
+        let runs = UPDATE_BUFFER as i64 + 2;
+        for run in 1..=runs {
+            let at = (chrono::DateTime::<Utc>::UNIX_EPOCH + chrono::Duration::hours(run)).to_rfc3339();
+            assert!(db.publish(update(run, &[coin(1, "BTC", 1, run as f64, &at), coin(1027, "ETH", 2, 1.0, &at)])));
+        }
+
+        // The oldest runs were dropped, so the first one still buffered comes in full
+        let message = watcher.next().await.expect("an update");
+        let StreamMessage::Snapshot { run_id, coins, resync, .. } = message else {
+            panic!("expected a snapshot, got {:?}", message);
+        };
+        assert!(resync);
+        assert_eq!(run_id, Some(runs - UPDATE_BUFFER as i64 + 1));
+        assert_eq!(ids(&coins), vec![1]);
+
+        // ...and the rest are diffs against it again
+        let message = watcher.next().await.expect("an update");
+        let StreamMessage::Diff { run_id, changed, removed, .. } = message else {
+            panic!("expected a diff, got {:?}", message);
+        };
+        assert_eq!(run_id, Some(runs - UPDATE_BUFFER as i64 + 2));
+        assert_eq!(ids(&changed), vec![1]);
+        assert!(removed.is_empty());
+        Ok(())
+    }
+}
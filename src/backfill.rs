@@ -0,0 +1,198 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+use crate::daemon::schedule::Schedule;
+use crate::database::{Database, Source};
+use crate::import::{replay_csv, ColumnMapping, ImportReport};
+
+/// A run of consecutive scheduled slots with no data.
+#[derive(Debug, Clone, Serialize)]
+pub struct Gap {
+    /// First missed slot
+    pub start: DateTime<Utc>,
+    /// Slot after the last missed one
+    pub end: DateTime<Utc>,
+    pub missed_slots: u64,
+}
+
+/// Scheduled slots in `[since, until)` that have no snapshot or rollup, grouped
+/// into gaps. A slot counts as covered by any data between it and the next
+/// slot; slots whose window hasn't closed by `until` are not checked.
+pub async fn find_gaps(db: &Database, schedule: &Schedule, since: DateTime<Utc>, until: DateTime<Utc>) -> Result<Vec<Gap>> {
+    let mut windows = Vec::new();
+    let mut slot = schedule.next_after(since - TimeDelta::milliseconds(1))?;
+    loop {
+        let next = schedule.next_after(slot)?;
+        if next > until {
+            break;
+        }
+        windows.push((slot, next));
+        slot = next;
+    }
+    let (Some(first), Some(last)) = (windows.first(), windows.last()) else {
+        return Ok(Vec::new());
+    };
+    let spans = db.covered_spans(first.0, last.1).await?;
+
+    // Spans are ordered by start, so one pass finds, for each window, the
+    // furthest end of any span starting before the window closes
+    let mut gaps: Vec<Gap> = Vec::new();
+    let mut spans = spans.into_iter().peekable();
+    let mut covered_until = DateTime::<Utc>::MIN_UTC;
+    for (slot, next) in windows {
+        while let Some((_, end)) = spans.next_if(|(start, _)| *start < next) {
+            covered_until = covered_until.max(end);
+        }
+        if covered_until > slot {
+            continue;
+        }
+        match gaps.last_mut() {
+            Some(gap) if gap.end == slot => {
+                gap.end = next;
+                gap.missed_slots += 1;
+            }
+            _ => gaps.push(Gap { start: slot, end: next, missed_slots: 1 }),
+        }
+    }
+    Ok(gaps)
+}
+
+/// CSV files to replay: files as given, directories expanded to the `.csv`,
+/// `.csv.gz` and `.csv.zst` files directly inside them, in name order.
+pub fn archive_files(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for path in paths {
+        if !path.is_dir() {
+            files.push(path.clone());
+            continue;
+        }
+        let mut entries = Vec::new();
+        for entry in std::fs::read_dir(path).with_context(|| format!("failed to read {}", path.display()))? {
+            let entry = entry?.path();
+            if entry.is_file() && is_csv(&entry) {
+                entries.push(entry);
+            }
+        }
+        if entries.is_empty() {
+            bail!("no CSV files in {}", path.display());
+        }
+        entries.sort();
+        files.extend(entries);
+    }
+    Ok(files)
+}
+
+fn is_csv(path: &Path) -> bool {
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+    [".csv", ".csv.gz", ".csv.zst"].iter().any(|ext| name.ends_with(ext))
+}
+
+/// Fill `gaps` from CSV archives such as those written by `scrape --csv`.
+/// Only rows scraped inside a gap are saved, tagged as backfilled; snapshots
+/// already in the database are skipped, so replaying twice is harmless.
+pub async fn backfill(db: &Database, gaps: &[Gap], files: &[PathBuf], mapping: &ColumnMapping) -> Result<ImportReport> {
+    // Gaps come from `find_gaps` in time order and never overlap
+    let in_gap = |ts: DateTime<Utc>| {
+        let i = gaps.partition_point(|gap| gap.end <= ts);
+        gaps.get(i).is_some_and(|gap| gap.start <= ts)
+    };
+
+    let mut report = ImportReport::default();
+    for file in files {
+        replay_csv(db, file, mapping, &mut report, in_gap, Source::Backfill).await?;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::testing::{coin, temp_db, ts};
+    use crate::database::SnapshotFilter;
+    use futures::TryStreamExt;
+    use std::time::Duration;
+
+    fn hourly() -> Schedule {
+        Schedule::interval(Duration::from_secs(3600)).expect("valid interval")
+    }
+
+    fn spans(gaps: &[Gap]) -> Vec<(DateTime<Utc>, DateTime<Utc>, u64)> {
+        gaps.iter().map(|gap| (gap.start, gap.end, gap.missed_slots)).collect()
+    }
+
+    /// Scrapes at 00:05, 01:10, 04:00, 05:30 and 07:59, so the 02:00 and
+    /// 03:00 slots and the 06:00 slot are missed.
+    async fn seed(db: &Database) -> Result<()> {
+        for at in ["2026-01-01T00:05:00Z", "2026-01-01T01:10:00Z", "2026-01-01T04:00:00Z", "2026-01-01T05:30:00Z", "2026-01-01T07:59:00Z"] {
+            db.save_coins(&[coin(1, "BTC", 1, 100.0, at)]).await?;
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn groups_missed_slots_into_gaps() -> Result<()> {
+        let (_dir, db) = temp_db().await?;
+        seed(&db).await?;
+
+        let gaps = find_gaps(&db, &hourly(), ts("2026-01-01T00:00:00Z"), ts("2026-01-01T08:00:00Z")).await?;
+        assert_eq!(spans(&gaps), vec![
+            (ts("2026-01-01T02:00:00Z"), ts("2026-01-01T04:00:00Z"), 2),
+            (ts("2026-01-01T06:00:00Z"), ts("2026-01-01T07:00:00Z"), 1),
+        ]);
+
+        // The 08:00 slot's window is still open at 08:30, so it isn't a gap yet
+        let gaps = find_gaps(&db, &hourly(), ts("2026-01-01T05:00:00Z"), ts("2026-01-01T08:30:00Z")).await?;
+        assert_eq!(spans(&gaps), vec![(ts("2026-01-01T06:00:00Z"), ts("2026-01-01T07:00:00Z"), 1)]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn an_empty_database_is_one_gap() -> Result<()> {
+        let (_dir, db) = temp_db().await?;
+        let gaps = find_gaps(&db, &hourly(), ts("2026-01-01T00:30:00Z"), ts("2026-01-01T04:00:00Z")).await?;
+        assert_eq!(spans(&gaps), vec![(ts("2026-01-01T01:00:00Z"), ts("2026-01-01T04:00:00Z"), 3)]);
+
+        assert!(find_gaps(&db, &hourly(), ts("2026-01-01T00:30:00Z"), ts("2026-01-01T01:30:00Z")).await?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn fills_only_the_gaps_and_only_once() -> Result<()> {
+        let (dir, db) = temp_db().await?;
+        seed(&db).await?;
+        let archive = dir.path().join("archive.csv");
+        std::fs::write(&archive,
+            "id,rank,name,symbol,price_usd,market_cap_usd,chg24h_pct,scraped_at\n\
+             1,1,Bitcoin,BTC,99,,,2026-01-01T01:10:00Z\n\
+             1,1,Bitcoin,BTC,102,,,2026-01-01T02:00:00Z\n\
+             1,1,Bitcoin,BTC,103,,,2026-01-01T03:15:00Z\n\
+             1027,2,Ethereum,ETH,10,,,2026-01-01T03:15:00Z\n\
+             1,1,Bitcoin,BTC,104,,,2026-01-01T05:00:00Z\n\
+             1,1,Bitcoin,BTC,106,,,2026-01-01T06:59:59Z\n"
+        )?;
+        let (since, until) = (ts("2026-01-01T00:00:00Z"), ts("2026-01-01T08:00:00Z"));
+        let gaps = find_gaps(&db, &hourly(), since, until).await?;
+
+        let first = backfill(&db, &gaps, std::slice::from_ref(&archive), &ColumnMapping::default()).await?;
+        assert_eq!((first.rows_read, first.inserted, first.out_of_range, first.runs), (6, 4, 2, 3));
+        assert!(find_gaps(&db, &hourly(), since, until).await?.is_empty());
+
+        // Replaying the same archive over the same gaps adds nothing
+        let second = backfill(&db, &gaps, std::slice::from_ref(&archive), &ColumnMapping::default()).await?;
+        assert_eq!((second.inserted, second.skipped, second.runs), (0, 4, 0));
+
+        let filter = SnapshotFilter { source: Some(Source::Backfill), ..SnapshotFilter::default() };
+        let backfilled: Vec<_> = db.stream_snapshots(&filter).try_collect().await?;
+        let backfilled: Vec<_> = backfilled.iter().map(|row| (row.symbol.as_str(), row.scraped_at)).collect();
+        assert_eq!(backfilled, vec![
+            ("BTC", ts("2026-01-01T02:00:00Z")),
+            ("BTC", ts("2026-01-01T03:15:00Z")),
+            ("ETH", ts("2026-01-01T03:15:00Z")),
+            ("BTC", ts("2026-01-01T06:59:59Z")),
+        ]);
+        assert_eq!(db.get_snapshot_count().await?, 9);
+        Ok(())
+    }
+}
@@ -0,0 +1,490 @@
+use anyhow::{anyhow, bail, Context, Result};
+use axum::http::HeaderValue;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+
+use crate::daemon::schedule::{format_duration, parse_duration, Schedule};
+use crate::database::RetentionPolicy;
+use crate::export::sink::Compression;
+
+/// Prefix of environment variables that override config keys, e.g.
+/// `COINBASE_SCRAPER_FETCH_BATCH_SIZE` sets `fetch.batch_size`.
+pub const ENV_PREFIX: &str = "COINBASE_SCRAPER_";
+/// Environment variable naming the config file.
+pub const CONFIG_ENV: &str = "COINBASE_SCRAPER_CONFIG";
+/// Config file read from the working directory when no path is given.
+pub const DEFAULT_CONFIG_FILE: &str = "coinbase_scraper.toml";
+
+const SECTIONS: [&str; 6] = ["database", "fetch", "server", "retention", "schedule", "backup"];
+
+/// Every setting, layered as: built-in defaults, then the TOML config file,
+/// then `COINBASE_SCRAPER_*` environment variables. Command-line flags are
+/// applied on top by the CLI.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub database: DatabaseConfig,
+    pub fetch: FetchConfig,
+    pub server: ServerConfig,
+    pub retention: RetentionPolicy,
+    pub schedule: ScheduleConfig,
+    pub backup: BackupConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    pub url: String,
+    pub max_connections: u32,
+    /// How long a writer waits for a locked database before failing
+    pub busy_timeout_ms: u64,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            url: "sqlite:cmc.db".to_string(),
+            max_connections: 10,
+            busy_timeout_ms: 5000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FetchConfig {
+    pub pages: u32,
+    /// Pages fetched concurrently
+    pub batch_size: u32,
+    /// Pause between batches
+    pub pause_ms: u64,
+}
+
+impl Default for FetchConfig {
+    fn default() -> Self {
+        Self {
+            pages: 10,
+            batch_size: 10,
+            pause_ms: 300,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub bind: SocketAddr,
+    /// Allowed CORS origins; `*` allows any origin
+    #[serde(deserialize_with = "string_or_list")]
+    pub cors_origins: Vec<String>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind: SocketAddr::from(([0, 0, 0, 0], 8080)),
+            cors_origins: vec!["*".to_string()],
+        }
+    }
+}
+
+impl ServerConfig {
+    pub fn cors_layer(&self) -> Result<CorsLayer> {
+        if self.cors_origins.iter().any(|o| o == "*") {
+            return Ok(CorsLayer::permissive());
+        }
+        Ok(CorsLayer::new()
+            .allow_origin(AllowOrigin::list(self.origins()?))
+            .allow_methods(Any)
+            .allow_headers(Any)
+            .expose_headers(Any))
+    }
+
+    fn origins(&self) -> Result<Vec<HeaderValue>> {
+        self.cors_origins.iter()
+            .filter(|o| *o != "*")
+            .map(|o| HeaderValue::from_str(o).map_err(|_| anyhow!("server.cors_origins: invalid origin '{}'", o)))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScheduleConfig {
+    /// Daemon scrape interval, e.g. "15m"
+    #[serde(with = "optional_duration")]
+    pub interval: Option<Duration>,
+    /// Daemon cron expression, in UTC
+    pub cron: Option<String>,
+    #[serde(with = "duration")]
+    pub max_backoff: Duration,
+    /// Run compaction with the `[retention]` policy this often
+    #[serde(with = "optional_duration")]
+    pub compact_every: Option<Duration>,
+    /// Back up the database into `[backup].dir` this often
+    #[serde(with = "optional_duration")]
+    pub backup_every: Option<Duration>,
+}
+
+impl Default for ScheduleConfig {
+    fn default() -> Self {
+        Self {
+            interval: None,
+            cron: None,
+            max_backoff: Duration::from_secs(30 * 60),
+            compact_every: None,
+            backup_every: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BackupConfig {
+    /// Where `db backup` and scheduled backups are written
+    pub dir: PathBuf,
+    /// `none`, `gzip[:level]` or `zstd[:level]`
+    #[serde(with = "compression")]
+    pub compress: Compression,
+    /// Scheduled backups to keep; older ones are deleted
+    pub keep: Option<usize>,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            dir: PathBuf::from("backups"),
+            compress: Compression::None,
+            keep: Some(7),
+        }
+    }
+}
+
+impl ScheduleConfig {
+    /// The scrape schedule, if one is configured.
+    pub fn scrape_schedule(&self) -> Result<Option<Schedule>> {
+        match (self.interval, &self.cron) {
+            (Some(_), Some(_)) => bail!("schedule.interval and schedule.cron are mutually exclusive"),
+            (Some(every), None) => Schedule::interval(every).context("schedule.interval").map(Some),
+            (None, Some(expr)) => Schedule::cron(expr).context("schedule.cron").map(Some),
+            (None, None) => Ok(None),
+        }
+    }
+}
+
+impl Config {
+    /// Load `path`, or `$COINBASE_SCRAPER_CONFIG`, or `coinbase_scraper.toml`
+    /// if it exists, then apply environment overrides and validate.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let path = path.map(Path::to_path_buf)
+            .or_else(|| std::env::var_os(CONFIG_ENV).map(PathBuf::from))
+            .or_else(|| Some(PathBuf::from(DEFAULT_CONFIG_FILE)).filter(|p| p.exists()));
+
+        let table = match &path {
+            Some(path) => {
+                let text = std::fs::read_to_string(path)
+                    .with_context(|| format!("failed to read config file {}", path.display()))?;
+                toml::from_str(&text).with_context(|| format!("invalid config file {}", path.display()))?
+            }
+            None => toml::Table::new(),
+        };
+        let source = path.map(|p| p.display().to_string()).unwrap_or_else(|| "defaults".to_string());
+        Self::from_table(table, std::env::vars(), &source)
+    }
+
+    /// Build a config from a parsed TOML table plus `COINBASE_SCRAPER_*`
+    /// overrides taken from `env`. Errors name the file or variable that set
+    /// the offending key.
+    pub fn from_table<I>(mut table: toml::Table, env: I, source: &str) -> Result<Self>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let mut origins = Origins::new(&table, source);
+        let defaults = toml::Table::try_from(Config::default())?;
+        for (name, value) in env {
+            let Some(key) = name.strip_prefix(ENV_PREFIX) else { continue };
+            if name == CONFIG_ENV {
+                continue;
+            }
+            let key = key.to_ascii_lowercase();
+            let (section, field) = SECTIONS.iter()
+                .find_map(|s| key.strip_prefix(s).and_then(|rest| rest.strip_prefix('_')).map(|f| (*s, f)))
+                .ok_or_else(|| anyhow!("{}: unknown config section, expected one of: {}", name, SECTIONS.join(", ")))?;
+            let entry = table.entry(section).or_insert_with(|| toml::Value::Table(toml::Table::new()));
+            let Some(entry) = entry.as_table_mut() else {
+                bail!("{}: {} is not a table", source, section);
+            };
+            let string_typed = defaults.get(section)
+                .and_then(|s| s.get(field))
+                .is_some_and(toml::Value::is_str);
+            entry.insert(field.to_string(), env_value(&value, string_typed));
+            origins.env.insert(format!("{}.{}", section, field), name);
+        }
+
+        // Errors name the key that failed, and the variable if it came from the environment
+        let config: Config = serde_path_to_error::deserialize(toml::Value::Table(table)).map_err(|err| {
+            let key = err.path().to_string();
+            anyhow!("{}: {}: {}", origins.of(&key), key, err.inner().message())
+        })?;
+        config.check().map_err(|(key, err)| err.context(format!("invalid configuration ({})", origins.of(key))))?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        self.check().map_err(|(_, err)| err)
+    }
+
+    /// Validate, returning the key or section at fault with the error.
+    fn check(&self) -> std::result::Result<(), (&'static str, anyhow::Error)> {
+        let at = |key: &'static str| move |err: anyhow::Error| (key, err);
+        if self.database.max_connections == 0 {
+            return Err(("database.max_connections", anyhow!("database.max_connections must be at least 1")));
+        }
+        if self.fetch.pages == 0 {
+            return Err(("fetch.pages", anyhow!("fetch.pages must be at least 1")));
+        }
+        if self.fetch.batch_size == 0 {
+            return Err(("fetch.batch_size", anyhow!("fetch.batch_size must be at least 1")));
+        }
+        self.server.origins().map_err(at("server.cors_origins"))?;
+        self.retention.validate().map_err(at("retention"))?;
+        self.schedule.scrape_schedule().map_err(at("schedule"))?;
+        if let Some(every) = self.schedule.compact_every {
+            Schedule::interval(every).context("schedule.compact_every").map_err(at("schedule.compact_every"))?;
+        }
+        if let Some(every) = self.schedule.backup_every {
+            Schedule::interval(every).context("schedule.backup_every").map_err(at("schedule.backup_every"))?;
+        }
+        if self.backup.keep == Some(0) {
+            return Err(("backup.keep", anyhow!("backup.keep must be at least 1")));
+        }
+        Ok(())
+    }
+
+    /// The effective configuration as TOML.
+    pub fn to_toml(&self) -> Result<String> {
+        Ok(toml::to_string_pretty(self)?)
+    }
+}
+
+/// Where each key was set: the config file or an environment variable.
+struct Origins<'a> {
+    file: &'a str,
+    file_keys: Vec<String>,
+    env: HashMap<String, String>,
+}
+
+impl<'a> Origins<'a> {
+    fn new(table: &toml::Table, file: &'a str) -> Self {
+        let file_keys = table.iter()
+            .flat_map(|(section, value)| match value.as_table() {
+                Some(fields) => fields.keys().map(|field| format!("{}.{}", section, field)).collect(),
+                None => vec![section.clone()],
+            })
+            .collect();
+        Self { file, file_keys, env: HashMap::new() }
+    }
+
+    /// The variables that set `key`, or any key of the section `key`
+    /// names; failing that, the file if it did, or the defaults.
+    fn of(&self, key: &str) -> String {
+        let within = |k: &String| k == key || k.strip_prefix(key).is_some_and(|rest| rest.starts_with('.'));
+        let mut vars: Vec<&str> = self.env.iter().filter(|(k, _)| within(k)).map(|(_, v)| v.as_str()).collect();
+        vars.sort_unstable();
+        if !vars.is_empty() {
+            vars.join(", ")
+        } else if self.file_keys.iter().any(within) {
+            self.file.to_string()
+        } else {
+            "defaults".to_string()
+        }
+    }
+}
+
+/// Environment values are taken as they are for keys that hold a string, so
+/// `COINBASE_SCRAPER_BACKUP_DIR=2024` names a directory. Others are read as
+/// TOML scalars or arrays where they parse as one (`10`, `true`,
+/// `["a", "b"]`) and as plain strings otherwise.
+fn env_value(value: &str, string_typed: bool) -> toml::Value {
+    if string_typed {
+        return toml::Value::String(value.to_string());
+    }
+    toml::from_str::<toml::Table>(&format!("v = {}", value))
+        .ok()
+        .and_then(|mut t| t.remove("v"))
+        .unwrap_or_else(|| toml::Value::String(value.to_string()))
+}
+
+/// Accept a list or a comma-separated string.
+fn string_or_list<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StringOrList {
+        String(String),
+        List(Vec<String>),
+    }
+    Ok(match StringOrList::deserialize(deserializer)? {
+        StringOrList::String(s) => s.split(',').map(|o| o.trim().to_string()).filter(|o| !o.is_empty()).collect(),
+        StringOrList::List(list) => list,
+    })
+}
+
+/// Durations are written as `90s`, `15m`, `1h` or `1d`; bare integers are seconds.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DurationValue {
+    Seconds(u64),
+    Text(String),
+}
+
+impl DurationValue {
+    fn into_duration<E: serde::de::Error>(self) -> Result<Duration, E> {
+        match self {
+            Self::Seconds(secs) => Ok(Duration::from_secs(secs)),
+            Self::Text(text) => parse_duration(&text).map_err(E::custom),
+        }
+    }
+}
+
+mod duration {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(value: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format_duration(*value))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        DurationValue::deserialize(deserializer)?.into_duration()
+    }
+}
+
+mod optional_duration {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(value: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(value) => serializer.serialize_some(&format_duration(*value)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Duration>, D::Error> {
+        Option::<DurationValue>::deserialize(deserializer)?.map(DurationValue::into_duration).transpose()
+    }
+}
+
+mod compression {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(value: &Compression, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(value)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Compression, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    fn file(text: &str) -> toml::Table {
+        toml::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn env_overrides_file_overrides_defaults() -> Result<()> {
+        let table = file("[fetch]\npages = 3\nbatch_size = 2\n[schedule]\ninterval = \"15m\"\n");
+        let config = Config::from_table(table, env(&[
+            ("COINBASE_SCRAPER_FETCH_PAGES", "5"),
+            ("COINBASE_SCRAPER_SCHEDULE_MAX_BACKOFF", "600"),
+            ("OTHER_FETCH_PAGES", "7"),
+        ]), "scraper.toml")?;
+
+        assert_eq!(config.fetch.pages, 5);
+        assert_eq!(config.fetch.batch_size, 2);
+        assert_eq!(config.fetch.pause_ms, FetchConfig::default().pause_ms);
+        assert_eq!(config.schedule.interval, Some(Duration::from_secs(900)));
+        assert_eq!(config.schedule.max_backoff, Duration::from_secs(600));
+        Ok(())
+    }
+
+    #[test]
+    fn env_values_for_string_keys_stay_strings() -> Result<()> {
+        let config = Config::from_table(toml::Table::new(), env(&[
+            ("COINBASE_SCRAPER_BACKUP_DIR", "2024"),
+            ("COINBASE_SCRAPER_DATABASE_URL", "true"),
+            ("COINBASE_SCRAPER_BACKUP_KEEP", "3"),
+            ("COINBASE_SCRAPER_SERVER_CORS_ORIGINS", "https://a.example, https://b.example"),
+        ]), "defaults")?;
+
+        assert_eq!(config.backup.dir, PathBuf::from("2024"));
+        assert_eq!(config.database.url, "true");
+        assert_eq!(config.backup.keep, Some(3));
+        assert_eq!(config.server.cors_origins, vec!["https://a.example", "https://b.example"]);
+        Ok(())
+    }
+
+    #[test]
+    fn errors_name_the_offending_key_and_its_source() {
+        let err = |table: &str, vars: &[(&str, &str)]| {
+            format!("{:#}", Config::from_table(file(table), env(vars), "scraper.toml").unwrap_err())
+        };
+
+        let message = err("", &[("COINBASE_SCRAPER_FETCH_PAGES", "many")]);
+        assert!(message.starts_with("COINBASE_SCRAPER_FETCH_PAGES: fetch.pages: "), "{}", message);
+
+        let message = err("[fetch]\npages = \"many\"\n", &[]);
+        assert!(message.starts_with("scraper.toml: fetch.pages: "), "{}", message);
+
+        let message = err("[fetch]\npages = 3\n", &[("COINBASE_SCRAPER_FETCH_PAGES", "0")]);
+        assert_eq!(message, "invalid configuration (COINBASE_SCRAPER_FETCH_PAGES): fetch.pages must be at least 1");
+
+        let message = err("[fetch]\nbatch_size = 0\n", &[("COINBASE_SCRAPER_FETCH_PAGES", "2")]);
+        assert_eq!(message, "invalid configuration (scraper.toml): fetch.batch_size must be at least 1");
+
+        let message = err("[retention]\nraw_days = 100\n", &[]);
+        assert!(message.starts_with("invalid configuration (scraper.toml): retention.hourly_days"), "{}", message);
+
+        let message = err("[schedule]\ncron = \"0 * * * *\"\n", &[("COINBASE_SCRAPER_SCHEDULE_INTERVAL", "1h")]);
+        assert!(message.starts_with("invalid configuration (COINBASE_SCRAPER_SCHEDULE_INTERVAL): "), "{}", message);
+
+        let message = err("[backup]\nkeep = 0\n", &[]);
+        assert!(message.starts_with("invalid configuration (scraper.toml): backup.keep"), "{}", message);
+
+        let message = err("", &[("COINBASE_SCRAPER_NETWORK_PROXY", "x")]);
+        assert!(message.starts_with("COINBASE_SCRAPER_NETWORK_PROXY: unknown config section"), "{}", message);
+
+        let message = err("[fetch]\npagez = 3\n", &[]);
+        assert!(message.starts_with("scraper.toml: fetch.pagez: unknown field"), "{}", message);
+    }
+
+    #[test]
+    fn show_prints_the_effective_config_as_toml() -> Result<()> {
+        let config = Config::from_table(file("[schedule]\ninterval = 900\n"), env(&[("COINBASE_SCRAPER_BACKUP_COMPRESS", "zstd")]), "scraper.toml")?;
+        let shown = config.to_toml()?;
+
+        let parsed: toml::Table = toml::from_str(&shown)?;
+        assert_eq!(parsed["schedule"]["interval"].as_str(), Some("15m"));
+        assert_eq!(parsed["schedule"]["max_backoff"].as_str(), Some("30m"));
+        assert_eq!(parsed["backup"]["compress"].as_str(), Some("zstd:3"));
+        assert_eq!(parsed["database"]["url"].as_str(), Some("sqlite:cmc.db"));
+        for section in SECTIONS {
+            assert!(parsed.contains_key(section), "{} missing from\n{}", section, shown);
+        }
+
+        // What it prints loads back to the same configuration
+        let reloaded = Config::from_table(parsed, env(&[]), "shown.toml")?;
+        assert_eq!(reloaded.to_toml()?, shown);
+        Ok(())
+    }
+}
@@ -0,0 +1,190 @@
+pub mod schedule;
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::config::{BackupConfig, FetchConfig};
+use crate::database::{Database, RetentionPolicy, RunStatus};
+use crate::export::append::CsvAppender;
+use crate::scrape::run_scrape;
+use schedule::{format_duration, Schedule};
+
+/// Resolves on Ctrl-C or, on Unix, SIGTERM as sent by systemd and Docker.
+/// The SIGTERM handler is installed right away, so from then on the signal
+/// no longer kills the process mid-run.
+fn shutdown_signal() -> std::io::Result<impl Future<Output = ()>> {
+    #[cfg(unix)]
+    let mut terminate = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
+    Ok(async move {
+        #[cfg(unix)]
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = terminate.recv() => {}
+        }
+        #[cfg(not(unix))]
+        let _ = tokio::signal::ctrl_c().await;
+    })
+}
+
+/// Exponential delay applied after consecutive failed runs.
+#[derive(Debug, Clone, Copy)]
+pub struct Backoff {
+    pub initial: Duration,
+    pub max: Duration,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_secs(30),
+            max: Duration::from_secs(30 * 60),
+        }
+    }
+}
+
+impl Backoff {
+    /// Delay before the next attempt after `failures` consecutive failures.
+    pub fn delay(&self, failures: u32) -> Duration {
+        let factor = 2u32.saturating_pow(failures.saturating_sub(1));
+        self.initial.saturating_mul(factor).min(self.max)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DaemonOptions {
+    pub schedule: Schedule,
+    pub fetch: FetchConfig,
+    pub csv: Option<CsvAppender>,
+    pub backoff: Backoff,
+    /// Run `Database::compact` with this policy on its own schedule
+    pub compaction: Option<(Schedule, RetentionPolicy)>,
+    /// Back up into the configured directory on its own schedule
+    pub backup: Option<(Schedule, BackupConfig)>,
+}
+
+/// Long-running scrape loop. Runs are strictly sequential: a slot that comes
+/// up while a run is still in progress is skipped, never started in parallel.
+pub struct Daemon {
+    db: Arc<Database>,
+    options: DaemonOptions,
+}
+
+impl Daemon {
+    pub fn new(db: Arc<Database>, options: DaemonOptions) -> Self {
+        Self { db, options }
+    }
+
+    /// Scrape on schedule until Ctrl-C or SIGTERM. A run in progress is
+    /// allowed to finish before the daemon exits.
+    pub async fn run(&self) -> Result<()> {
+        let mut shutdown = std::pin::pin!(shutdown_signal()?);
+        let mut failures = 0u32;
+        let mut next = self.options.schedule.next_after(Utc::now())?;
+        let mut next_compaction = match &self.options.compaction {
+            Some((schedule, _)) => Some(schedule.next_after(Utc::now())?),
+            None => None,
+        };
+        let mut next_backup = match &self.options.backup {
+            Some((schedule, _)) => Some(schedule.next_after(Utc::now())?),
+            None => None,
+        };
+        tracing::info!("Daemon started, scraping {} pages {}", self.options.fetch.pages, self.options.schedule);
+
+        loop {
+            tracing::info!("Next scrape at {}", next.to_rfc3339());
+            tokio::select! {
+                _ = sleep_until(next) => {}
+                _ = &mut shutdown => {
+                    tracing::info!("Shutting down");
+                    return Ok(());
+                }
+            }
+
+            let start = Instant::now();
+            match self.scrape_once().await {
+                Ok(count) => {
+                    failures = 0;
+                    tracing::info!("Saved {} coins in {:.2}s", count, start.elapsed().as_secs_f64());
+                }
+                Err(err) => {
+                    failures += 1;
+                    tracing::error!("Scrape failed ({} in a row): {:#}", failures, err);
+                }
+            }
+
+            if let (Some((schedule, policy)), Some(due)) = (&self.options.compaction, next_compaction)
+                && Utc::now() >= due {
+                match self.db.compact(policy, Utc::now(), false).await {
+                    Ok(report) => tracing::info!("Compacted database: {:?}", report),
+                    Err(err) => tracing::error!("Compaction failed: {:#}", err),
+                }
+                next_compaction = Some(schedule.next_after(Utc::now())?);
+            }
+
+            // After compaction, so the backup holds the compacted data
+            if let (Some((schedule, config)), Some(due)) = (&self.options.backup, next_backup)
+                && Utc::now() >= due {
+                match self.db.backup_to_dir(config, Utc::now()).await {
+                    Ok(report) => {
+                        tracing::info!("Backed up database to {} ({} bytes)", report.path.display(), report.bytes);
+                        for path in &report.removed {
+                            tracing::info!("Removed old backup {}", path.display());
+                        }
+                    }
+                    Err(err) => tracing::error!("Backup failed: {:#}", err),
+                }
+                next_backup = Some(schedule.next_after(Utc::now())?);
+            }
+
+            let now = Utc::now();
+            let slot = self.options.schedule.next_after(next)?;
+            next = self.options.schedule.next_after(now)?;
+            if next > slot {
+                tracing::warn!("Run overran its slot, skipping to {}", next.to_rfc3339());
+            }
+            if failures > 0 {
+                let delay = self.options.backoff.delay(failures);
+                let retry = now + chrono::TimeDelta::from_std(delay)?;
+                if retry > next {
+                    tracing::warn!("Backing off for {}", format_duration(delay));
+                    next = retry;
+                }
+            }
+        }
+    }
+
+    async fn scrape_once(&self) -> Result<usize> {
+        let summary = run_scrape(&self.db, &self.options.fetch, self.options.csv.as_ref()).await?;
+        if let Some(csv) = &summary.csv
+            && let Some(rotated) = &csv.outcome.rotated_to {
+            tracing::info!("Rotated previous CSV to {}", rotated.display());
+        }
+        match summary.run.status {
+            RunStatus::Failed => bail!("run {}: {}", summary.run.id, summary.errors.join("; ")),
+            RunStatus::Partial => tracing::warn!("Run {} was partial: {}", summary.run.id, summary.errors.join("; ")),
+            _ => {}
+        }
+        Ok(summary.run.coins_saved as usize)
+    }
+}
+
+async fn sleep_until(at: DateTime<Utc>) {
+    let wait = (at - Utc::now()).to_std().unwrap_or_default();
+    tokio::time::sleep(wait).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let backoff = Backoff::default();
+        let delays: Vec<u64> = (1..=8).map(|n| backoff.delay(n).as_secs()).collect();
+        assert_eq!(delays, vec![30, 60, 120, 240, 480, 960, 1800, 1800]);
+        assert_eq!(backoff.delay(0), backoff.initial);
+        assert_eq!(backoff.delay(u32::MAX), backoff.max);
+    }
+}
@@ -0,0 +1,155 @@
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, DurationRound, TimeDelta, Utc};
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// When the daemon runs a job.
+#[derive(Debug, Clone)]
+pub enum Schedule {
+    /// Every `n` seconds, aligned to the Unix epoch so `15m` fires at :00,
+    /// :15, :30 and :45 regardless of when the daemon started.
+    Interval(Duration),
+    /// A cron expression, evaluated in UTC.
+    Cron(Box<cron::Schedule>),
+}
+
+impl Schedule {
+    pub fn interval(every: Duration) -> Result<Self> {
+        if every.as_secs() == 0 {
+            bail!("interval must be at least one second");
+        }
+        Ok(Self::Interval(every))
+    }
+
+    /// Parse a standard five-field cron expression (minute precision) or the
+    /// six/seven-field form with seconds and years.
+    pub fn cron(expr: &str) -> Result<Self> {
+        let expr = expr.trim();
+        let full = match expr.split_whitespace().count() {
+            5 => format!("0 {}", expr),
+            _ => expr.to_string(),
+        };
+        let schedule = cron::Schedule::from_str(&full)
+            .with_context(|| format!("invalid cron expression '{}'", expr))?;
+        Ok(Self::Cron(Box::new(schedule)))
+    }
+
+    /// The first slot strictly after `now`.
+    pub fn next_after(&self, now: DateTime<Utc>) -> Result<DateTime<Utc>> {
+        match self {
+            Self::Interval(every) => {
+                let step = TimeDelta::from_std(*every)?;
+                Ok(now.duration_trunc(step)? + step)
+            }
+            Self::Cron(schedule) => schedule.after(&now).next()
+                .ok_or_else(|| anyhow!("cron schedule '{}' has no upcoming runs", schedule)),
+        }
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Interval(every) => write!(f, "every {}", format_duration(*every)),
+            Self::Cron(schedule) => write!(f, "cron '{}'", schedule),
+        }
+    }
+}
+
+/// Parse durations like `90s`, `15m`, `1h` or `1d`. A bare number is seconds.
+pub fn parse_duration(s: &str) -> Result<Duration> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let n: u64 = number.parse().with_context(|| format!("invalid duration '{}'", s))?;
+    let unit_secs = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        other => bail!("unknown duration unit '{}' in '{}', expected s, m, h or d", other, s),
+    };
+    let secs = n.checked_mul(unit_secs).ok_or_else(|| anyhow!("duration '{}' is too long", s))?;
+    Ok(Duration::from_secs(secs))
+}
+
+pub fn format_duration(d: Duration) -> String {
+    let secs = d.as_secs();
+    match secs {
+        s if s > 0 && s % 86400 == 0 => format!("{}d", s / 86400),
+        s if s > 0 && s % 3600 == 0 => format!("{}h", s / 3600),
+        s if s > 0 && s % 60 == 0 => format!("{}m", s / 60),
+        s => format!("{}s", s),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ts(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_durations() -> Result<()> {
+        assert_eq!(parse_duration("90")?, Duration::from_secs(90));
+        assert_eq!(parse_duration(" 90s ")?, Duration::from_secs(90));
+        assert_eq!(parse_duration("15m")?, Duration::from_secs(900));
+        assert_eq!(parse_duration("1H")?, Duration::from_secs(3600));
+        assert_eq!(parse_duration("2d")?, Duration::from_secs(172_800));
+        assert_eq!(parse_duration("0s")?, Duration::ZERO);
+
+        for bad in ["", "m", "-5m", "1.5h", "10w", "5 minutes"] {
+            assert!(parse_duration(bad).is_err(), "{}", bad);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_durations_that_overflow() {
+        let err = parse_duration("999999999999999999d").unwrap_err();
+        assert!(err.to_string().contains("too long"), "{}", err);
+        assert!(parse_duration(&format!("{}m", u64::MAX / 60 + 1)).is_err());
+        assert!(parse_duration(&format!("{}", u64::MAX)).is_ok());
+        assert!(parse_duration(&format!("{}0", u64::MAX)).is_err());
+    }
+
+    #[test]
+    fn formats_durations_in_the_largest_whole_unit() {
+        assert_eq!(format_duration(Duration::from_secs(90)), "90s");
+        assert_eq!(format_duration(Duration::from_secs(900)), "15m");
+        assert_eq!(format_duration(Duration::from_secs(7200)), "2h");
+        assert_eq!(format_duration(Duration::from_secs(86_400)), "1d");
+        assert_eq!(format_duration(Duration::from_secs(90_000)), "25h");
+        assert_eq!(format_duration(Duration::ZERO), "0s");
+        for s in ["45s", "15m", "6h", "3d"] {
+            assert_eq!(format_duration(parse_duration(s).unwrap()), s);
+        }
+    }
+
+    #[test]
+    fn intervals_align_to_the_epoch() -> Result<()> {
+        let every = Schedule::interval(Duration::from_secs(900))?;
+        assert_eq!(every.next_after(ts("2026-01-01T10:07:12Z"))?, ts("2026-01-01T10:15:00Z"));
+        assert_eq!(every.next_after(ts("2026-01-01T10:15:00Z"))?, ts("2026-01-01T10:30:00Z"));
+        assert_eq!(every.to_string(), "every 15m");
+        assert!(Schedule::interval(Duration::from_millis(500)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn cron_accepts_five_fields_and_runs_in_utc() -> Result<()> {
+        let cron = Schedule::cron("*/15 * * * *")?;
+        assert_eq!(cron.next_after(ts("2026-01-01T10:07:12Z"))?, ts("2026-01-01T10:15:00Z"));
+        assert_eq!(cron.next_after(ts("2026-01-01T10:15:00Z"))?, ts("2026-01-01T10:30:00Z"));
+
+        let daily = Schedule::cron("30 0 9 * * *")?;
+        assert_eq!(daily.next_after(ts("2026-01-01T10:00:00Z"))?, ts("2026-01-02T09:00:30Z"));
+
+        assert!(Schedule::cron("not a cron").is_err());
+        assert!(Schedule::cron("61 * * * *").is_err());
+        Ok(())
+    }
+}
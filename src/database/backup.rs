@@ -0,0 +1,316 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+use sqlx::{Row, SqlitePool};
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use super::{Database, MIGRATOR};
+use crate::config::BackupConfig;
+use crate::export::sink::{AtomicFile, Compression};
+
+/// File name prefix of backups written to a backup directory.
+pub const BACKUP_PREFIX: &str = "cmc_backup_";
+
+#[derive(Debug, Serialize)]
+pub struct BackupReport {
+    pub path: PathBuf,
+    pub bytes: u64,
+    /// Latest migration applied to the backed-up database
+    pub schema_version: Option<i64>,
+    /// Old backups deleted to stay within `keep`
+    pub removed: Vec<PathBuf>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RestoreReport {
+    /// Schema version recorded in the backup
+    pub backup_version: Option<i64>,
+    /// Schema version after bringing the backup up to date
+    pub schema_version: Option<i64>,
+    /// Where the database that was replaced was moved to
+    pub previous: Option<PathBuf>,
+}
+
+/// `cmc_backup_YYYYMMDD_HHMMSS.db`, plus `.gz` or `.zst` when compressed.
+pub fn backup_filename(ts: DateTime<Utc>, compression: Compression) -> String {
+    format!("{}{}.db{}", BACKUP_PREFIX, ts.format("%Y%m%d_%H%M%S"), compression.extension())
+}
+
+impl Database {
+    /// Latest migration applied to this database.
+    pub async fn schema_version(&self) -> Result<Option<i64>> {
+        schema_version(&self.pool).await
+    }
+
+    /// Write a consistent copy of the database to `path`. `VACUUM INTO` reads
+    /// from a single transaction, so the server and daemon keep reading and
+    /// writing while it runs; the copy is also defragmented.
+    pub async fn backup(&self, path: &Path, compression: Compression) -> Result<BackupReport> {
+        let name = path.file_name()
+            .with_context(|| format!("{} is not a file path", path.display()))?
+            .to_string_lossy();
+        // VACUUM INTO refuses to overwrite, so clear out any leftover from a crash
+        let vacuumed = path.with_file_name(format!(".{}.{}.vacuum", name, std::process::id()));
+        let _ = fs::remove_file(&vacuumed);
+
+        let result = self.vacuum_into(&vacuumed, path, compression).await;
+        let _ = fs::remove_file(&vacuumed);
+        result?;
+
+        Ok(BackupReport {
+            path: path.to_path_buf(),
+            bytes: fs::metadata(path)?.len(),
+            schema_version: self.schema_version().await?,
+            removed: Vec::new(),
+        })
+    }
+
+    async fn vacuum_into(&self, vacuumed: &Path, path: &Path, compression: Compression) -> Result<()> {
+        sqlx::query("VACUUM INTO ?")
+            .bind(vacuumed.to_string_lossy().into_owned())
+            .execute(&self.pool)
+            .await
+            .with_context(|| format!("failed to back up to {}", path.display()))?;
+
+        // Copying and compressing can take a while on a big database, so keep
+        // it off the async workers the server and daemon run on
+        let (vacuumed, path) = (vacuumed.to_path_buf(), path.to_path_buf());
+        tokio::task::spawn_blocking(move || {
+            let mut out = AtomicFile::create(&path, compression)?;
+            io::copy(&mut File::open(&vacuumed)?, &mut out)?;
+            out.commit()
+        })
+        .await?
+    }
+
+    /// Back up into `config.dir` under a timestamped name, then delete the
+    /// oldest backups there beyond `config.keep`.
+    pub async fn backup_to_dir(&self, config: &BackupConfig, now: DateTime<Utc>) -> Result<BackupReport> {
+        fs::create_dir_all(&config.dir)
+            .with_context(|| format!("failed to create {}", config.dir.display()))?;
+        let path = config.dir.join(backup_filename(now, config.compress));
+        let mut report = self.backup(&path, config.compress).await?;
+        if let Some(keep) = config.keep {
+            let dir = config.dir.clone();
+            report.removed = tokio::task::spawn_blocking(move || prune_backups(&dir, keep)).await??;
+        }
+        Ok(report)
+    }
+}
+
+/// Delete all but the newest `keep` backups in `dir`. Names sort by time.
+pub fn prune_backups(dir: &Path, keep: usize) -> Result<Vec<PathBuf>> {
+    let mut backups: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_file() && path.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.starts_with(BACKUP_PREFIX)))
+        .collect();
+    backups.sort();
+
+    let excess = backups.len().saturating_sub(keep);
+    let removed: Vec<PathBuf> = backups.drain(..excess).collect();
+    for path in &removed {
+        fs::remove_file(path).with_context(|| format!("failed to remove old backup {}", path.display()))?;
+    }
+    Ok(removed)
+}
+
+/// Replace the database at `database_url` with a backup, which may be gzip
+/// or zstd compressed.
+///
+/// The backup is unpacked next to the target and checked before anything is
+/// replaced: it must pass `integrity_check` and must not contain migrations
+/// this build doesn't know, i.e. come from a newer version. Older backups are
+/// migrated forward. The database being replaced is kept alongside as
+/// `<name>.before-restore`. Stop the server and daemon first.
+pub async fn restore(backup: &Path, compression: Compression, database_url: &str, force: bool) -> Result<RestoreReport> {
+    let options = SqliteConnectOptions::from_str(database_url)
+        .with_context(|| format!("invalid database url '{}'", database_url))?;
+    let target = options.get_filename().to_path_buf();
+    if target.as_os_str().is_empty() || target == Path::new(":memory:") {
+        bail!("cannot restore into an in-memory database");
+    }
+    if target.exists() && !force {
+        bail!("{} already exists; pass --force to replace it", target.display());
+    }
+
+    let name = target.file_name()
+        .with_context(|| format!("{} is not a file path", target.display()))?
+        .to_string_lossy()
+        .into_owned();
+    let staged = target.with_file_name(format!(".{}.{}.restore", name, std::process::id()));
+    let result = stage(backup, compression, &staged).await;
+    let (backup_version, schema_version) = match result {
+        Ok(versions) => versions,
+        Err(err) => {
+            remove_with_sidecars(&staged);
+            return Err(err);
+        }
+    };
+
+    let previous = if target.exists() {
+        let previous = target.with_file_name(format!("{}.before-restore", name));
+        remove_with_sidecars(&previous);
+        move_with_sidecars(&target, &previous)?;
+        Some(previous)
+    } else {
+        None
+    };
+    fs::rename(&staged, &target)
+        .with_context(|| format!("failed to move the restored database to {}", target.display()))?;
+
+    Ok(RestoreReport { backup_version, schema_version, previous })
+}
+
+/// Unpack and verify a backup at `staged`, returning its schema version
+/// before and after migrating.
+async fn stage(backup: &Path, compression: Compression, staged: &Path) -> Result<(Option<i64>, Option<i64>)> {
+    let (from, to) = (backup.to_path_buf(), staged.to_path_buf());
+    tokio::task::spawn_blocking(move || -> Result<()> {
+        let mut reader = compression.reader(&from)?;
+        let mut out = File::create(&to).with_context(|| format!("failed to create {}", to.display()))?;
+        io::copy(&mut reader, &mut out).with_context(|| format!("failed to read backup {}", from.display()))?;
+        out.sync_all()?;
+        Ok(())
+    })
+    .await??;
+
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect_with(SqliteConnectOptions::new().filename(staged))
+        .await?;
+    let result = check_backup(&pool, backup).await;
+    pool.close().await;
+    result
+}
+
+async fn check_backup(pool: &SqlitePool, backup: &Path) -> Result<(Option<i64>, Option<i64>)> {
+    let integrity: String = sqlx::query("PRAGMA integrity_check")
+        .fetch_one(pool)
+        .await
+        .with_context(|| format!("{} is not a SQLite database", backup.display()))?
+        .get(0);
+    if integrity != "ok" {
+        bail!("{} failed integrity_check: {}", backup.display(), integrity);
+    }
+
+    let has_migrations = sqlx::query("SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations'")
+        .fetch_optional(pool)
+        .await?
+        .is_some();
+    if !has_migrations {
+        bail!("{} has no schema version; it was not made by this tool", backup.display());
+    }
+
+    let supported = MIGRATOR.migrations.iter().map(|m| m.version).max();
+    let applied: Vec<i64> = sqlx::query("SELECT version FROM _sqlx_migrations WHERE success ORDER BY version")
+        .fetch_all(pool)
+        .await?
+        .iter()
+        .map(|row| row.get(0))
+        .collect();
+    if let Some(unknown) = applied.iter().find(|v| !MIGRATOR.migrations.iter().any(|m| m.version == **v)) {
+        bail!("{} has schema version {}, which this build doesn't know (latest is {}); restore it with a newer build",
+              backup.display(), unknown, supported.unwrap_or_default());
+    }
+    let backup_version = applied.last().copied();
+
+    MIGRATOR.run(pool).await.with_context(|| format!("failed to migrate {}", backup.display()))?;
+    Ok((backup_version, schema_version(pool).await?))
+}
+
+async fn schema_version(pool: &SqlitePool) -> Result<Option<i64>> {
+    let row = sqlx::query("SELECT MAX(version) AS version FROM _sqlx_migrations WHERE success")
+        .fetch_one(pool)
+        .await?;
+    Ok(row.get("version"))
+}
+
+const SIDECARS: [&str; 3] = ["-wal", "-shm", "-journal"];
+
+fn sidecar(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Move a database file together with its WAL and journal files, so a stale
+/// WAL is never replayed into a different database.
+fn move_with_sidecars(from: &Path, to: &Path) -> Result<()> {
+    fs::rename(from, to).with_context(|| format!("failed to move {} to {}", from.display(), to.display()))?;
+    for suffix in SIDECARS {
+        let side = sidecar(from, suffix);
+        if side.exists() {
+            fs::rename(&side, sidecar(to, suffix))?;
+        }
+    }
+    Ok(())
+}
+
+fn remove_with_sidecars(path: &Path) {
+    let _ = fs::remove_file(path);
+    for suffix in SIDECARS {
+        let _ = fs::remove_file(sidecar(path, suffix));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::testing::{coin, temp_db};
+
+    #[tokio::test]
+    async fn restores_a_compressed_backup() -> Result<()> {
+        let (dir, db) = temp_db().await?;
+        db.save_coins(&[coin(1, "BTC", 1, 100.0, "2026-01-01T00:00:00Z")]).await?;
+        let backup = dir.path().join("backup.db.gz");
+        db.backup(&backup, Compression::Gzip(6)).await?;
+
+        let target = dir.path().join("restored.db");
+        let url = format!("sqlite:{}", target.display());
+        let report = restore(&backup, Compression::from_path(&backup), &url, false).await?;
+        assert_eq!(report.backup_version, db.schema_version().await?);
+        assert_eq!(report.schema_version, report.backup_version);
+        assert!(report.previous.is_none());
+
+        let restored = Database::new(&url).await?;
+        assert_eq!(restored.get_snapshot_count().await?, 1);
+        restored.pool.close().await;
+
+        // An existing database is only replaced with force, and kept aside
+        assert!(restore(&backup, Compression::from_path(&backup), &url, false).await.is_err());
+        let report = restore(&backup, Compression::from_path(&backup), &url, true).await?;
+        assert_eq!(report.previous, Some(dir.path().join("restored.db.before-restore")));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn refuses_a_backup_from_a_newer_schema() -> Result<()> {
+        let (dir, db) = temp_db().await?;
+        let backup = dir.path().join("backup.db");
+        db.backup(&backup, Compression::None).await?;
+
+        let pool = SqlitePool::connect(&format!("sqlite:{}", backup.display())).await?;
+        sqlx::query(
+            "INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time) VALUES (9999, 'from the future', TRUE, x'00', 0)"
+        )
+        .execute(&pool)
+        .await?;
+        pool.close().await;
+
+        let target = dir.path().join("restored.db");
+        let err = restore(&backup, Compression::None, &format!("sqlite:{}", target.display()), false).await.unwrap_err();
+        assert!(format!("{:#}", err).contains("schema version 9999"), "{:#}", err);
+        assert!(!target.exists());
+        // Nothing staged is left behind
+        let leftovers: Vec<_> = fs::read_dir(dir.path())?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_name().to_string_lossy().ends_with(".restore"))
+            .collect();
+        assert!(leftovers.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,304 @@
+use anyhow::{bail, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use utoipa::ToSchema;
+
+use super::{listing::as_of_snapshots, Database, EpochMillis};
+use crate::daemon::schedule::format_duration;
+use crate::model::{CompareResponse, CompareSeries};
+
+/// Most buckets one comparison may span.
+pub const MAX_BUCKETS: i64 = 2000;
+
+/// What the values of a comparison are.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum CompareMode {
+    /// Closing price in USD
+    #[default]
+    Price,
+    /// Price scaled so each series starts at 100
+    Normalized,
+    /// Percent change from the previous bucket
+    Returns,
+    /// Price divided by the base coin's price in the same bucket
+    Ratio,
+}
+
+impl CompareMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Price => "price",
+            Self::Normalized => "normalized",
+            Self::Returns => "returns",
+            Self::Ratio => "ratio",
+        }
+    }
+}
+
+impl FromStr for CompareMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "price" => Ok(Self::Price),
+            "normalized" => Ok(Self::Normalized),
+            "returns" => Ok(Self::Returns),
+            "ratio" => Ok(Self::Ratio),
+            other => bail!("unknown mode '{}', expected price, normalized, returns or ratio", other),
+        }
+    }
+}
+
+impl fmt::Display for CompareMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Coins and time range to compare. Buckets are aligned to multiples of
+/// `interval` since the Unix epoch, starting with the one holding `since`.
+#[derive(Debug, Clone)]
+pub struct CompareQuery {
+    pub symbols: Vec<String>,
+    pub since: DateTime<Utc>,
+    pub until: DateTime<Utc>,
+    pub interval: Duration,
+    pub mode: CompareMode,
+    /// Coin the ratios are against; required in `Ratio` mode
+    pub base: Option<String>,
+}
+
+impl CompareQuery {
+    fn start_ms(&self) -> i64 {
+        let since = self.since.timestamp_millis();
+        since - since.rem_euclid(self.interval.num_milliseconds().max(1))
+    }
+
+    /// Number of buckets in the range. Counted in `i128`, as an interval may
+    /// be nearly `i64::MAX` milliseconds long.
+    pub fn buckets(&self) -> i64 {
+        let step = i128::from(self.interval.num_milliseconds().max(1));
+        let span = i128::from(self.until.timestamp_millis()) - i128::from(self.start_ms());
+        i64::try_from((span + step - 1).div_euclid(step).max(0)).unwrap_or(i64::MAX)
+    }
+}
+
+impl Database {
+    /// Closing prices of several coins on a common bucket grid, from raw
+    /// snapshots and, for compacted ranges, the rollups, transformed as
+    /// `query.mode` asks. Symbols matching no coin are left out and listed
+    /// as unresolved; a symbol shared by several coins means the best-ranked.
+    pub async fn compare(&self, query: &CompareQuery) -> Result<CompareResponse> {
+        let step = query.interval.num_milliseconds();
+        if step <= 0 {
+            bail!("compare interval must be positive");
+        }
+        if query.mode == CompareMode::Ratio && query.base.is_none() {
+            bail!("ratio comparisons need a base coin");
+        }
+        let start = query.start_ms();
+        let buckets = query.buckets() as usize;
+
+        let symbols: Vec<String> = query.symbols.iter().map(|s| s.to_uppercase()).collect();
+        let base = query.base.as_ref().map(|s| s.to_uppercase());
+        let wanted: Vec<String> = symbols.iter().chain(&base).cloned().collect();
+        let wanted_json = serde_json::Value::from(wanted).to_string();
+
+        // A ticker can be shared; each symbol means its best-ranked coin in
+        // the latest scrape, or its oldest listing if none is ranked
+        let resolved: HashMap<String, i64> = sqlx::query(
+            r#"
+            SELECT symbol, id FROM (
+                SELECT c.symbol, c.id,
+                       ROW_NUMBER() OVER (PARTITION BY c.symbol ORDER BY l.cmc_rank IS NULL, l.cmc_rank, c.id) AS n
+                FROM coins c
+                LEFT JOIN snapshots l ON l.coin_id = c.id AND l.ts_utc = (SELECT MAX(ts_utc) FROM snapshots)
+                WHERE c.symbol IN (SELECT value FROM json_each(?1))
+            )
+            WHERE n = 1
+            "#
+        )
+        .bind(&wanted_json)
+        .fetch_all(&self.pool)
+        .await?
+        .iter()
+        .map(|row| (row.get("symbol"), row.get("id")))
+        .collect();
+        let ids_json = serde_json::Value::from(resolved.values().copied().collect::<Vec<_>>()).to_string();
+
+        // SQLite takes the bare columns of a MAX() aggregate from the row
+        // holding the maximum, so price_usd is each bucket's close
+        let rows = sqlx::query(&format!(
+            r#"
+            SELECT s.coin_id, (s.ts_utc - ?2) / ?3 AS bucket, s.price_usd, MAX(s.ts_utc) AS ts_utc
+            FROM {} s
+            WHERE s.coin_id IN (SELECT value FROM json_each(?1))
+              AND s.price_usd IS NOT NULL
+            GROUP BY s.coin_id, bucket
+            "#,
+            as_of_snapshots(Some("?2"), "?4 - 1")
+        ))
+        .bind(&ids_json)
+        .bind(start)
+        .bind(step)
+        .bind(EpochMillis(query.until))
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut closes: HashMap<i64, Vec<Option<f64>>> = HashMap::new();
+        for row in rows {
+            let bucket = row.get::<i64, _>("bucket") as usize;
+            if bucket < buckets {
+                closes.entry(row.get("coin_id")).or_insert_with(|| vec![None; buckets])[bucket] = row.get("price_usd");
+            }
+        }
+        let empty = vec![None; buckets];
+        let closes_of = |symbol: &String| resolved.get(symbol).and_then(|id| closes.get(id)).unwrap_or(&empty);
+        let base_closes = base.as_ref().map(closes_of);
+
+        let mut response = CompareResponse {
+            interval: format_duration(query.interval.to_std()?),
+            mode: query.mode.to_string(),
+            base: if query.mode == CompareMode::Ratio { base.clone() } else { None },
+            timestamps: (0..buckets)
+                .map(|i| DateTime::from_timestamp_millis(start + i as i64 * step).unwrap_or_default())
+                .collect(),
+            ..CompareResponse::default()
+        };
+        for symbol in symbols {
+            if !resolved.contains_key(&symbol) {
+                response.unresolved.push(symbol);
+                continue;
+            }
+            let prices = closes_of(&symbol);
+            let values = match query.mode {
+                CompareMode::Price => prices.clone(),
+                CompareMode::Normalized => {
+                    let first = prices.iter().flatten().copied().find(|p| *p != 0.0);
+                    prices.iter().map(|p| Some(p.as_ref()? * 100.0 / first?)).collect()
+                }
+                CompareMode::Returns => (0..buckets)
+                    .map(|i| {
+                        let previous = prices.get(i.checked_sub(1)?).copied().flatten().filter(|p| *p != 0.0)?;
+                        Some((prices[i]? - previous) * 100.0 / previous)
+                    })
+                    .collect(),
+                CompareMode::Ratio => prices.iter()
+                    .zip(base_closes.unwrap_or(&empty))
+                    .map(|(p, b)| Some(p.as_ref()? / b.filter(|b| *b != 0.0)?))
+                    .collect(),
+            };
+            response.series.push(CompareSeries { symbol, values });
+        }
+        if let Some(base) = base.filter(|base| !resolved.contains_key(base) && !response.unresolved.contains(base)) {
+            response.unresolved.push(base);
+        }
+
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::testing::{coin, temp_db, ts};
+
+    /// Hourly buckets from 10:00 to 13:00. BTC has no price at 11:00, and a
+    /// minor coin also listed as BTC trades at 1 throughout.
+    async fn compare(mode: CompareMode, base: Option<&str>) -> Result<CompareResponse> {
+        let (_dir, db) = temp_db().await?;
+        let scrapes = [
+            ("2026-01-01T09:59:00Z", vec![(1, 1, 90.0), (1027, 2, 9.0)]),
+            ("2026-01-01T10:10:00Z", vec![(1, 1, 100.0), (1027, 2, 10.0)]),
+            ("2026-01-01T10:50:00Z", vec![(1, 1, 110.0)]),
+            ("2026-01-01T10:55:00Z", vec![(9999, 500, 1.0)]),
+            ("2026-01-01T11:30:00Z", vec![(1027, 2, 11.0), (9999, 500, 1.0)]),
+            ("2026-01-01T12:20:00Z", vec![(1, 1, 121.0)]),
+            ("2026-01-01T12:40:00Z", vec![(1027, 2, 12.1)]),
+            ("2026-01-01T13:00:00Z", vec![(1, 1, 200.0), (1027, 2, 20.0), (9999, 500, 1.0)]),
+        ];
+        for (at, coins) in scrapes {
+            let symbol = |id| if id == 1027 { "ETH" } else { "BTC" };
+            let rows: Vec<_> = coins.into_iter().map(|(id, rank, price)| coin(id, symbol(id), rank, price, at)).collect();
+            db.save_coins(&rows).await?;
+        }
+        db.compare(&CompareQuery {
+            symbols: vec!["btc".to_string(), "ETH".to_string(), "DOGE".to_string()],
+            since: ts("2026-01-01T10:30:00Z"),
+            until: ts("2026-01-01T13:00:00Z"),
+            interval: Duration::hours(1),
+            mode,
+            base: base.map(str::to_string),
+        }).await
+    }
+
+    fn assert_values(actual: &[Option<f64>], expected: &[Option<f64>]) {
+        assert_eq!(actual.len(), expected.len(), "{:?} != {:?}", actual, expected);
+        for (a, e) in actual.iter().zip(expected) {
+            match (a, e) {
+                (Some(a), Some(e)) => assert!((a - e).abs() < 1e-9, "{:?} != {:?}", actual, expected),
+                _ => assert_eq!(a, e, "{:?} != {:?}", actual, expected),
+            }
+        }
+    }
+
+    #[test]
+    fn counts_buckets_for_any_interval() {
+        let query = |since: &str, until: &str, interval: Duration| CompareQuery {
+            symbols: Vec::new(),
+            since: ts(since),
+            until: ts(until),
+            interval,
+            mode: CompareMode::Price,
+            base: None,
+        };
+        assert_eq!(query("2026-01-01T10:30:00Z", "2026-01-01T13:00:00Z", Duration::hours(1)).buckets(), 3);
+        assert_eq!(query("2026-01-01T10:30:00Z", "2026-01-01T13:00:01Z", Duration::hours(1)).buckets(), 4);
+        assert_eq!(query("2026-01-01T10:30:00Z", "2026-01-01T10:00:00Z", Duration::hours(1)).buckets(), 0);
+        // One bucket from the epoch covers everything
+        assert_eq!(query("2026-01-01T10:30:00Z", "2026-01-01T13:00:00Z", Duration::MAX).buckets(), 1);
+        assert_eq!(query("1969-12-31T00:00:00Z", "2026-01-01T13:00:00Z", Duration::MAX).buckets(), 2);
+    }
+
+    #[tokio::test]
+    async fn closes_align_to_interval_buckets() -> Result<()> {
+        let response = compare(CompareMode::Price, None).await?;
+        assert_eq!(response.timestamps, vec![ts("2026-01-01T10:00:00Z"), ts("2026-01-01T11:00:00Z"), ts("2026-01-01T12:00:00Z")]);
+        assert_eq!(response.unresolved, vec!["DOGE"]);
+        assert_eq!(response.series.iter().map(|s| s.symbol.as_str()).collect::<Vec<_>>(), vec!["BTC", "ETH"]);
+        // The other BTC's later prices don't leak into Bitcoin's
+        assert_values(&response.series[0].values, &[Some(110.0), None, Some(121.0)]);
+        assert_values(&response.series[1].values, &[Some(10.0), Some(11.0), Some(12.1)]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn normalizes_from_the_first_close() -> Result<()> {
+        let response = compare(CompareMode::Normalized, None).await?;
+        assert_values(&response.series[0].values, &[Some(100.0), None, Some(110.0)]);
+        assert_values(&response.series[1].values, &[Some(100.0), Some(110.0), Some(121.0)]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn returns_need_the_previous_bucket() -> Result<()> {
+        let response = compare(CompareMode::Returns, None).await?;
+        assert_values(&response.series[0].values, &[None, None, None]);
+        assert_values(&response.series[1].values, &[None, Some(10.0), Some(10.0)]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn ratios_divide_by_the_base_in_each_bucket() -> Result<()> {
+        let response = compare(CompareMode::Ratio, Some("eth")).await?;
+        assert_eq!(response.base.as_deref(), Some("ETH"));
+        assert_values(&response.series[0].values, &[Some(11.0), None, Some(10.0)]);
+        assert_values(&response.series[1].values, &[Some(1.0), Some(1.0), Some(1.0)]);
+        Ok(())
+    }
+}
@@ -0,0 +1,103 @@
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use std::collections::HashMap;
+
+use super::{CoinQuery, Database};
+use crate::model::{CoinResponse, CoinsDiff, RankChange};
+
+impl Database {
+    /// Compare the top `limit` coins of two scrapes: the last one at or
+    /// before `to` (default the latest) and the last one at or before `from`
+    /// (default the one before `to`). A side with no scrape counts as an
+    /// empty ranking.
+    pub async fn diff_coins(&self, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>, limit: u32) -> Result<CoinsDiff> {
+        let to_ts = match to {
+            Some(to) => self.scrape_at(to).await?,
+            None => self.latest_snapshot_at().await?,
+        };
+        let from_ts = match (from, to_ts) {
+            (Some(from), _) => self.scrape_at(from).await?,
+            (None, Some(to_ts)) => self.scrape_at(to_ts - Duration::milliseconds(1)).await?,
+            (None, None) => None,
+        };
+
+        let from_coins = self.ranking_at(from_ts, limit).await?;
+        let to_coins = self.ranking_at(to_ts, limit).await?;
+        let before: HashMap<u64, &CoinResponse> = from_coins.iter().map(|coin| (coin.id, coin)).collect();
+        let after: HashMap<u64, &CoinResponse> = to_coins.iter().map(|coin| (coin.id, coin)).collect();
+
+        let mut diff = CoinsDiff { from_ts_utc: from_ts, to_ts_utc: to_ts, ..CoinsDiff::default() };
+        for coin in &to_coins {
+            let Some(previous) = before.get(&coin.id) else {
+                diff.entries.push(coin.clone());
+                continue;
+            };
+            if let (Some(from_rank), Some(to_rank)) = (previous.rank, coin.rank)
+                && from_rank != to_rank {
+                diff.rank_changes.push(RankChange {
+                    id: coin.id,
+                    symbol: coin.symbol.clone(),
+                    name: coin.name.clone(),
+                    from_rank,
+                    to_rank,
+                    change: from_rank as i64 - to_rank as i64,
+                });
+            }
+        }
+        diff.exits = from_coins.iter().filter(|coin| !after.contains_key(&coin.id)).cloned().collect();
+        Ok(diff)
+    }
+
+    /// Top `limit` coins by rank of the scrape at `ts_utc`, if there is one.
+    async fn ranking_at(&self, ts_utc: Option<DateTime<Utc>>, limit: u32) -> Result<Vec<CoinResponse>> {
+        let Some(ts_utc) = ts_utc else {
+            return Ok(Vec::new());
+        };
+        let query = CoinQuery { at: Some(ts_utc), limit, ..CoinQuery::default() };
+        Ok(self.list_coins(&query).await?.coins)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::testing::{coin, temp_db, ts};
+
+    fn ids(coins: &[CoinResponse]) -> Vec<u64> {
+        coins.iter().map(|coin| coin.id).collect()
+    }
+
+    #[tokio::test]
+    async fn reports_entries_exits_and_rank_changes() -> Result<()> {
+        let (_dir, db) = temp_db().await?;
+        let first = "2026-01-01T00:00:00Z";
+        db.save_coins(&[coin(1, "BTC", 1, 100.0, first), coin(1027, "ETH", 2, 10.0, first), coin(52, "XRP", 3, 1.0, first), coin(5, "BNB", 4, 5.0, first)]).await?;
+        let second = "2026-01-01T01:00:00Z";
+        db.save_coins(&[coin(1, "BTC", 1, 100.0, second), coin(52, "XRP", 2, 1.0, second), coin(5, "BNB", 3, 5.0, second), coin(1027, "ETH", 4, 10.0, second)]).await?;
+
+        let diff = db.diff_coins(None, None, 3).await?;
+        assert_eq!(diff.from_ts_utc, Some(ts(first)));
+        assert_eq!(diff.to_ts_utc, Some(ts(second)));
+        assert_eq!(ids(&diff.entries), vec![5]);
+        assert_eq!(ids(&diff.exits), vec![1027]);
+        let changes: Vec<_> = diff.rank_changes.iter().map(|c| (c.id, c.from_rank, c.to_rank, c.change)).collect();
+        assert_eq!(changes, vec![(52, 3, 2, 1)]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn a_missing_side_counts_as_empty() -> Result<()> {
+        let (_dir, db) = temp_db().await?;
+        let at = "2026-01-01T00:00:00Z";
+        db.save_coins(&[coin(1, "BTC", 1, 100.0, at), coin(1027, "ETH", 2, 10.0, at)]).await?;
+
+        let diff = db.diff_coins(None, None, 10).await?;
+        assert_eq!(diff.from_ts_utc, None);
+        assert_eq!(ids(&diff.entries), vec![1, 1027]);
+
+        let diff = db.diff_coins(None, Some(ts("2025-12-31T00:00:00Z")), 10).await?;
+        assert_eq!(diff.to_ts_utc, None);
+        assert!(diff.entries.is_empty() && diff.exits.is_empty());
+        Ok(())
+    }
+}
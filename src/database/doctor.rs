@@ -0,0 +1,338 @@
+use anyhow::Result;
+use chrono::Utc;
+use serde::Serialize;
+use sqlx::Row;
+
+use super::{Database, EpochMillis};
+
+/// Largest epoch millisecond chrono can represent as a date (year 9999).
+const MAX_TS_MILLIS: i64 = 253_402_300_799_999;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Check {
+    /// SQLite's own page and index consistency check
+    Integrity,
+    ForeignKeys,
+    Duplicates,
+    Timestamps,
+    Values,
+}
+
+/// Rows of one table failing one check.
+#[derive(Debug, Clone, Serialize)]
+pub struct Finding {
+    pub check: Check,
+    pub table: &'static str,
+    pub problem: String,
+    pub rows: u64,
+    /// A few affected rowids, to look at by hand
+    pub examples: Vec<i64>,
+    /// Whether `repair` fixes it
+    pub repairable: bool,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct RepairReport {
+    /// Snapshots moved to `snapshots_quarantine`, by reason
+    pub quarantined: Vec<(String, u64)>,
+}
+
+/// One problem a row can have: the `WHERE` condition that finds it, evaluated
+/// against the table aliased as `t`. Snapshot findings double as quarantine
+/// reasons; they are checked in order and a row is quarantined for the first
+/// one it fails, so duplicates come last and only see otherwise valid rows.
+struct RowCheck {
+    check: Check,
+    table: &'static str,
+    problem: &'static str,
+    condition: &'static str,
+}
+
+const ROW_CHECKS: &[RowCheck] = &[
+    RowCheck {
+        check: Check::Timestamps,
+        table: "snapshots",
+        problem: "unparsable timestamp",
+        condition: "typeof(t.ts_utc) != 'integer' OR t.ts_utc NOT BETWEEN 0 AND $MAX_TS",
+    },
+    RowCheck {
+        check: Check::ForeignKeys,
+        table: "snapshots",
+        problem: "missing coin",
+        condition: "t.coin_id NOT IN (SELECT id FROM coins)",
+    },
+    RowCheck {
+        check: Check::Values,
+        table: "snapshots",
+        problem: "negative price_usd",
+        condition: "t.price_usd < 0",
+    },
+    RowCheck {
+        check: Check::Values,
+        table: "snapshots",
+        problem: "negative market_cap_usd",
+        condition: "t.market_cap_usd < 0",
+    },
+    RowCheck {
+        check: Check::Values,
+        table: "snapshots",
+        problem: "rank below 1",
+        condition: "t.cmc_rank < 1",
+    },
+    RowCheck {
+        check: Check::Values,
+        table: "snapshots",
+        problem: "24h change below -100%",
+        condition: "t.change_24h < -100",
+    },
+    RowCheck {
+        check: Check::Duplicates,
+        table: "snapshots",
+        problem: "duplicate coin and timestamp",
+        condition: "EXISTS (SELECT 1 FROM snapshots d WHERE d.coin_id = t.coin_id AND d.ts_utc = t.ts_utc AND d.id < t.id)",
+    },
+    RowCheck {
+        check: Check::Timestamps,
+        table: "snapshots_hourly",
+        problem: "unparsable bucket timestamp",
+        condition: "typeof(t.bucket_ts) != 'integer' OR t.bucket_ts NOT BETWEEN 0 AND $MAX_TS",
+    },
+    RowCheck {
+        check: Check::ForeignKeys,
+        table: "snapshots_hourly",
+        problem: "missing coin",
+        condition: "t.coin_id NOT IN (SELECT id FROM coins)",
+    },
+    RowCheck {
+        check: Check::Timestamps,
+        table: "snapshots_daily",
+        problem: "unparsable bucket timestamp",
+        condition: "typeof(t.bucket_ts) != 'integer' OR t.bucket_ts NOT BETWEEN 0 AND $MAX_TS",
+    },
+    RowCheck {
+        check: Check::ForeignKeys,
+        table: "snapshots_daily",
+        problem: "missing coin",
+        condition: "t.coin_id NOT IN (SELECT id FROM coins)",
+    },
+    RowCheck {
+        check: Check::Timestamps,
+        table: "scrape_runs",
+        problem: "unparsable start time",
+        condition: "typeof(t.started_at) != 'integer' OR t.started_at NOT BETWEEN 0 AND $MAX_TS",
+    },
+];
+
+impl RowCheck {
+    fn condition(&self) -> String {
+        self.condition.replace("$MAX_TS", &MAX_TS_MILLIS.to_string())
+    }
+}
+
+impl Database {
+    /// Look for rows that break the schema's assumptions. Nothing is changed.
+    pub async fn check_integrity(&self) -> Result<Vec<Finding>> {
+        let mut findings = Vec::new();
+
+        let messages: Vec<String> = sqlx::query("PRAGMA integrity_check")
+            .fetch_all(&self.pool)
+            .await?
+            .iter()
+            .map(|row| row.get(0))
+            .filter(|message: &String| message != "ok")
+            .collect();
+        if !messages.is_empty() {
+            findings.push(Finding {
+                check: Check::Integrity,
+                table: "database",
+                problem: messages.join("; "),
+                rows: messages.len() as u64,
+                examples: Vec::new(),
+                repairable: false,
+            });
+        }
+
+        for check in ROW_CHECKS {
+            let condition = check.condition();
+            let row = sqlx::query(&format!("SELECT COUNT(*) AS count FROM {} t WHERE {}", check.table, condition))
+                .fetch_one(&self.pool)
+                .await?;
+            let rows: i64 = row.get("count");
+            if rows == 0 {
+                continue;
+            }
+            let examples = sqlx::query(&format!("SELECT t.rowid FROM {} t WHERE {} ORDER BY t.rowid LIMIT 5", check.table, condition))
+                .fetch_all(&self.pool)
+                .await?
+                .iter()
+                .map(|row| row.get::<i64, _>(0))
+                .collect();
+            findings.push(Finding {
+                check: check.check,
+                table: check.table,
+                problem: check.problem.to_string(),
+                rows: rows as u64,
+                examples,
+                repairable: check.table == "snapshots",
+            });
+        }
+
+        Ok(findings)
+    }
+
+    /// Move every snapshot that fails a check, including all but the first of
+    /// each set of duplicates, into `snapshots_quarantine`. Runs in a single
+    /// transaction. Problems in other tables are only reported.
+    pub async fn repair(&self) -> Result<RepairReport> {
+        let mut report = RepairReport::default();
+        let mut tx = self.pool.begin().await?;
+        let now = EpochMillis(Utc::now());
+
+        for check in ROW_CHECKS.iter().filter(|check| check.table == "snapshots") {
+            let condition = check.condition();
+            let moved = sqlx::query(&format!(
+                r#"
+                INSERT INTO snapshots_quarantine
+                    (original_id, coin_id, ts_utc, cmc_rank, price_usd, market_cap_usd, change_24h, source, reason, quarantined_at)
+                SELECT t.id, t.coin_id, t.ts_utc, t.cmc_rank, t.price_usd, t.market_cap_usd, t.change_24h, t.source, ?, ?
+                FROM snapshots t WHERE {}
+                "#,
+                condition
+            ))
+            .bind(check.problem)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+            if moved == 0 {
+                continue;
+            }
+            sqlx::query(&format!("DELETE FROM snapshots WHERE id IN (SELECT t.id FROM snapshots t WHERE {})", condition))
+                .execute(&mut *tx)
+                .await?;
+            report.quarantined.push((check.problem.to_string(), moved));
+        }
+
+        // Summaries of scrapes that lost rows are recomputed, or dropped if
+        // nothing is left of the scrape
+        if !report.quarantined.is_empty() {
+            let quarantined = "SELECT ts_utc FROM snapshots_quarantine WHERE quarantined_at = ?1";
+            sqlx::query(&format!("DELETE FROM market_summaries WHERE ts_utc IN ({})", quarantined))
+                .bind(now)
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query(&super::market::summarize_sql(quarantined))
+                .bind(now)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::testing::{coin, temp_db};
+
+    /// One valid scrape plus a row for every check, written past the foreign
+    /// key and type constraints the normal save path would enforce.
+    async fn seed(db: &Database) -> Result<()> {
+        db.save_coins(&[coin(1, "BTC", 1, 100.0, "2026-01-01T00:00:00Z")]).await?;
+        let mut conn = db.pool.acquire().await?;
+        sqlx::raw_sql(
+            r#"
+            PRAGMA foreign_keys = OFF;
+            INSERT INTO snapshots (id, coin_id, ts_utc, cmc_rank, price_usd, market_cap_usd, change_24h, source) VALUES
+                (101, 1, 'garbage', 1, 1.0, 1.0, 0.0, 'import'),
+                (102, 999, 1767225600000, 1, 1.0, 1.0, 0.0, 'scrape'),
+                (103, 1, 1767229200000, 1, -1.0, 1.0, 0.0, 'scrape'),
+                (104, 1, 1767232800000, 1, 1.0, -1.0, 0.0, 'scrape'),
+                (105, 1, 1767236400000, 0, 1.0, 1.0, 0.0, 'scrape'),
+                (106, 1, 1767240000000, 1, 1.0, 1.0, -150.0, 'scrape'),
+                (107, 1, 1767225600000, 1, 100.0, 100000.0, 0.0, 'backfill');
+            INSERT INTO snapshots_hourly (coin_id, bucket_ts, close_usd, samples) VALUES (1, 'garbage', 1.0, 1), (999, 0, 1.0, 1);
+            INSERT INTO snapshots_daily (coin_id, bucket_ts, close_usd, samples) VALUES (1, 'garbage', 1.0, 1), (999, 0, 1.0, 1);
+            INSERT INTO scrape_runs (started_at, status, pages_requested) VALUES ('garbage', 'ok', 1);
+            PRAGMA foreign_keys = ON;
+            "#
+        )
+        .execute(&mut *conn)
+        .await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn check_integrity_reports_every_finding() -> Result<()> {
+        let (_dir, db) = temp_db().await?;
+        seed(&db).await?;
+
+        let findings: Vec<(&str, String, u64, Vec<i64>, bool)> = db.check_integrity().await?
+            .into_iter()
+            .map(|f| (f.table, f.problem, f.rows, f.examples, f.repairable))
+            .collect();
+        let expected: Vec<(&str, &str, u64, Vec<i64>, bool)> = vec![
+            ("snapshots", "unparsable timestamp", 1, vec![101], true),
+            ("snapshots", "missing coin", 1, vec![102], true),
+            ("snapshots", "negative price_usd", 1, vec![103], true),
+            ("snapshots", "negative market_cap_usd", 1, vec![104], true),
+            ("snapshots", "rank below 1", 1, vec![105], true),
+            ("snapshots", "24h change below -100%", 1, vec![106], true),
+            ("snapshots", "duplicate coin and timestamp", 1, vec![107], true),
+            ("snapshots_hourly", "unparsable bucket timestamp", 1, vec![1], false),
+            ("snapshots_hourly", "missing coin", 1, vec![2], false),
+            ("snapshots_daily", "unparsable bucket timestamp", 1, vec![1], false),
+            ("snapshots_daily", "missing coin", 1, vec![2], false),
+            ("scrape_runs", "unparsable start time", 1, vec![1], false),
+        ];
+        let expected: Vec<_> = expected.into_iter().map(|(t, p, r, e, rep)| (t, p.to_string(), r, e, rep)).collect();
+        assert_eq!(findings, expected);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn repair_quarantines_bad_snapshots_with_their_source() -> Result<()> {
+        let (_dir, db) = temp_db().await?;
+        seed(&db).await?;
+
+        let report = db.repair().await?;
+        assert_eq!(report.quarantined.len(), 7);
+        assert!(report.quarantined.iter().all(|(_, rows)| *rows == 1));
+
+        let quarantined: Vec<(i64, String, String)> = sqlx::query_as(
+            "SELECT original_id, source, reason FROM snapshots_quarantine ORDER BY original_id"
+        )
+        .fetch_all(&db.pool)
+        .await?;
+        assert_eq!(quarantined.first(), Some(&(101, "import".to_string(), "unparsable timestamp".to_string())));
+        assert_eq!(quarantined.last(), Some(&(107, "backfill".to_string(), "duplicate coin and timestamp".to_string())));
+        assert_eq!(quarantined.len(), 7);
+
+        // Only the first snapshot is left, and only unrepairable findings
+        assert_eq!(db.get_snapshot_count().await?, 1);
+        assert!(db.check_integrity().await?.iter().all(|f| !f.repairable));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn repair_tolerates_reused_snapshot_ids() -> Result<()> {
+        let (_dir, db) = temp_db().await?;
+        db.save_coins(&[coin(1, "BTC", 1, 100.0, "2026-01-01T00:00:00Z")]).await?;
+        for price in [-1.0, -2.0] {
+            sqlx::query("INSERT INTO snapshots (id, coin_id, ts_utc, price_usd) VALUES (42, 1, 1767229200000, ?)")
+                .bind(price)
+                .execute(&db.pool)
+                .await?;
+            db.repair().await?;
+        }
+
+        let quarantined: Vec<(i64, f64)> = sqlx::query_as("SELECT original_id, price_usd FROM snapshots_quarantine ORDER BY id")
+            .fetch_all(&db.pool)
+            .await?;
+        assert_eq!(quarantined, vec![(42, -1.0), (42, -2.0)]);
+        Ok(())
+    }
+}
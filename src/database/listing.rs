@@ -0,0 +1,491 @@
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use std::fmt;
+use std::str::FromStr;
+use utoipa::ToSchema;
+
+use super::{coin_response, Database, EpochMillis};
+use crate::model::CoinResponse;
+
+/// Column to sort coins by.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CoinSort {
+    #[default]
+    Rank,
+    Price,
+    MarketCap,
+    #[serde(rename = "change_24h")]
+    Change24h,
+}
+
+impl CoinSort {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Rank => "rank",
+            Self::Price => "price",
+            Self::MarketCap => "market_cap",
+            Self::Change24h => "change_24h",
+        }
+    }
+
+    fn column(&self) -> &'static str {
+        match self {
+            Self::Rank => "s.cmc_rank",
+            Self::Price => "s.price_usd",
+            Self::MarketCap => "s.market_cap_usd",
+            Self::Change24h => "s.change_24h",
+        }
+    }
+
+    /// Rank reads best ascending, everything else descending.
+    pub fn default_order(&self) -> SortOrder {
+        match self {
+            Self::Rank => SortOrder::Asc,
+            _ => SortOrder::Desc,
+        }
+    }
+}
+
+impl FromStr for CoinSort {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "rank" => Ok(Self::Rank),
+            "price" => Ok(Self::Price),
+            "market_cap" => Ok(Self::MarketCap),
+            "change_24h" => Ok(Self::Change24h),
+            other => bail!("unknown sort '{}', expected rank, price, market_cap or change_24h", other),
+        }
+    }
+}
+
+impl fmt::Display for CoinSort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+impl SortOrder {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Asc => "asc",
+            Self::Desc => "desc",
+        }
+    }
+}
+
+impl FromStr for SortOrder {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "asc" => Ok(Self::Asc),
+            "desc" => Ok(Self::Desc),
+            other => bail!("unknown order '{}', expected asc or desc", other),
+        }
+    }
+}
+
+impl fmt::Display for SortOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Raw snapshots plus the hourly and daily rollups of compacted ranges, for
+/// looking up past scrapes. A rollup is stamped with the last millisecond of
+/// its bucket, as it holds values seen up to then.
+///
+/// Only rows stamped from `since`, when given, through `until` are read. Both
+/// are SQL expressions; they are shifted onto each table's own time column so
+/// its index is used.
+pub(super) fn as_of_snapshots(since: Option<&str>, until: &str) -> String {
+    let arm = |table: &str, stamp: &str, price: &str, column: &str, end: i64| {
+        let bound = |op: &str, value: &str| match end {
+            0 => format!("{} {} {}", column, op, value),
+            end => format!("{} {} {} - {}", column, op, value, end),
+        };
+        let lower = since.map(|since| format!("{} AND ", bound(">=", since))).unwrap_or_default();
+        format!(
+            "SELECT coin_id, {} AS ts_utc, cmc_rank, {} AS price_usd, market_cap_usd, change_24h FROM {} WHERE {}{}",
+            stamp, price, table, lower, bound("<=", until),
+        )
+    };
+    format!(
+        "(\n    {}\n    UNION ALL\n    {}\n    UNION ALL\n    {}\n)",
+        arm("snapshots", "ts_utc", "price_usd", "ts_utc", 0),
+        arm("snapshots_hourly", "bucket_ts + 3599999", "close_usd", "bucket_ts", 3_599_999),
+        arm("snapshots_daily", "bucket_ts + 86399999", "close_usd", "bucket_ts", 86_399_999),
+    )
+}
+
+/// Which coins of the latest scrape `list_coins` returns, and in what order.
+/// Unset filters don't filter.
+#[derive(Debug, Clone)]
+pub struct CoinQuery {
+    /// List the last scrape at or before this time instead of the latest
+    pub at: Option<DateTime<Utc>>,
+    pub sort: CoinSort,
+    /// Defaults to `sort.default_order()`
+    pub order: Option<SortOrder>,
+    pub symbols: Option<Vec<String>>,
+    /// Case-insensitive substring of the coin name
+    pub search: Option<String>,
+    pub min_market_cap: Option<f64>,
+    pub max_market_cap: Option<f64>,
+    pub min_rank: Option<u64>,
+    pub max_rank: Option<u64>,
+    pub limit: u32,
+    pub offset: u32,
+    /// Continue after the last coin of a previous page
+    pub cursor: Option<Cursor>,
+}
+
+impl Default for CoinQuery {
+    fn default() -> Self {
+        Self {
+            at: None,
+            sort: CoinSort::default(),
+            order: None,
+            symbols: None,
+            search: None,
+            min_market_cap: None,
+            max_market_cap: None,
+            min_rank: None,
+            max_rank: None,
+            limit: Self::DEFAULT_LIMIT,
+            offset: 0,
+            cursor: None,
+        }
+    }
+}
+
+impl CoinQuery {
+    /// Page size when none is given.
+    pub const DEFAULT_LIMIT: u32 = 100;
+
+    pub fn order(&self) -> SortOrder {
+        self.order.unwrap_or_else(|| self.sort.default_order())
+    }
+}
+
+/// One page of `list_coins`.
+#[derive(Debug, Clone)]
+pub struct CoinPage {
+    pub coins: Vec<CoinResponse>,
+    /// Coins matching the filters across all pages
+    pub total: u64,
+    /// Cursor for the next page, if there is one
+    pub next_cursor: Option<Cursor>,
+}
+
+/// Position after a coin in a sorted listing. It pins the scrape being paged
+/// through, so a scrape landing mid-way doesn't shift later pages.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cursor {
+    pub ts_utc: i64,
+    pub sort: CoinSort,
+    pub order: SortOrder,
+    /// Whether the last coin had no value in the sort column; those sort last
+    null: bool,
+    key: f64,
+    id: i64,
+}
+
+/// Cursors are opaque to clients: the fields, colon-separated and hex-encoded.
+impl fmt::Display for Cursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = format!("{}:{}:{}:{}:{}:{}", self.ts_utc, self.sort, self.order, self.null as u8, self.key, self.id);
+        text.bytes().try_for_each(|b| write!(f, "{:02x}", b))
+    }
+}
+
+impl FromStr for Cursor {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || anyhow!("invalid cursor '{}'", s);
+        if !s.is_ascii() || !s.len().is_multiple_of(2) {
+            return Err(invalid());
+        }
+        let bytes = (0..s.len()).step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| invalid())?;
+        let text = String::from_utf8(bytes).map_err(|_| invalid())?;
+        let fields: Vec<&str> = text.split(':').collect();
+        let [ts_utc, sort, order, null, key, id] = fields[..] else {
+            return Err(invalid());
+        };
+        (|| -> Result<Self> {
+            Ok(Self {
+                ts_utc: ts_utc.parse()?,
+                sort: sort.parse()?,
+                order: order.parse()?,
+                null: null == "1",
+                key: key.parse()?,
+                id: id.parse()?,
+            })
+        })().with_context(invalid)
+    }
+}
+
+impl Database {
+    /// Filter, sort and page through the coins of the latest scrape run, of
+    /// the last one at or before `query.at`, or of the run a cursor was
+    /// issued for. Coins without a rank are left out,
+    /// as in `get_latest_coins_api`; coins missing the sort value come last.
+    pub async fn list_coins(&self, query: &CoinQuery) -> Result<CoinPage> {
+        // The latest scrape is always raw; older ones may only survive in rollups
+        let source = if query.at.is_some() { as_of_snapshots(Some("?1"), "?1") } else { "snapshots".to_string() };
+        let ts_utc = match (&query.cursor, query.at) {
+            (Some(cursor), _) => Some(cursor.ts_utc),
+            (None, Some(at)) => self.scrape_at(at).await?.map(|ts| ts.timestamp_millis()),
+            (None, None) => sqlx::query("SELECT MAX(ts_utc) AS ts FROM snapshots")
+                .fetch_one(&self.pool)
+                .await?
+                .get::<Option<i64>, _>("ts"),
+        };
+        let Some(ts_utc) = ts_utc else {
+            return Ok(CoinPage { coins: Vec::new(), total: 0, next_cursor: None });
+        };
+
+        let column = query.sort.column();
+        let sign = if query.order() == SortOrder::Desc { "-" } else { "" };
+        // Everything sorts ascending on (missing, ±value, id), so one row
+        // comparison finds the rows after a cursor in either direction
+        let key = format!("COALESCE(CAST({}{} AS REAL), 0.0)", sign, column);
+        let filters = format!(r#"
+            FROM {source} s
+            JOIN coins c ON s.coin_id = c.id
+            WHERE s.ts_utc = ?1
+            AND s.cmc_rank IS NOT NULL
+            AND (?2 IS NULL OR c.symbol IN (SELECT value FROM json_each(?2)))
+            AND (?3 IS NULL OR c.name LIKE ?3 ESCAPE '\')
+            AND (?4 IS NULL OR s.market_cap_usd >= ?4)
+            AND (?5 IS NULL OR s.market_cap_usd <= ?5)
+            AND (?6 IS NULL OR s.cmc_rank >= ?6)
+            AND (?7 IS NULL OR s.cmc_rank <= ?7)
+        "#);
+        let after = if query.cursor.is_some() {
+            format!("AND ({} IS NULL, {}, c.id) > (?10, ?11, ?12)", column, key)
+        } else {
+            String::new()
+        };
+
+        let count_sql = format!("SELECT COUNT(*) AS total {}", filters);
+        let page_sql = format!(
+            r#"
+            SELECT c.id, c.name, c.symbol, s.cmc_rank, s.price_usd, s.market_cap_usd, s.change_24h, s.ts_utc,
+                   {column} IS NULL AS sort_null, {key} AS sort_key
+            {filters}
+            {after}
+            ORDER BY {column} IS NULL, {key}, c.id
+            LIMIT ?8 OFFSET ?9
+            "#,
+        );
+
+        let symbols = query.symbols.as_ref().map(|symbols| {
+            serde_json::Value::from(symbols.iter().map(|s| s.to_uppercase()).collect::<Vec<_>>()).to_string()
+        });
+        let search = query.search.as_ref().map(|term| {
+            format!("%{}%", term.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_"))
+        });
+
+        let total: i64 = sqlx::query(&count_sql)
+            .bind(ts_utc)
+            .bind(&symbols)
+            .bind(&search)
+            .bind(query.min_market_cap)
+            .bind(query.max_market_cap)
+            .bind(query.min_rank.map(|r| r as i64))
+            .bind(query.max_rank.map(|r| r as i64))
+            .fetch_one(&self.pool)
+            .await?
+            .get("total");
+
+        // One extra row tells whether there is a next page
+        let mut page = sqlx::query(&page_sql)
+            .bind(ts_utc)
+            .bind(&symbols)
+            .bind(&search)
+            .bind(query.min_market_cap)
+            .bind(query.max_market_cap)
+            .bind(query.min_rank.map(|r| r as i64))
+            .bind(query.max_rank.map(|r| r as i64))
+            .bind(query.limit as i64 + 1)
+            .bind(query.offset as i64);
+        if let Some(cursor) = &query.cursor {
+            page = page.bind(cursor.null).bind(cursor.key).bind(cursor.id);
+        }
+        let mut rows = page.fetch_all(&self.pool).await?;
+
+        let more = rows.len() > query.limit as usize;
+        rows.truncate(query.limit as usize);
+        let next_cursor = match rows.last() {
+            Some(last) if more => Some(Cursor {
+                ts_utc,
+                sort: query.sort,
+                order: query.order(),
+                null: last.get::<i64, _>("sort_null") != 0,
+                key: last.get("sort_key"),
+                id: last.get("id"),
+            }),
+            _ => None,
+        };
+        let coins = rows.iter().map(coin_response).collect::<Result<Vec<_>>>()?;
+
+        Ok(CoinPage { coins, total: total as u64, next_cursor })
+    }
+
+    /// Time of the last scrape at or before `at`, counting rollup buckets
+    /// that had closed by then.
+    pub async fn scrape_at(&self, at: DateTime<Utc>) -> Result<Option<DateTime<Utc>>> {
+        let row = sqlx::query(&format!("SELECT MAX(ts_utc) AS ts_utc FROM {}", as_of_snapshots(None, "?1")))
+            .bind(EpochMillis(at))
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(row.try_get::<Option<EpochMillis>, _>("ts_utc")?.map(Into::into))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::testing::{coin, temp_db, ts};
+
+    /// Page through a listing with cursors, `limit` coins at a time.
+    async fn page_ids(db: &Database, mut query: CoinQuery) -> Result<Vec<Vec<u64>>> {
+        let mut pages = Vec::new();
+        loop {
+            let page = db.list_coins(&query).await?;
+            pages.push(page.coins.iter().map(|c| c.id).collect());
+            match page.next_cursor {
+                Some(cursor) => query.cursor = Some(cursor.to_string().parse()?),
+                None => return Ok(pages),
+            }
+        }
+    }
+
+    #[test]
+    fn default_query_has_a_page_size() {
+        assert_eq!(CoinQuery::default().limit, CoinQuery::DEFAULT_LIMIT);
+    }
+
+    #[tokio::test]
+    async fn cursors_page_in_order_with_missing_values_last() -> Result<()> {
+        let (_dir, db) = temp_db().await?;
+        let at = "2026-01-01T00:00:00Z";
+        let mut coins = vec![
+            coin(1, "BTC", 1, 100.0, at),
+            coin(2, "ETH", 2, 50.0, at),
+            coin(3, "USDT", 3, 1.0, at),
+            coin(4, "XRP", 4, 50.0, at),
+            coin(5, "BNB", 5, 20.0, at),
+        ];
+        coins[2].price_usd = None;
+        db.save_coins(&coins).await?;
+
+        let query = CoinQuery { sort: CoinSort::Price, limit: 2, ..CoinQuery::default() };
+        assert_eq!(page_ids(&db, query.clone()).await?, vec![vec![1, 2], vec![4, 5], vec![3]]);
+
+        let asc = CoinQuery { order: Some(SortOrder::Asc), ..query };
+        assert_eq!(page_ids(&db, asc).await?, vec![vec![5, 2], vec![4, 1], vec![3]]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cursors_round_trip_through_hex() -> Result<()> {
+        let cursor = Cursor { ts_utc: 1_767_225_600_000, sort: CoinSort::Change24h, order: SortOrder::Desc, null: true, key: -1.5, id: 1027 };
+        let text = cursor.to_string();
+        assert!(text.bytes().all(|b| b.is_ascii_hexdigit()));
+        assert_eq!(text.parse::<Cursor>()?, cursor);
+        assert!("zz".parse::<Cursor>().is_err());
+        assert!("616263".parse::<Cursor>().is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cursors_stay_on_their_scrape() -> Result<()> {
+        let (_dir, db) = temp_db().await?;
+        let first = "2026-01-01T00:00:00Z";
+        db.save_coins(&[coin(1, "BTC", 1, 100.0, first), coin(2, "ETH", 2, 50.0, first), coin(3, "XRP", 3, 2.0, first)]).await?;
+
+        let query = CoinQuery { limit: 2, ..CoinQuery::default() };
+        let page = db.list_coins(&query).await?;
+        let cursor = page.next_cursor.expect("a second page");
+
+        // A new scrape reorders everything and adds a coin
+        let second = "2026-01-01T01:00:00Z";
+        db.save_coins(&[coin(3, "XRP", 1, 2.0, second), coin(4, "BNB", 2, 20.0, second), coin(1, "BTC", 3, 100.0, second)]).await?;
+
+        let rest = db.list_coins(&CoinQuery { cursor: Some(cursor), ..query }).await?;
+        assert_eq!(rest.coins.iter().map(|c| c.id).collect::<Vec<_>>(), vec![3]);
+        assert_eq!(rest.coins[0].rank, Some(3));
+        assert_eq!(rest.coins[0].ts_utc, ts(first));
+        assert_eq!(rest.total, 3);
+        assert!(rest.next_cursor.is_none());
+        Ok(())
+    }
+
+    /// BTC and ETH compacted into the 10:00 hourly bucket of the 1st, then a
+    /// raw scrape at noon.
+    async fn compacted() -> Result<(tempfile::TempDir, Database)> {
+        let (dir, db) = temp_db().await?;
+        let noon = "2026-01-01T12:00:00Z";
+        db.save_coins(&[coin(1, "BTC", 1, 120.0, noon), coin(1027, "ETH", 2, 12.0, noon)]).await?;
+        for (id, rank, close) in [(1, 1, 110.0), (1027, 2, 11.0)] {
+            sqlx::query(
+                "INSERT INTO snapshots_hourly (coin_id, bucket_ts, open_usd, high_usd, low_usd, close_usd, cmc_rank, market_cap_usd, change_24h, samples)
+                 VALUES (?1, ?2, ?3, ?3, ?3, ?3, ?4, NULL, NULL, 2)",
+            )
+            .bind(id)
+            .bind(EpochMillis(ts("2026-01-01T10:00:00Z")))
+            .bind(close)
+            .bind(rank)
+            .execute(&db.pool)
+            .await?;
+        }
+        Ok((dir, db))
+    }
+
+    #[tokio::test]
+    async fn scrape_at_counts_buckets_once_closed() -> Result<()> {
+        let (_dir, db) = compacted().await?;
+        let bucket_close = ts("2026-01-01T10:59:59.999Z");
+
+        assert_eq!(db.scrape_at(ts("2026-01-01T09:59:59Z")).await?, None);
+        assert_eq!(db.scrape_at(ts("2026-01-01T10:30:00Z")).await?, None);
+        assert_eq!(db.scrape_at(bucket_close).await?, Some(bucket_close));
+        assert_eq!(db.scrape_at(ts("2026-01-01T11:30:00Z")).await?, Some(bucket_close));
+        assert_eq!(db.scrape_at(ts("2026-01-01T12:00:00Z")).await?, Some(ts("2026-01-01T12:00:00Z")));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn lists_past_scrapes_from_rollups() -> Result<()> {
+        let (_dir, db) = compacted().await?;
+
+        let before = db.list_coins(&CoinQuery { at: Some(ts("2026-01-01T10:30:00Z")), ..CoinQuery::default() }).await?;
+        assert!(before.coins.is_empty());
+
+        let page = db.list_coins(&CoinQuery { at: Some(ts("2026-01-01T10:59:59.999Z")), ..CoinQuery::default() }).await?;
+        assert_eq!(page.coins.iter().map(|c| (c.id, c.price_usd)).collect::<Vec<_>>(), vec![(1, Some(110.0)), (1027, Some(11.0))]);
+        assert_eq!(page.coins[0].ts_utc, ts("2026-01-01T10:59:59.999Z"));
+
+        let latest = db.list_coins(&CoinQuery { at: Some(ts("2026-01-02T00:00:00Z")), ..CoinQuery::default() }).await?;
+        assert_eq!(latest.coins[0].price_usd, Some(120.0));
+        Ok(())
+    }
+}
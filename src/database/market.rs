@@ -0,0 +1,162 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sqlx::{sqlite::{SqlitePool, SqliteRow}, Row, Sqlite};
+
+use super::{Database, EpochMillis};
+use crate::model::MarketSummary;
+
+/// CoinMarketCap id of Bitcoin, for its dominance.
+pub const BTC_ID: u64 = 1;
+/// CoinMarketCap id of Ethereum, for its dominance.
+pub const ETH_ID: u64 = 1027;
+
+/// Recompute the summaries of the scrapes whose times `ts_query` selects,
+/// binding at most its one parameter.
+pub(super) fn summarize_sql(ts_query: &str) -> String {
+    format!(
+        r#"
+        INSERT OR REPLACE INTO market_summaries
+        SELECT s.ts_utc,
+               SUM(s.market_cap_usd),
+               (SUM(CASE WHEN s.change_24h > -100 THEN s.market_cap_usd END)
+                / SUM(CASE WHEN s.change_24h > -100 THEN s.market_cap_usd / (1 + s.change_24h / 100.0) END) - 1) * 100,
+               COUNT(*),
+               100.0 * SUM(CASE WHEN s.coin_id = {btc} THEN s.market_cap_usd END) / SUM(s.market_cap_usd),
+               100.0 * SUM(CASE WHEN s.coin_id = {eth} THEN s.market_cap_usd END) / SUM(s.market_cap_usd),
+               100.0 * TOTAL(CASE WHEN c.symbol IN (SELECT symbol FROM stablecoins) THEN s.market_cap_usd END) / SUM(s.market_cap_usd),
+               COUNT(CASE WHEN s.change_24h > 0 THEN 1 END),
+               COUNT(CASE WHEN s.change_24h < 0 THEN 1 END),
+               COUNT(CASE WHEN s.change_24h = 0 THEN 1 END)
+        FROM snapshots s
+        JOIN coins c ON s.coin_id = c.id
+        WHERE s.ts_utc IN ({ts_query})
+        GROUP BY s.ts_utc
+        "#,
+        btc = BTC_ID,
+        eth = ETH_ID,
+    )
+}
+
+/// Summarize every stored scrape if nothing has been summarized yet, as
+/// when a database predating summaries is first opened.
+pub(super) async fn backfill(pool: &SqlitePool) -> Result<()> {
+    sqlx::query(&summarize_sql("SELECT DISTINCT ts_utc FROM snapshots WHERE NOT EXISTS (SELECT 1 FROM market_summaries)"))
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Recompute the summaries of the scrapes at `times`, inside a save.
+pub(super) async fn summarize_at(tx: &mut sqlx::Transaction<'_, Sqlite>, times: &[DateTime<Utc>]) -> Result<()> {
+    let times: Vec<i64> = times.iter().map(|ts| ts.timestamp_millis()).collect();
+    sqlx::query(&summarize_sql("SELECT value FROM json_each(?1)"))
+        .bind(serde_json::Value::from(times).to_string())
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
+
+fn market_summary(row: &SqliteRow) -> Result<MarketSummary> {
+    Ok(MarketSummary {
+        ts_utc: row.try_get::<EpochMillis, _>("ts_utc")?.into(),
+        total_market_cap_usd: row.get("total_market_cap_usd"),
+        change_24h_pct: row.get("change_24h_pct"),
+        coins: row.get::<i64, _>("coins") as u64,
+        btc_dominance_pct: row.get("btc_dominance_pct"),
+        eth_dominance_pct: row.get("eth_dominance_pct"),
+        stablecoin_share_pct: row.get("stablecoin_share_pct"),
+        advancers: row.get::<i64, _>("advancers") as u64,
+        decliners: row.get::<i64, _>("decliners") as u64,
+        unchanged: row.get::<i64, _>("unchanged") as u64,
+    })
+}
+
+impl Database {
+    /// Market summary of the latest scrape.
+    pub async fn market_summary(&self) -> Result<Option<MarketSummary>> {
+        sqlx::query("SELECT * FROM market_summaries ORDER BY ts_utc DESC LIMIT 1")
+            .fetch_optional(&self.pool)
+            .await?
+            .as_ref()
+            .map(market_summary)
+            .transpose()
+    }
+
+    /// Market summaries within `[since, until)`, oldest first and capped at
+    /// `limit`. Summaries outlive compaction, so this covers every scrape.
+    pub async fn market_history(&self, since: Option<DateTime<Utc>>, until: Option<DateTime<Utc>>, limit: Option<u32>) -> Result<Vec<MarketSummary>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT * FROM market_summaries
+            WHERE (?1 IS NULL OR ts_utc >= ?1)
+              AND (?2 IS NULL OR ts_utc < ?2)
+            ORDER BY ts_utc ASC
+            LIMIT ?3
+            "#
+        )
+        .bind(since.map(EpochMillis))
+        .bind(until.map(EpochMillis))
+        .bind(limit.map_or(-1, i64::from))
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter().map(market_summary).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::testing::{coin, temp_db, ts};
+
+    /// $1000 of market cap: BTC 600 up 10%, ETH 200 down 5%, USDT 100 flat
+    /// and XRP 100 with no 24h change.
+    async fn seed(db: &Database) -> Result<()> {
+        let at = "2026-01-01T00:00:00Z";
+        let mut coins = vec![
+            coin(BTC_ID, "BTC", 1, 0.6, at),
+            coin(ETH_ID, "ETH", 2, 0.2, at),
+            coin(825, "USDT", 3, 0.1, at),
+            coin(52, "XRP", 4, 0.1, at),
+        ];
+        coins[0].chg24h_pct = Some(10.0);
+        coins[1].chg24h_pct = Some(-5.0);
+        coins[3].chg24h_pct = None;
+        db.save_coins(&coins).await
+    }
+
+    fn assert_fixture(summary: &MarketSummary) {
+        let close = |a: Option<f64>, b: f64| assert!(a.is_some_and(|a| (a - b).abs() < 1e-9), "{:?} != {}", a, b);
+        assert_eq!(summary.ts_utc, ts("2026-01-01T00:00:00Z"));
+        close(summary.total_market_cap_usd, 1000.0);
+        close(summary.btc_dominance_pct, 60.0);
+        close(summary.eth_dominance_pct, 20.0);
+        close(summary.stablecoin_share_pct, 10.0);
+        // XRP has no change, so the market moved from 600/1.1 + 200/0.95 + 100 to 900
+        close(summary.change_24h_pct, (900.0 / (600.0 / 1.1 + 200.0 / 0.95 + 100.0) - 1.0) * 100.0);
+        assert_eq!(summary.coins, 4);
+        assert_eq!((summary.advancers, summary.decliners, summary.unchanged), (1, 1, 1));
+    }
+
+    #[tokio::test]
+    async fn summarizes_each_saved_scrape() -> Result<()> {
+        let (_dir, db) = temp_db().await?;
+        seed(&db).await?;
+        assert_fixture(&db.market_summary().await?.expect("a summary"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn backfills_a_database_without_summaries() -> Result<()> {
+        let (dir, db) = temp_db().await?;
+        seed(&db).await?;
+        sqlx::query("DELETE FROM market_summaries").execute(&db.pool).await?;
+        db.pool.close().await;
+
+        let db = Database::new(&format!("sqlite:{}", dir.path().join("test.db").display())).await?;
+        let history = db.market_history(None, None, None).await?;
+        assert_eq!(history.len(), 1);
+        assert_fixture(&history[0]);
+        Ok(())
+    }
+}
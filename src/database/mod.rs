@@ -0,0 +1,662 @@
+pub mod backup;
+pub mod compare;
+pub mod diff;
+pub mod doctor;
+pub mod listing;
+pub mod market;
+pub mod movers;
+pub mod retention;
+pub mod runs;
+pub mod time;
+pub mod updates;
+
+use anyhow::{Context, Result};
+use futures::{Stream, StreamExt};
+use serde::Serialize;
+use sqlx::{migrate::Migrator, sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions, SqliteRow}, Row};
+use chrono::{DateTime, Utc};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::config::DatabaseConfig;
+use crate::model::{CoinRow, CoinResponse, LatestCoinsResponse};
+
+pub use backup::{BackupReport, RestoreReport};
+pub use doctor::{Finding, RepairReport};
+pub use compare::{CompareMode, CompareQuery};
+pub use listing::{CoinPage, CoinQuery, CoinSort, Cursor, SortOrder};
+pub use movers::{MoverDirection, MoversFilter, MoverWindow};
+pub use retention::{CompactReport, RetentionPolicy};
+pub use runs::{RunStatus, ScrapeRun};
+pub use time::EpochMillis;
+
+/// Schema migrations embedded from `migrations/`.
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+pub struct Database {
+    pool: SqlitePool,
+    updates: updates::Updates,
+}
+
+/// Where a snapshot came from, stored in `snapshots.source`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    /// Saved by a live scrape
+    Scrape,
+    /// Loaded with `import`
+    Import,
+    /// Reconstructed from an archive by `backfill`
+    Backfill,
+}
+
+impl Source {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Scrape => "scrape",
+            Self::Import => "import",
+            Self::Backfill => "backfill",
+        }
+    }
+}
+
+impl FromStr for Source {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "scrape" => Ok(Self::Scrape),
+            "import" => Ok(Self::Import),
+            "backfill" => Ok(Self::Backfill),
+            other => anyhow::bail!("unknown source '{}', expected scrape, import or backfill", other),
+        }
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Which snapshots `stream_snapshots` returns. Unset fields don't filter.
+#[derive(Debug, Clone, Default)]
+pub struct SnapshotFilter {
+    pub symbols: Option<Vec<String>>,
+    pub min_rank: Option<u64>,
+    pub max_rank: Option<u64>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    /// Only the most recent scrape run
+    pub latest_only: bool,
+    /// Only raw snapshots from this source; rollups mix sources and are left out
+    pub source: Option<Source>,
+}
+
+// Filters are bound as nullable parameters so the statement text is static and
+// the row stream doesn't borrow anything but the pool. Rolled-up buckets are
+// included (at their close price) so exports cover the full retained history.
+const STREAM_SNAPSHOTS_SQL: &str = r#"
+SELECT s.coin_id, c.name, c.symbol, s.ts_utc, s.cmc_rank, s.price_usd, s.market_cap_usd, s.change_24h
+FROM (
+    SELECT coin_id, ts_utc, cmc_rank, price_usd, market_cap_usd, change_24h, source FROM snapshots
+    UNION ALL
+    SELECT coin_id, bucket_ts, cmc_rank, close_usd, market_cap_usd, change_24h, NULL FROM snapshots_hourly
+    UNION ALL
+    SELECT coin_id, bucket_ts, cmc_rank, close_usd, market_cap_usd, change_24h, NULL FROM snapshots_daily
+) s
+JOIN coins c ON s.coin_id = c.id
+WHERE (?1 IS NULL OR c.symbol IN (SELECT value FROM json_each(?1)))
+  AND (?2 IS NULL OR s.cmc_rank >= ?2)
+  AND (?3 IS NULL OR s.cmc_rank <= ?3)
+  AND (?4 IS NULL OR s.ts_utc >= ?4)
+  AND (?5 IS NULL OR s.ts_utc < ?5)
+  AND (NOT ?6 OR s.ts_utc = (SELECT MAX(ts_utc) FROM snapshots))
+  AND (?7 IS NULL OR s.source = ?7)
+ORDER BY s.ts_utc ASC, s.cmc_rank IS NULL, s.cmc_rank ASC
+"#;
+
+impl Database {
+    pub async fn new(database_url: &str) -> Result<Self> {
+        Self::connect(&DatabaseConfig {
+            url: database_url.to_string(),
+            ..DatabaseConfig::default()
+        }).await
+    }
+
+    pub async fn connect(config: &DatabaseConfig) -> Result<Self> {
+        let options = SqliteConnectOptions::from_str(&config.url)
+            .with_context(|| format!("invalid database url '{}'", config.url))?
+            .busy_timeout(Duration::from_millis(config.busy_timeout_ms));
+        let pool = SqlitePoolOptions::new()
+            .max_connections(config.max_connections)
+            .connect_with(options)
+            .await?;
+
+        // Brings both fresh and pre-migration databases up to the current schema
+        MIGRATOR.run(&pool).await?;
+        market::backfill(&pool).await?;
+
+        Ok(Self { pool, updates: updates::Updates::new() })
+    }
+
+    pub async fn save_coins(&self, coins: &[CoinRow]) -> Result<()> {
+        self.save_coins_from(coins, Source::Scrape).await
+    }
+
+    /// Save snapshots tagged with where they came from.
+    pub async fn save_coins_from(&self, coins: &[CoinRow], source: Source) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        for coin in coins {
+            // Upsert coin (insert if not exists)
+            sqlx::query("INSERT OR IGNORE INTO coins (id, name, symbol) VALUES (?, ?, ?)")
+                .bind(coin.id as i64)
+                .bind(&coin.name)
+                .bind(&coin.symbol)
+                .execute(&mut *tx)
+                .await?;
+
+            // Insert snapshot
+            sqlx::query("INSERT INTO snapshots (coin_id, ts_utc, cmc_rank, price_usd, market_cap_usd, change_24h, source) VALUES (?, ?, ?, ?, ?, ?, ?)")
+                .bind(coin.id as i64)
+                .bind(EpochMillis(coin.scraped_at))
+                .bind(coin.rank.map(|r| r as i64))
+                .bind(coin.price_usd)
+                .bind(coin.market_cap_usd)
+                .bind(coin.chg24h_pct)
+                .bind(source.as_str())
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        let mut times: Vec<DateTime<Utc>> = coins.iter().map(|coin| coin.scraped_at).collect();
+        times.sort_unstable();
+        times.dedup();
+        market::summarize_at(&mut tx, &times).await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Coins that already have a snapshot at exactly this timestamp.
+    pub async fn coin_ids_at(&self, ts: DateTime<Utc>) -> Result<HashSet<u64>> {
+        let rows = sqlx::query("SELECT coin_id FROM snapshots WHERE ts_utc = ?")
+            .bind(EpochMillis(ts))
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows.iter().map(|row| row.get::<i64, _>("coin_id") as u64).collect())
+    }
+
+    /// Spans in `[since, until)` that hold data, ordered by start: each
+    /// distinct scrape time (as a 1ms span) plus every hourly and daily rollup
+    /// bucket, so compacted history doesn't look like missing history.
+    pub async fn covered_spans(&self, since: DateTime<Utc>, until: DateTime<Utc>) -> Result<Vec<(DateTime<Utc>, DateTime<Utc>)>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT DISTINCT ts_utc AS start_ts, ts_utc + 1 AS end_ts FROM snapshots
+            WHERE ts_utc >= ?1 AND ts_utc < ?2
+            UNION
+            SELECT DISTINCT bucket_ts, bucket_ts + 3600000 FROM snapshots_hourly
+            WHERE bucket_ts + 3600000 > ?1 AND bucket_ts < ?2
+            UNION
+            SELECT DISTINCT bucket_ts, bucket_ts + 86400000 FROM snapshots_daily
+            WHERE bucket_ts + 86400000 > ?1 AND bucket_ts < ?2
+            ORDER BY start_ts
+            "#
+        )
+        .bind(EpochMillis(since))
+        .bind(EpochMillis(until))
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut spans = Vec::with_capacity(rows.len());
+        for row in rows {
+            spans.push((
+                row.try_get::<EpochMillis, _>("start_ts")?.into(),
+                row.try_get::<EpochMillis, _>("end_ts")?.into(),
+            ));
+        }
+        Ok(spans)
+    }
+
+    /// Time of the first snapshot or rollup bucket, if any.
+    pub async fn earliest_snapshot_at(&self) -> Result<Option<DateTime<Utc>>> {
+        let row = sqlx::query(
+            r#"
+            SELECT MIN(ts) AS ts_utc FROM (
+                SELECT MIN(ts_utc) AS ts FROM snapshots
+                UNION ALL SELECT MIN(bucket_ts) FROM snapshots_hourly
+                UNION ALL SELECT MIN(bucket_ts) FROM snapshots_daily
+            )
+            "#
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(row.try_get::<Option<EpochMillis>, _>("ts_utc")?.map(Into::into))
+    }
+
+    pub async fn get_latest_coins(&self, limit: i64) -> Result<Vec<CoinSummary>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT c.name, c.symbol, s.cmc_rank, s.price_usd, s.market_cap_usd, s.change_24h
+            FROM snapshots s
+            JOIN coins c ON s.coin_id = c.id
+            WHERE s.ts_utc = (SELECT MAX(ts_utc) FROM snapshots)
+            AND s.cmc_rank IS NOT NULL
+            ORDER BY s.cmc_rank ASC
+            LIMIT ?
+            "#
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut coins = Vec::new();
+        for row in rows {
+            coins.push(CoinSummary {
+                name: row.get("name"),
+                symbol: row.get("symbol"), 
+                rank: row.get::<Option<i64>, _>("cmc_rank").map(|r| r as u64),
+                price_usd: row.get("price_usd"),
+                market_cap_usd: row.get("market_cap_usd"),
+                change_24h: row.get("change_24h"),
+            });
+        }
+
+        Ok(coins)
+    }
+
+    pub async fn get_latest_coins_api(&self, limit: i64) -> Result<Vec<CoinResponse>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT c.id, c.name, c.symbol, s.cmc_rank, s.price_usd, s.market_cap_usd, s.change_24h, s.ts_utc
+            FROM snapshots s
+            JOIN coins c ON s.coin_id = c.id
+            WHERE s.ts_utc = (SELECT MAX(ts_utc) FROM snapshots)
+            AND s.cmc_rank IS NOT NULL
+            ORDER BY s.cmc_rank ASC
+            LIMIT ?
+            "#
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut coins = Vec::new();
+        for row in rows {
+            coins.push(coin_response(&row)?);
+        }
+
+        Ok(coins)
+    }
+
+    pub async fn get_coin_latest_api(&self, symbol: &str) -> Result<Option<CoinResponse>> {
+        let row = sqlx::query(
+            r#"
+            SELECT c.id, c.name, c.symbol, s.cmc_rank, s.price_usd, s.market_cap_usd, s.change_24h, s.ts_utc
+            FROM snapshots s
+            JOIN coins c ON s.coin_id = c.id
+            WHERE c.symbol = ? AND s.ts_utc = (SELECT MAX(ts_utc) FROM snapshots WHERE coin_id = c.id)
+            "#
+        )
+        .bind(symbol.to_uppercase())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        if let Some(row) = row {
+            Ok(Some(coin_response(&row)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Latest snapshot of every coin matching one of `symbols` or `ids`, in
+    /// one query, plus the symbols and ids that matched nothing.
+    pub async fn get_coins_latest_api(&self, symbols: &[String], ids: &[u64]) -> Result<LatestCoinsResponse> {
+        let symbols: Vec<String> = symbols.iter().map(|s| s.to_uppercase()).collect();
+        let rows = sqlx::query(
+            r#"
+            SELECT c.id, c.name, c.symbol, s.cmc_rank, s.price_usd, s.market_cap_usd, s.change_24h, s.ts_utc
+            FROM snapshots s
+            JOIN coins c ON s.coin_id = c.id
+            WHERE (c.symbol IN (SELECT value FROM json_each(?1)) OR c.id IN (SELECT value FROM json_each(?2)))
+            AND s.ts_utc = (SELECT MAX(ts_utc) FROM snapshots WHERE coin_id = c.id)
+            ORDER BY s.cmc_rank IS NULL, s.cmc_rank, c.id
+            "#
+        )
+        .bind(serde_json::Value::from(symbols.clone()).to_string())
+        .bind(serde_json::Value::from(ids.to_vec()).to_string())
+        .fetch_all(&self.pool)
+        .await?;
+
+        let coins = rows.iter().map(coin_response).collect::<Result<Vec<_>>>()?;
+        let found_symbols: HashSet<&str> = coins.iter().map(|coin| coin.symbol.as_str()).collect();
+        let found_ids: HashSet<u64> = coins.iter().map(|coin| coin.id).collect();
+        let unresolved = symbols.iter().filter(|s| !found_symbols.contains(s.as_str())).cloned().collect();
+        let unresolved_ids = ids.iter().filter(|id| !found_ids.contains(id)).copied().collect();
+
+        Ok(LatestCoinsResponse { coins, unresolved, unresolved_ids })
+    }
+
+    /// Price history for a symbol within `[since, until)`, oldest first and
+    /// capped at `limit` points.
+    ///
+    /// Raw snapshots only cover the retention window; older ranges are read
+    /// from the hourly and daily rollups (using the bucket close) so callers
+    /// see one continuous series.
+    pub async fn get_coin_history(&self, symbol: &str, since: Option<DateTime<Utc>>, until: Option<DateTime<Utc>>, limit: Option<u32>) -> Result<Vec<HistoryPoint>> {
+        let since = EpochMillis(since.unwrap_or(DateTime::UNIX_EPOCH));
+        let rows = sqlx::query(
+            r#"
+            SELECT ts_utc, price_usd, market_cap_usd FROM (
+                SELECT s.ts_utc, s.price_usd, s.market_cap_usd
+                FROM snapshots s
+                JOIN coins c ON s.coin_id = c.id
+                WHERE c.symbol = ?1
+                UNION ALL
+                SELECT h.bucket_ts, h.close_usd, h.market_cap_usd
+                FROM snapshots_hourly h
+                JOIN coins c ON h.coin_id = c.id
+                WHERE c.symbol = ?1
+                UNION ALL
+                SELECT d.bucket_ts, d.close_usd, d.market_cap_usd
+                FROM snapshots_daily d
+                JOIN coins c ON d.coin_id = c.id
+                WHERE c.symbol = ?1
+            )
+            WHERE ts_utc >= ?2
+              AND (?3 IS NULL OR ts_utc < ?3)
+            ORDER BY ts_utc ASC
+            LIMIT ?4
+            "#
+        )
+        .bind(symbol)
+        .bind(since)
+        .bind(until.map(EpochMillis))
+        // SQLite treats a negative LIMIT as no limit
+        .bind(limit.map_or(-1, i64::from))
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut history = Vec::new();
+        for row in rows {
+            history.push(HistoryPoint {
+                timestamp: row.try_get::<EpochMillis, _>("ts_utc")?.into(),
+                price_usd: row.get("price_usd"),
+                market_cap_usd: row.get("market_cap_usd"),
+            });
+        }
+
+        Ok(history)
+    }
+
+    /// Price history since `since` of several coins in one query, oldest
+    /// first per coin, read like `get_coin_history`. Coins without points are
+    /// left out.
+    pub async fn get_coin_histories(&self, ids: &[u64], since: DateTime<Utc>) -> Result<HashMap<u64, Vec<HistoryPoint>>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT coin_id, ts_utc, price_usd, market_cap_usd FROM (
+                SELECT coin_id, ts_utc, price_usd, market_cap_usd FROM snapshots
+                WHERE coin_id IN (SELECT value FROM json_each(?1)) AND ts_utc >= ?2
+                UNION ALL
+                SELECT coin_id, bucket_ts, close_usd, market_cap_usd FROM snapshots_hourly
+                WHERE coin_id IN (SELECT value FROM json_each(?1)) AND bucket_ts >= ?2
+                UNION ALL
+                SELECT coin_id, bucket_ts, close_usd, market_cap_usd FROM snapshots_daily
+                WHERE coin_id IN (SELECT value FROM json_each(?1)) AND bucket_ts >= ?2
+            )
+            ORDER BY coin_id, ts_utc ASC
+            "#
+        )
+        .bind(serde_json::Value::from(ids.to_vec()).to_string())
+        .bind(EpochMillis(since))
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut histories: HashMap<u64, Vec<HistoryPoint>> = HashMap::new();
+        for row in rows {
+            histories.entry(row.get::<i64, _>("coin_id") as u64).or_default().push(HistoryPoint {
+                timestamp: row.try_get::<EpochMillis, _>("ts_utc")?.into(),
+                price_usd: row.get("price_usd"),
+                market_cap_usd: row.get("market_cap_usd"),
+            });
+        }
+        Ok(histories)
+    }
+
+    /// Stream matching snapshots oldest first, straight from a database cursor,
+    /// so arbitrarily large exports run in constant memory.
+    pub fn stream_snapshots(&self, filter: &SnapshotFilter) -> impl Stream<Item = Result<CoinRow>> + Send + '_ {
+        let symbols = filter.symbols.as_ref().map(|symbols| {
+            serde_json::Value::from(symbols.iter().map(|s| s.to_uppercase()).collect::<Vec<_>>()).to_string()
+        });
+
+        sqlx::query(STREAM_SNAPSHOTS_SQL)
+            .bind(symbols)
+            .bind(filter.min_rank.map(|r| r as i64))
+            .bind(filter.max_rank.map(|r| r as i64))
+            .bind(filter.since.map(EpochMillis))
+            .bind(filter.until.map(EpochMillis))
+            .bind(filter.latest_only)
+            .bind(filter.source.map(|s| s.as_str()))
+            .fetch(&self.pool)
+            .map(|row| {
+                let row = row?;
+                Ok(CoinRow {
+                    id: row.get::<i64, _>("coin_id") as u64,
+                    rank: row.get::<Option<i64>, _>("cmc_rank").map(|r| r as u64),
+                    name: row.get("name"),
+                    symbol: row.get("symbol"),
+                    price_usd: row.get("price_usd"),
+                    market_cap_usd: row.get("market_cap_usd"),
+                    chg24h_pct: row.get("change_24h"),
+                    scraped_at: row.try_get::<EpochMillis, _>("ts_utc")?.into(),
+                })
+            })
+    }
+
+    /// Time of the most recent scrape, if any.
+    pub async fn latest_snapshot_at(&self) -> Result<Option<DateTime<Utc>>> {
+        let row = sqlx::query("SELECT MAX(ts_utc) AS ts_utc FROM snapshots")
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(row.try_get::<Option<EpochMillis>, _>("ts_utc")?.map(Into::into))
+    }
+
+    /// Coin id to rank in the scrape before the latest one.
+    pub async fn get_previous_ranks(&self) -> Result<HashMap<u64, u64>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT coin_id, cmc_rank
+            FROM snapshots
+            WHERE cmc_rank IS NOT NULL
+            AND ts_utc = (
+                SELECT MAX(ts_utc) FROM snapshots
+                WHERE ts_utc < (SELECT MAX(ts_utc) FROM snapshots)
+            )
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter()
+            .map(|row| (row.get::<i64, _>("coin_id") as u64, row.get::<i64, _>("cmc_rank") as u64))
+            .collect())
+    }
+
+    pub async fn get_snapshot_count(&self) -> Result<i64> {
+        let row = sqlx::query("SELECT COUNT(*) as count FROM snapshots")
+            .fetch_one(&self.pool)
+            .await?;
+        
+        Ok(row.get("count"))
+    }
+}
+
+/// Columns: id, name, symbol, cmc_rank, price_usd, market_cap_usd, change_24h, ts_utc.
+fn coin_response(row: &SqliteRow) -> Result<CoinResponse> {
+    Ok(CoinResponse {
+        id: row.get::<i64, _>("id") as u64,
+        name: row.get("name"),
+        symbol: row.get("symbol"),
+        rank: row.get::<Option<i64>, _>("cmc_rank").map(|r| r as u64),
+        price_usd: row.get("price_usd"),
+        market_cap_usd: row.get("market_cap_usd"),
+        change_24h: row.get("change_24h"),
+        ts_utc: row.try_get::<EpochMillis, _>("ts_utc")?.into(),
+    })
+}
+
+#[derive(Debug)]
+pub struct CoinSummary {
+    pub name: String,
+    pub symbol: String,
+    pub rank: Option<u64>,
+    pub price_usd: Option<f64>,
+    pub market_cap_usd: Option<f64>,
+    pub change_24h: Option<f64>,
+}
+
+#[derive(Debug)]
+pub struct HistoryPoint {
+    pub timestamp: DateTime<Utc>,
+    pub price_usd: Option<f64>,
+    pub market_cap_usd: Option<f64>,
+}
+#[cfg(test)]
+pub(crate) mod testing {
+    use anyhow::Result;
+    use chrono::{DateTime, Utc};
+    use tempfile::TempDir;
+
+    use super::Database;
+    use crate::model::CoinRow;
+
+    /// A migrated database in a temporary directory, deleted with the
+    /// returned `TempDir`.
+    pub async fn temp_db() -> Result<(TempDir, Database)> {
+        let dir = tempfile::tempdir()?;
+        let url = format!("sqlite:{}?mode=rwc", dir.path().join("test.db").display());
+        let db = Database::new(&url).await?;
+        Ok((dir, db))
+    }
+
+    pub fn ts(s: &str) -> DateTime<Utc> {
+        s.parse().expect("valid RFC 3339 timestamp")
+    }
+
+    pub fn coin(id: u64, symbol: &str, rank: u64, price_usd: f64, scraped_at: &str) -> CoinRow {
+        CoinRow {
+            id,
+            rank: Some(rank),
+            name: symbol.to_lowercase(),
+            symbol: symbol.to_string(),
+            price_usd: Some(price_usd),
+            market_cap_usd: Some(price_usd * 1000.0),
+            chg24h_pct: Some(0.0),
+            scraped_at: ts(scraped_at),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use testing::{coin, temp_db, ts};
+
+    #[tokio::test]
+    async fn migrates_text_timestamps_to_epoch_millis() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let url = format!("sqlite:{}?mode=rwc", dir.path().join("legacy.db").display());
+
+        // A database from before versioned migrations: text timestamps, no
+        // _sqlx_migrations table
+        let pool = SqlitePool::connect(&url).await?;
+        sqlx::raw_sql(include_str!("../../migrations/001_initial.sql")).execute(&pool).await?;
+        sqlx::raw_sql(include_str!("../../migrations/002_rollups.sql")).execute(&pool).await?;
+        sqlx::raw_sql(
+            r#"
+            INSERT INTO coins (id, name, symbol) VALUES (1, 'Bitcoin', 'BTC');
+            INSERT INTO snapshots (coin_id, ts_utc, cmc_rank, price_usd) VALUES
+                (1, '2026-01-05T10:00:00Z', 1, 100.0),
+                (1, '2026-01-05T11:00:00.250+00:00', 1, 110.0),
+                (1, 'yesterday-ish', 1, 90.0);
+            INSERT INTO snapshots_hourly (coin_id, bucket_ts, close_usd, samples) VALUES
+                (1, '2026-01-04T10:00:00Z', 95.0, 3);
+            "#
+        )
+        .execute(&pool)
+        .await?;
+        pool.close().await;
+
+        let db = Database::new(&url).await?;
+        let snapshots: Vec<(String, i64, f64)> = sqlx::query_as("SELECT typeof(ts_utc), ts_utc, price_usd FROM snapshots ORDER BY ts_utc")
+            .fetch_all(&db.pool)
+            .await?;
+        assert_eq!(snapshots, vec![
+            ("integer".to_string(), ts("2026-01-05T10:00:00Z").timestamp_millis(), 100.0),
+            ("integer".to_string(), ts("2026-01-05T11:00:00.250Z").timestamp_millis(), 110.0),
+        ]);
+        let hourly: Vec<(i64,)> = sqlx::query_as("SELECT bucket_ts FROM snapshots_hourly").fetch_all(&db.pool).await?;
+        assert_eq!(hourly, vec![(ts("2026-01-04T10:00:00Z").timestamp_millis(),)]);
+
+        let quarantined: Vec<(i64, String, f64, String)> = sqlx::query_as("SELECT coin_id, ts_utc, price_usd, reason FROM snapshots_quarantine")
+            .fetch_all(&db.pool)
+            .await?;
+        assert_eq!(quarantined, vec![(1, "yesterday-ish".to_string(), 90.0, "unparsable timestamp".to_string())]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn coin_histories_group_points_by_coin() -> Result<()> {
+        let (_dir, db) = testing::temp_db().await?;
+        for (hour, btc, eth) in [(0, 100.0, 10.0), (1, 101.0, 11.0), (2, 102.0, 12.0)] {
+            let at = format!("2026-01-01T0{}:00:00Z", hour);
+            db.save_coins(&[testing::coin(1, "BTC", 1, btc, &at), testing::coin(1027, "ETH", 2, eth, &at)]).await?;
+        }
+        db.save_coins(&[testing::coin(5, "DOGE", 3, 0.1, "2026-01-01T02:00:00Z")]).await?;
+
+        let histories = db.get_coin_histories(&[1, 1027, 99], ts("2026-01-01T01:00:00Z")).await?;
+        let prices = |id: u64| histories[&id].iter().map(|p| p.price_usd.unwrap()).collect::<Vec<_>>();
+        assert_eq!(histories.len(), 2);
+        assert_eq!(prices(1), [101.0, 102.0]);
+        assert_eq!(prices(1027), [11.0, 12.0]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn batch_lookup_mixes_symbols_and_ids() -> Result<()> {
+        let (_dir, db) = temp_db().await?;
+        let first = "2026-01-01T00:00:00Z";
+        db.save_coins(&[coin(1, "BTC", 1, 100.0, first), coin(1027, "ETH", 2, 10.0, first), coin(52, "XRP", 3, 1.0, first)]).await?;
+        let second = "2026-01-01T01:00:00Z";
+        db.save_coins(&[coin(1, "BTC", 1, 101.0, second), coin(1027, "ETH", 2, 11.0, second)]).await?;
+
+        // XRP dropped out of the latest scrape but still has a latest snapshot
+        let found = db.get_coins_latest_api(&["eth".to_string(), "DOGE".to_string()], &[52, 1, 424242]).await?;
+        let coins: Vec<_> = found.coins.iter().map(|c| (c.id, c.price_usd, c.ts_utc)).collect();
+        assert_eq!(coins, vec![
+            (1, Some(101.0), ts(second)),
+            (1027, Some(11.0), ts(second)),
+            (52, Some(1.0), ts(first)),
+        ]);
+        assert_eq!(found.unresolved, vec!["DOGE"]);
+        assert_eq!(found.unresolved_ids, vec![424242]);
+
+        // A coin asked for by symbol and by id comes back once
+        let found = db.get_coins_latest_api(&["BTC".to_string(), "btc".to_string()], &[1]).await?;
+        assert_eq!(found.coins.iter().map(|c| c.id).collect::<Vec<_>>(), vec![1]);
+        assert!(found.unresolved.is_empty() && found.unresolved_ids.is_empty());
+
+        let found = db.get_coins_latest_api(&[], &[]).await?;
+        assert!(found.coins.is_empty() && found.unresolved.is_empty() && found.unresolved_ids.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,298 @@
+use anyhow::{bail, Result};
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use std::fmt;
+use std::str::FromStr;
+use utoipa::ToSchema;
+
+use super::{Database, EpochMillis};
+use crate::model::{Mover, MoversResponse};
+
+/// Period a move is measured over, ending at the latest scrape.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub enum MoverWindow {
+    #[serde(rename = "1h")]
+    Hour,
+    #[default]
+    #[serde(rename = "24h")]
+    Day,
+    #[serde(rename = "7d")]
+    Week,
+}
+
+impl MoverWindow {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Hour => "1h",
+            Self::Day => "24h",
+            Self::Week => "7d",
+        }
+    }
+
+    pub fn duration(&self) -> Duration {
+        match self {
+            Self::Hour => Duration::hours(1),
+            Self::Day => Duration::days(1),
+            Self::Week => Duration::days(7),
+        }
+    }
+}
+
+impl FromStr for MoverWindow {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "1h" => Ok(Self::Hour),
+            "24h" => Ok(Self::Day),
+            "7d" => Ok(Self::Week),
+            other => bail!("unknown window '{}', expected 1h, 24h or 7d", other),
+        }
+    }
+}
+
+impl fmt::Display for MoverWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Which side of the movers to return.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum MoverDirection {
+    Up,
+    Down,
+}
+
+impl FromStr for MoverDirection {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "up" => Ok(Self::Up),
+            "down" => Ok(Self::Down),
+            other => bail!("unknown direction '{}', expected up or down", other),
+        }
+    }
+}
+
+/// Which coins of the latest scrape `get_movers` ranks.
+#[derive(Debug, Clone)]
+pub struct MoversFilter {
+    pub window: MoverWindow,
+    /// Only gainers or only losers; both when unset
+    pub direction: Option<MoverDirection>,
+    /// Coins on each side
+    pub limit: u32,
+    pub min_market_cap: Option<f64>,
+    /// Only the N largest coins by market cap
+    pub top: Option<u32>,
+}
+
+impl Default for MoversFilter {
+    fn default() -> Self {
+        Self {
+            window: MoverWindow::default(),
+            direction: None,
+            limit: 10,
+            min_market_cap: None,
+            top: None,
+        }
+    }
+}
+
+impl Database {
+    /// Biggest gainers and losers of the latest scrape over a window.
+    ///
+    /// Each coin's change is measured against its newest stored price from
+    /// between one and two windows before the latest scrape, reading hourly
+    /// and daily rollups where raw snapshots were compacted. For the 24h
+    /// window, coins without such a price fall back to the scraped
+    /// `change_24h`; for the others they are left out. Gainers rose and
+    /// losers fell, so a coin that didn't move is on neither side.
+    pub async fn get_movers(&self, filter: &MoversFilter) -> Result<MoversResponse> {
+        let mut movers = MoversResponse {
+            window: filter.window.to_string(),
+            ..MoversResponse::default()
+        };
+        let Some(latest) = self.latest_snapshot_at().await? else {
+            return Ok(movers);
+        };
+        let target = latest - filter.window.duration();
+        let oldest = target - filter.window.duration();
+
+        let sides = [(MoverDirection::Up, ">", "DESC", &mut movers.gainers), (MoverDirection::Down, "<", "ASC", &mut movers.losers)];
+        for (direction, sign, order, side) in sides {
+            if filter.direction.is_some_and(|d| d != direction) {
+                continue;
+            }
+            let rows = sqlx::query(&format!(
+                r#"
+                WITH latest AS (
+                    SELECT c.id, c.name, c.symbol, s.cmc_rank, s.price_usd, s.market_cap_usd, s.change_24h, s.ts_utc
+                    FROM snapshots s
+                    JOIN coins c ON s.coin_id = c.id
+                    WHERE s.ts_utc = ?1
+                    AND s.cmc_rank IS NOT NULL
+                    AND (?2 IS NULL OR s.market_cap_usd >= ?2)
+                    AND (?3 < 0 OR s.coin_id IN (
+                        SELECT coin_id FROM snapshots
+                        WHERE ts_utc = ?1 AND market_cap_usd IS NOT NULL
+                        ORDER BY market_cap_usd DESC
+                        LIMIT ?3
+                    ))
+                ),
+                history AS (
+                    SELECT coin_id, ts_utc, price_usd FROM snapshots WHERE ts_utc BETWEEN ?4 AND ?5
+                    UNION ALL
+                    SELECT coin_id, bucket_ts, close_usd FROM snapshots_hourly WHERE bucket_ts BETWEEN ?4 AND ?5
+                    UNION ALL
+                    SELECT coin_id, bucket_ts, close_usd FROM snapshots_daily WHERE bucket_ts BETWEEN ?4 AND ?5
+                ),
+                base AS (
+                    SELECT coin_id, ts_utc, price_usd,
+                           ROW_NUMBER() OVER (PARTITION BY coin_id ORDER BY ts_utc DESC) AS n
+                    FROM history
+                    WHERE price_usd > 0
+                ),
+                moves AS (
+                    SELECT l.*, b.price_usd AS base_price_usd, b.ts_utc AS base_ts_utc,
+                           CASE WHEN b.price_usd IS NOT NULL THEN (l.price_usd / b.price_usd - 1.0) * 100.0
+                                WHEN ?6 THEN l.change_24h
+                           END AS change_pct
+                    FROM latest l
+                    LEFT JOIN base b ON b.coin_id = l.id AND b.n = 1
+                )
+                SELECT * FROM moves
+                WHERE change_pct {} 0
+                ORDER BY change_pct {}, cmc_rank ASC
+                LIMIT ?7
+                "#,
+                sign, order
+            ))
+            .bind(EpochMillis(latest))
+            .bind(filter.min_market_cap)
+            .bind(filter.top.map_or(-1, i64::from))
+            .bind(EpochMillis(oldest))
+            .bind(EpochMillis(target))
+            .bind(filter.window == MoverWindow::Day)
+            .bind(filter.limit as i64)
+            .fetch_all(&self.pool)
+            .await?;
+
+            for row in rows {
+                side.push(Mover {
+                    id: row.get::<i64, _>("id") as u64,
+                    symbol: row.get("symbol"),
+                    name: row.get("name"),
+                    rank: row.get::<Option<i64>, _>("cmc_rank").map(|r| r as u64),
+                    price_usd: row.get("price_usd"),
+                    market_cap_usd: row.get("market_cap_usd"),
+                    change_24h: row.get("change_24h"),
+                    ts_utc: row.try_get::<EpochMillis, _>("ts_utc")?.into(),
+                    change_pct: row.get("change_pct"),
+                    base_price_usd: row.get("base_price_usd"),
+                    base_ts_utc: row.try_get::<Option<EpochMillis>, _>("base_ts_utc")?.map(Into::into),
+                });
+            }
+        }
+
+        Ok(movers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::testing::{coin, temp_db, ts};
+    use crate::model::CoinRow;
+
+    fn ids(movers: &[Mover]) -> Vec<u64> {
+        movers.iter().map(|m| m.id).collect()
+    }
+
+    fn assert_sides(movers: &MoversResponse) {
+        assert!(movers.gainers.iter().all(|m| m.change_pct > 0.0), "{:?}", movers.gainers);
+        assert!(movers.losers.iter().all(|m| m.change_pct < 0.0), "{:?}", movers.losers);
+    }
+
+    /// Latest scrape on the 15th; XRP is only seen recently, and ETH has no
+    /// price from the day before.
+    async fn seeded() -> Result<(tempfile::TempDir, Database)> {
+        let (dir, db) = temp_db().await?;
+        let scrapes: Vec<Vec<CoinRow>> = vec![
+            vec![coin(1, "BTC", 1, 10.0, "2025-12-30T00:00:00Z")],
+            vec![coin(1, "BTC", 1, 50.0, "2026-01-03T00:00:00Z")],
+            vec![coin(1027, "ETH", 2, 20.0, "2026-01-05T00:00:00Z")],
+            vec![coin(1, "BTC", 1, 80.0, "2026-01-07T00:00:00Z")],
+            vec![coin(1, "BTC", 1, 90.0, "2026-01-10T00:00:00Z")],
+            vec![coin(52, "XRP", 3, 1.0, "2026-01-12T00:00:00Z")],
+            vec![coin(1, "BTC", 1, 110.0, "2026-01-14T00:00:00Z")],
+        ];
+        for scrape in scrapes {
+            db.save_coins(&scrape).await?;
+        }
+        let latest = "2026-01-15T00:00:00Z";
+        let mut xrp = coin(52, "XRP", 3, 2.0, latest);
+        xrp.chg24h_pct = Some(7.0);
+        let mut eth = coin(1027, "ETH", 2, 10.0, latest);
+        eth.chg24h_pct = Some(-3.0);
+        db.save_coins(&[coin(1, "BTC", 1, 100.0, latest), eth, xrp]).await?;
+        Ok((dir, db))
+    }
+
+    #[tokio::test]
+    async fn measures_against_the_newest_price_a_window_back() -> Result<()> {
+        let (_dir, db) = seeded().await?;
+        let movers = db.get_movers(&MoversFilter { window: MoverWindow::Week, ..MoversFilter::default() }).await?;
+
+        // XRP has no price from 7-14 days back, so it is left out
+        assert_sides(&movers);
+        assert_eq!(ids(&movers.gainers), vec![1]);
+        assert_eq!(ids(&movers.losers), vec![1027]);
+        let btc = &movers.gainers[0];
+        assert_eq!(btc.base_price_usd, Some(80.0));
+        assert_eq!(btc.base_ts_utc, Some(ts("2026-01-07T00:00:00Z")));
+        assert!((btc.change_pct - 25.0).abs() < 1e-9);
+        assert!((movers.losers[0].change_pct + 50.0).abs() < 1e-9);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn day_window_falls_back_to_the_scraped_change() -> Result<()> {
+        let (_dir, db) = seeded().await?;
+        let movers = db.get_movers(&MoversFilter::default()).await?;
+
+        assert_sides(&movers);
+        assert_eq!(ids(&movers.gainers), vec![52]);
+        assert_eq!(ids(&movers.losers), vec![1, 1027]);
+        let xrp = &movers.gainers[0];
+        assert_eq!(xrp.change_pct, 7.0);
+        assert_eq!(xrp.base_price_usd, None);
+        let btc = &movers.losers[0];
+        assert_eq!(btc.base_price_usd, Some(110.0));
+        assert!((btc.change_pct - (100.0 / 110.0 - 1.0) * 100.0).abs() < 1e-9);
+        assert_eq!(movers.losers[1].change_pct, -3.0);
+
+        let up = db.get_movers(&MoversFilter { direction: Some(MoverDirection::Up), ..MoversFilter::default() }).await?;
+        assert!(up.losers.is_empty());
+        assert_eq!(ids(&up.gainers), vec![52]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn top_keeps_the_largest_by_market_cap() -> Result<()> {
+        let (_dir, db) = seeded().await?;
+        let filter = MoversFilter { top: Some(2), limit: 5, ..MoversFilter::default() };
+        let movers = db.get_movers(&filter).await?;
+        assert_sides(&movers);
+        assert!(movers.gainers.is_empty());
+        assert_eq!(ids(&movers.losers), vec![1, 1027]);
+
+        let filter = MoversFilter { min_market_cap: Some(50_000.0), ..filter };
+        assert_eq!(ids(&db.get_movers(&filter).await?.losers), vec![1]);
+        Ok(())
+    }
+}
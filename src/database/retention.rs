@@ -0,0 +1,380 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use super::{Database, EpochMillis};
+
+/// How long each resolution of snapshot data is kept.
+///
+/// Raw snapshots newer than `raw_days` are left untouched. Older ones are
+/// rolled up into hourly buckets, hourly buckets older than `hourly_days` are
+/// rolled up into daily buckets, and daily buckets are kept forever unless
+/// `daily_days` is set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetentionPolicy {
+    pub raw_days: u32,
+    pub hourly_days: u32,
+    pub daily_days: Option<u32>,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            raw_days: 7,
+            hourly_days: 90,
+            daily_days: None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PolicyFile {
+    #[serde(default)]
+    retention: RetentionPolicy,
+}
+
+impl RetentionPolicy {
+    /// Load the `[retention]` table from a TOML policy file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read policy file {}", path.display()))?;
+        let file: PolicyFile = toml::from_str(&text)
+            .with_context(|| format!("invalid policy file {}", path.display()))?;
+        file.retention.validate()?;
+        Ok(file.retention)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.raw_days == 0 {
+            bail!("retention.raw_days must be at least 1");
+        }
+        if self.hourly_days < self.raw_days {
+            bail!("retention.hourly_days ({}) must not be shorter than retention.raw_days ({})",
+                  self.hourly_days, self.raw_days);
+        }
+        if let Some(daily_days) = self.daily_days
+            && daily_days < self.hourly_days {
+            bail!("retention.daily_days ({}) must not be shorter than retention.hourly_days ({})",
+                  daily_days, self.hourly_days);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct CompactReport {
+    pub raw_rows_rolled_up: u64,
+    pub hourly_buckets_written: u64,
+    pub hourly_rows_rolled_up: u64,
+    pub daily_buckets_written: u64,
+    pub daily_rows_deleted: u64,
+}
+
+// Both rollups keep OHLC of the price plus the last-seen rank, market cap and
+// 24h change. Buckets are keyed by their start time so the ON CONFLICT branch
+// only fires when late rows land in a bucket that was already compacted; the
+// times the open and close were seen decide which side's values win.
+const ROLLUP_HOURLY_SQL: &str = r#"
+INSERT INTO snapshots_hourly
+    (coin_id, bucket_ts, open_usd, high_usd, low_usd, close_usd, cmc_rank, market_cap_usd, change_24h, samples, open_ts, close_ts)
+SELECT coin_id, bucket, open_usd, high_usd, low_usd, close_usd, cmc_rank, market_cap_usd, change_24h, samples, open_ts, close_ts
+FROM (
+    SELECT coin_id, bucket,
+        FIRST_VALUE(price_usd) OVER oldest AS open_usd,
+        MAX(price_usd) OVER bucket_rows AS high_usd,
+        MIN(price_usd) OVER bucket_rows AS low_usd,
+        FIRST_VALUE(price_usd) OVER newest AS close_usd,
+        FIRST_VALUE(cmc_rank) OVER newest AS cmc_rank,
+        FIRST_VALUE(market_cap_usd) OVER newest AS market_cap_usd,
+        FIRST_VALUE(change_24h) OVER newest AS change_24h,
+        COUNT(*) OVER bucket_rows AS samples,
+        MIN(ts_utc) OVER bucket_rows AS open_ts,
+        MAX(ts_utc) OVER bucket_rows AS close_ts,
+        ROW_NUMBER() OVER newest AS rn
+    FROM (
+        SELECT coin_id, ts_utc, price_usd, cmc_rank, market_cap_usd, change_24h,
+            ts_utc - ts_utc % 3600000 AS bucket
+        FROM snapshots
+        WHERE ts_utc < ?
+    )
+    WINDOW bucket_rows AS (PARTITION BY coin_id, bucket),
+           oldest AS (PARTITION BY coin_id, bucket ORDER BY ts_utc ASC),
+           newest AS (PARTITION BY coin_id, bucket ORDER BY ts_utc DESC)
+)
+WHERE rn = 1
+ON CONFLICT (coin_id, bucket_ts) DO UPDATE SET
+    open_usd = CASE WHEN excluded.open_ts < COALESCE(open_ts, bucket_ts) THEN excluded.open_usd ELSE open_usd END,
+    open_ts = MIN(COALESCE(open_ts, bucket_ts), excluded.open_ts),
+    high_usd = MAX(COALESCE(high_usd, excluded.high_usd), COALESCE(excluded.high_usd, high_usd)),
+    low_usd = MIN(COALESCE(low_usd, excluded.low_usd), COALESCE(excluded.low_usd, low_usd)),
+    close_usd = CASE WHEN excluded.close_ts >= COALESCE(close_ts, bucket_ts) THEN excluded.close_usd ELSE close_usd END,
+    cmc_rank = CASE WHEN excluded.close_ts >= COALESCE(close_ts, bucket_ts) THEN excluded.cmc_rank ELSE cmc_rank END,
+    market_cap_usd = CASE WHEN excluded.close_ts >= COALESCE(close_ts, bucket_ts) THEN excluded.market_cap_usd ELSE market_cap_usd END,
+    change_24h = CASE WHEN excluded.close_ts >= COALESCE(close_ts, bucket_ts) THEN excluded.change_24h ELSE change_24h END,
+    close_ts = MAX(COALESCE(close_ts, bucket_ts), excluded.close_ts),
+    samples = samples + excluded.samples
+"#;
+
+const ROLLUP_DAILY_SQL: &str = r#"
+INSERT INTO snapshots_daily
+    (coin_id, bucket_ts, open_usd, high_usd, low_usd, close_usd, cmc_rank, market_cap_usd, change_24h, samples, open_ts, close_ts)
+SELECT coin_id, bucket, open_usd, high_usd, low_usd, close_usd, cmc_rank, market_cap_usd, change_24h, samples, open_ts, close_ts
+FROM (
+    SELECT coin_id, bucket,
+        FIRST_VALUE(open_usd) OVER oldest AS open_usd,
+        MAX(high_usd) OVER bucket_rows AS high_usd,
+        MIN(low_usd) OVER bucket_rows AS low_usd,
+        FIRST_VALUE(close_usd) OVER newest AS close_usd,
+        FIRST_VALUE(cmc_rank) OVER newest AS cmc_rank,
+        FIRST_VALUE(market_cap_usd) OVER newest AS market_cap_usd,
+        FIRST_VALUE(change_24h) OVER newest AS change_24h,
+        SUM(samples) OVER bucket_rows AS samples,
+        MIN(COALESCE(open_ts, bucket_ts)) OVER bucket_rows AS open_ts,
+        MAX(COALESCE(close_ts, bucket_ts + 3599999)) OVER bucket_rows AS close_ts,
+        ROW_NUMBER() OVER newest AS rn
+    FROM (
+        SELECT coin_id, bucket_ts, open_usd, high_usd, low_usd, close_usd, cmc_rank, market_cap_usd, change_24h, samples,
+            open_ts, close_ts, bucket_ts - bucket_ts % 86400000 AS bucket
+        FROM snapshots_hourly
+        WHERE bucket_ts < ?
+    )
+    WINDOW bucket_rows AS (PARTITION BY coin_id, bucket),
+           oldest AS (PARTITION BY coin_id, bucket ORDER BY bucket_ts ASC),
+           newest AS (PARTITION BY coin_id, bucket ORDER BY bucket_ts DESC)
+)
+WHERE rn = 1
+ON CONFLICT (coin_id, bucket_ts) DO UPDATE SET
+    open_usd = CASE WHEN excluded.open_ts < COALESCE(open_ts, bucket_ts) THEN excluded.open_usd ELSE open_usd END,
+    open_ts = MIN(COALESCE(open_ts, bucket_ts), excluded.open_ts),
+    high_usd = MAX(COALESCE(high_usd, excluded.high_usd), COALESCE(excluded.high_usd, high_usd)),
+    low_usd = MIN(COALESCE(low_usd, excluded.low_usd), COALESCE(excluded.low_usd, low_usd)),
+    close_usd = CASE WHEN excluded.close_ts >= COALESCE(close_ts, bucket_ts) THEN excluded.close_usd ELSE close_usd END,
+    cmc_rank = CASE WHEN excluded.close_ts >= COALESCE(close_ts, bucket_ts) THEN excluded.cmc_rank ELSE cmc_rank END,
+    market_cap_usd = CASE WHEN excluded.close_ts >= COALESCE(close_ts, bucket_ts) THEN excluded.market_cap_usd ELSE market_cap_usd END,
+    change_24h = CASE WHEN excluded.close_ts >= COALESCE(close_ts, bucket_ts) THEN excluded.change_24h ELSE change_24h END,
+    close_ts = MAX(COALESCE(close_ts, bucket_ts), excluded.close_ts),
+    samples = samples + excluded.samples
+"#;
+
+impl Database {
+    /// Apply a retention policy: roll expired raw snapshots into hourly
+    /// buckets, expired hourly buckets into daily ones, then delete the
+    /// rolled-up rows. Runs in a single transaction; with `dry_run` the
+    /// transaction is rolled back and only the counts are reported.
+    pub async fn compact(&self, policy: &RetentionPolicy, now: DateTime<Utc>, dry_run: bool) -> Result<CompactReport> {
+        policy.validate()?;
+
+        // Cutoffs are aligned to bucket boundaries so a bucket is never split
+        // between raw and rolled-up storage.
+        let raw_cutoff = (now - Duration::days(policy.raw_days.into()))
+            .duration_trunc(Duration::hours(1))?;
+        let hourly_cutoff = (now - Duration::days(policy.hourly_days.into()))
+            .duration_trunc(Duration::days(1))?;
+
+        let mut report = CompactReport::default();
+        let mut tx = self.pool.begin().await?;
+
+        report.hourly_buckets_written = sqlx::query(ROLLUP_HOURLY_SQL)
+            .bind(EpochMillis(raw_cutoff))
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        report.raw_rows_rolled_up = sqlx::query("DELETE FROM snapshots WHERE ts_utc < ?")
+            .bind(EpochMillis(raw_cutoff))
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+        report.daily_buckets_written = sqlx::query(ROLLUP_DAILY_SQL)
+            .bind(EpochMillis(hourly_cutoff))
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        report.hourly_rows_rolled_up = sqlx::query("DELETE FROM snapshots_hourly WHERE bucket_ts < ?")
+            .bind(EpochMillis(hourly_cutoff))
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+        if let Some(daily_days) = policy.daily_days {
+            let daily_cutoff = (now - Duration::days(daily_days.into()))
+                .duration_trunc(Duration::days(1))?;
+            report.daily_rows_deleted = sqlx::query("DELETE FROM snapshots_daily WHERE bucket_ts < ?")
+                .bind(EpochMillis(daily_cutoff))
+                .execute(&mut *tx)
+                .await?
+                .rows_affected();
+        }
+
+        if dry_run {
+            tx.rollback().await?;
+        } else {
+            tx.commit().await?;
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::testing::{coin, temp_db, ts};
+
+    type Bucket = (i64, Option<f64>, Option<f64>, Option<f64>, Option<f64>, i64);
+
+    async fn buckets(db: &Database, table: &str) -> Result<Vec<Bucket>> {
+        Ok(sqlx::query_as(&format!(
+            "SELECT bucket_ts, open_usd, high_usd, low_usd, close_usd, samples FROM {} ORDER BY bucket_ts", table
+        ))
+        .fetch_all(&db.pool)
+        .await?)
+    }
+
+    fn ms(s: &str) -> i64 {
+        ts(s).timestamp_millis()
+    }
+
+    /// Raw cutoff 2026-01-09 00:00, hourly cutoff 2026-01-08 00:00.
+    fn policy() -> RetentionPolicy {
+        RetentionPolicy { raw_days: 1, hourly_days: 2, daily_days: None }
+    }
+
+    async fn seed(db: &Database) -> Result<()> {
+        // Saved out of order so open and close must come from ts_utc
+        for (price, at) in [
+            (3.0, "2026-01-07T05:30:00Z"),
+            (1.0, "2026-01-07T01:00:00Z"),
+            (20.0, "2026-01-08T10:55:00Z"),
+            (10.0, "2026-01-08T10:05:00Z"),
+            (5.0, "2026-01-08T10:40:00Z"),
+            (30.0, "2026-01-08T10:20:00Z"),
+            (40.0, "2026-01-08T23:59:59.999Z"),
+            (50.0, "2026-01-09T00:00:00Z"),
+        ] {
+            db.save_coins(&[coin(1, "BTC", 1, price, at)]).await?;
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn compact_rolls_up_ohlc_at_bucket_boundaries() -> Result<()> {
+        let (_dir, db) = temp_db().await?;
+        seed(&db).await?;
+
+        let report = db.compact(&policy(), ts("2026-01-10T00:00:00Z"), false).await?;
+        assert_eq!(report.raw_rows_rolled_up, 7);
+        assert_eq!(report.hourly_buckets_written, 4);
+        assert_eq!(report.daily_buckets_written, 1);
+        assert_eq!(report.hourly_rows_rolled_up, 2);
+
+        assert_eq!(buckets(&db, "snapshots_hourly").await?, vec![
+            (ms("2026-01-08T10:00:00Z"), Some(10.0), Some(30.0), Some(5.0), Some(20.0), 4),
+            (ms("2026-01-08T23:00:00Z"), Some(40.0), Some(40.0), Some(40.0), Some(40.0), 1),
+        ]);
+        assert_eq!(buckets(&db, "snapshots_daily").await?, vec![
+            (ms("2026-01-07T00:00:00Z"), Some(1.0), Some(3.0), Some(1.0), Some(3.0), 2),
+        ]);
+        // Only the snapshot at the raw cutoff is left raw
+        let raw: Vec<(i64,)> = sqlx::query_as("SELECT ts_utc FROM snapshots").fetch_all(&db.pool).await?;
+        assert_eq!(raw, vec![(ms("2026-01-09T00:00:00Z"),)]);
+
+        let history: Vec<_> = db.get_coin_history("BTC", None, None, None).await?
+            .into_iter()
+            .map(|point| (point.timestamp, point.price_usd))
+            .collect();
+        assert_eq!(history, vec![
+            (ts("2026-01-07T00:00:00Z"), Some(3.0)),
+            (ts("2026-01-08T10:00:00Z"), Some(20.0)),
+            (ts("2026-01-08T23:00:00Z"), Some(40.0)),
+            (ts("2026-01-09T00:00:00Z"), Some(50.0)),
+        ]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn compact_is_idempotent() -> Result<()> {
+        let (_dir, db) = temp_db().await?;
+        seed(&db).await?;
+        let now = ts("2026-01-10T00:00:00Z");
+        db.compact(&policy(), now, false).await?;
+        let hourly = buckets(&db, "snapshots_hourly").await?;
+        let daily = buckets(&db, "snapshots_daily").await?;
+
+        let report = db.compact(&policy(), now, false).await?;
+        assert_eq!(
+            (report.raw_rows_rolled_up, report.hourly_buckets_written, report.daily_buckets_written, report.hourly_rows_rolled_up),
+            (0, 0, 0, 0),
+        );
+        assert_eq!(buckets(&db, "snapshots_hourly").await?, hourly);
+        assert_eq!(buckets(&db, "snapshots_daily").await?, daily);
+        assert_eq!(db.get_snapshot_count().await?, 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn dry_run_changes_nothing() -> Result<()> {
+        let (_dir, db) = temp_db().await?;
+        seed(&db).await?;
+
+        let report = db.compact(&policy(), ts("2026-01-10T00:00:00Z"), true).await?;
+        assert_eq!(report.raw_rows_rolled_up, 7);
+        assert_eq!(db.get_snapshot_count().await?, 8);
+        assert!(buckets(&db, "snapshots_hourly").await?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn late_rows_only_move_open_and_close_outwards() -> Result<()> {
+        let (_dir, db) = temp_db().await?;
+        let now = ts("2026-01-10T00:00:00Z");
+        for (price, at) in [(10.0, "2026-01-08T10:20:00Z"), (20.0, "2026-01-08T10:30:00Z")] {
+            db.save_coins(&[coin(1, "BTC", 1, price, at)]).await?;
+        }
+        db.compact(&policy(), now, false).await?;
+
+        // A late batch reaching both before and inside the compacted bucket,
+        // then one landing in the middle
+        for (price, at) in [(5.0, "2026-01-08T10:05:00Z"), (15.0, "2026-01-08T10:25:00Z")] {
+            db.save_coins(&[coin(1, "BTC", 1, price, at)]).await?;
+        }
+        db.compact(&policy(), now, false).await?;
+        db.save_coins(&[coin(1, "BTC", 2, 40.0, "2026-01-08T10:50:00Z")]).await?;
+        db.compact(&policy(), now, false).await?;
+        db.save_coins(&[coin(1, "BTC", 3, 25.0, "2026-01-08T10:45:00Z")]).await?;
+        db.compact(&policy(), now, false).await?;
+
+        assert_eq!(buckets(&db, "snapshots_hourly").await?, vec![
+            (ms("2026-01-08T10:00:00Z"), Some(5.0), Some(40.0), Some(5.0), Some(40.0), 6),
+        ]);
+        let (rank, open_ts, close_ts): (i64, i64, i64) = sqlx::query_as("SELECT cmc_rank, open_ts, close_ts FROM snapshots_hourly")
+            .fetch_one(&db.pool)
+            .await?;
+        assert_eq!(rank, 2);
+        assert_eq!((open_ts, close_ts), (ms("2026-01-08T10:05:00Z"), ms("2026-01-08T10:50:00Z")));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn late_hourly_buckets_keep_the_daily_open_and_close() -> Result<()> {
+        let (_dir, db) = temp_db().await?;
+        let now = ts("2026-01-10T00:00:00Z");
+        let policy = RetentionPolicy { raw_days: 1, hourly_days: 1, daily_days: None };
+        for (price, at) in [(10.0, "2026-01-07T10:00:00Z"), (20.0, "2026-01-07T12:00:00Z")] {
+            db.save_coins(&[coin(1, "BTC", 1, price, at)]).await?;
+        }
+        db.compact(&policy, now, false).await?;
+        for (price, at) in [(5.0, "2026-01-07T08:00:00Z"), (15.0, "2026-01-07T11:00:00Z")] {
+            db.save_coins(&[coin(1, "BTC", 1, price, at)]).await?;
+        }
+        db.compact(&policy, now, false).await?;
+
+        assert_eq!(buckets(&db, "snapshots_daily").await?, vec![
+            (ms("2026-01-07T00:00:00Z"), Some(5.0), Some(20.0), Some(5.0), Some(20.0), 4),
+        ]);
+        Ok(())
+    }
+}
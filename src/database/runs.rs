@@ -0,0 +1,137 @@
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::Row;
+use std::fmt;
+use std::str::FromStr;
+
+use super::{Database, EpochMillis};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RunStatus {
+    Running,
+    /// Every page was scraped and saved
+    Ok,
+    /// Some pages failed but the rest were saved
+    Partial,
+    /// Nothing was saved
+    Failed,
+}
+
+impl RunStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Running => "running",
+            Self::Ok => "ok",
+            Self::Partial => "partial",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+impl FromStr for RunStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "running" => Ok(Self::Running),
+            "ok" => Ok(Self::Ok),
+            "partial" => Ok(Self::Partial),
+            "failed" => Ok(Self::Failed),
+            other => bail!("unknown run status '{}'", other),
+        }
+    }
+}
+
+impl fmt::Display for RunStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A row of `scrape_runs`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ScrapeRun {
+    #[serde(rename = "run_id")]
+    pub id: i64,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    /// `scraped_at` of the snapshots this run saved
+    pub ts_utc: Option<DateTime<Utc>>,
+    pub status: RunStatus,
+    pub pages_requested: u32,
+    pub pages_failed: u32,
+    pub coins_saved: u64,
+    pub error: Option<String>,
+}
+
+impl Database {
+    /// Record the start of a run and return its id.
+    pub async fn begin_run(&self, started_at: DateTime<Utc>, pages_requested: u32) -> Result<i64> {
+        let result = sqlx::query(
+            "INSERT INTO scrape_runs (started_at, status, pages_requested) VALUES (?, ?, ?)"
+        )
+        .bind(EpochMillis(started_at))
+        .bind(RunStatus::Running.as_str())
+        .bind(pages_requested)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.last_insert_rowid())
+    }
+
+    /// Store the final state of a run started with `begin_run`.
+    pub async fn finish_run(&self, run: &ScrapeRun) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE scrape_runs
+            SET finished_at = ?, ts_utc = ?, status = ?, pages_failed = ?, coins_saved = ?, error = ?
+            WHERE id = ?
+            "#
+        )
+        .bind(run.finished_at.map(EpochMillis))
+        .bind(run.ts_utc.map(EpochMillis))
+        .bind(run.status.as_str())
+        .bind(run.pages_failed)
+        .bind(run.coins_saved as i64)
+        .bind(&run.error)
+        .bind(run.id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Most recent runs first.
+    pub async fn recent_runs(&self, limit: i64) -> Result<Vec<ScrapeRun>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, started_at, finished_at, ts_utc, status, pages_requested, pages_failed, coins_saved, error
+            FROM scrape_runs
+            ORDER BY started_at DESC, id DESC
+            LIMIT ?
+            "#
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut runs = Vec::new();
+        for row in rows {
+            runs.push(ScrapeRun {
+                id: row.get("id"),
+                started_at: row.try_get::<EpochMillis, _>("started_at")?.into(),
+                finished_at: row.try_get::<Option<EpochMillis>, _>("finished_at")?.map(Into::into),
+                ts_utc: row.try_get::<Option<EpochMillis>, _>("ts_utc")?.map(Into::into),
+                status: row.get::<String, _>("status").parse()?,
+                pages_requested: row.get("pages_requested"),
+                pages_failed: row.get("pages_failed"),
+                coins_saved: row.get::<i64, _>("coins_saved") as u64,
+                error: row.get("error"),
+            });
+        }
+
+        Ok(runs)
+    }
+}
@@ -0,0 +1,49 @@
+use chrono::{DateTime, Utc};
+use sqlx::encode::IsNull;
+use sqlx::error::BoxDynError;
+use sqlx::sqlite::{Sqlite, SqliteArgumentValue, SqliteTypeInfo, SqliteValueRef};
+use sqlx::{Decode, Encode, Type};
+
+/// A UTC timestamp stored as an integer number of Unix epoch milliseconds.
+///
+/// sqlx's own `DateTime<Utc>` mapping treats integers as whole seconds, so
+/// columns holding milliseconds are bound and decoded through this type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EpochMillis(pub DateTime<Utc>);
+
+impl From<DateTime<Utc>> for EpochMillis {
+    fn from(ts: DateTime<Utc>) -> Self {
+        Self(ts)
+    }
+}
+
+impl From<EpochMillis> for DateTime<Utc> {
+    fn from(ts: EpochMillis) -> Self {
+        ts.0
+    }
+}
+
+impl Type<Sqlite> for EpochMillis {
+    fn type_info() -> SqliteTypeInfo {
+        <i64 as Type<Sqlite>>::type_info()
+    }
+
+    fn compatible(ty: &SqliteTypeInfo) -> bool {
+        <i64 as Type<Sqlite>>::compatible(ty)
+    }
+}
+
+impl<'q> Encode<'q, Sqlite> for EpochMillis {
+    fn encode_by_ref(&self, args: &mut Vec<SqliteArgumentValue<'q>>) -> Result<IsNull, BoxDynError> {
+        <i64 as Encode<'q, Sqlite>>::encode_by_ref(&self.0.timestamp_millis(), args)
+    }
+}
+
+impl<'r> Decode<'r, Sqlite> for EpochMillis {
+    fn decode(value: SqliteValueRef<'r>) -> Result<Self, BoxDynError> {
+        let millis = <i64 as Decode<'r, Sqlite>>::decode(value)?;
+        DateTime::from_timestamp_millis(millis)
+            .map(Self)
+            .ok_or_else(|| format!("epoch milliseconds out of range: {}", millis).into())
+    }
+}
@@ -0,0 +1,75 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+use super::Database;
+use crate::model::ScrapeUpdate;
+
+/// Updates buffered per subscriber before a slow one starts missing them.
+pub const UPDATE_BUFFER: usize = 16;
+
+/// In-process fan-out of newly saved scrape runs to stream subscribers.
+pub(super) struct Updates {
+    sender: broadcast::Sender<Arc<ScrapeUpdate>>,
+    /// Newest run published so far, so a run is never announced twice
+    latest: std::sync::Mutex<Option<DateTime<Utc>>>,
+}
+
+impl Updates {
+    pub(super) fn new() -> Self {
+        Self {
+            sender: broadcast::channel(UPDATE_BUFFER).0,
+            latest: std::sync::Mutex::new(None),
+        }
+    }
+}
+
+impl Database {
+    /// Receive every scrape run published from now on. A receiver that falls
+    /// more than `UPDATE_BUFFER` runs behind gets `RecvError::Lagged`.
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<ScrapeUpdate>> {
+        self.updates.sender.subscribe()
+    }
+
+    /// Announce a saved run to subscribers. Runs no newer than the last one
+    /// published are dropped, so imports of old data and a run seen both
+    /// in-process and by `publish_latest` don't reach clients.
+    pub fn publish(&self, update: ScrapeUpdate) -> bool {
+        let mut latest = self.updates.latest.lock().unwrap_or_else(|e| e.into_inner());
+        if latest.is_some_and(|latest| update.ts_utc <= latest) {
+            return false;
+        }
+        *latest = Some(update.ts_utc);
+        // No subscribers is not an error
+        let _ = self.updates.sender.send(Arc::new(update));
+        true
+    }
+
+    /// Publish the latest run in the database if it is newer than the last
+    /// one published, for runs saved by another process. The first call only
+    /// records where the database stands.
+    pub async fn publish_latest(&self) -> Result<bool> {
+        let Some(ts_utc) = self.latest_snapshot_at().await? else {
+            return Ok(false);
+        };
+        {
+            let mut latest = self.updates.latest.lock().unwrap_or_else(|e| e.into_inner());
+            match *latest {
+                None => {
+                    *latest = Some(ts_utc);
+                    return Ok(false);
+                }
+                Some(latest) if ts_utc <= latest => return Ok(false),
+                _ => {}
+            }
+        }
+
+        let coins = self.get_latest_coins_api(-1).await?;
+        let run_id = self.recent_runs(5).await?
+            .into_iter()
+            .find(|run| run.ts_utc == Some(ts_utc))
+            .map(|run| run.id);
+        Ok(self.publish(ScrapeUpdate { run_id, ts_utc, coins }))
+    }
+}
@@ -0,0 +1,226 @@
+use anyhow::{bail, Result};
+use chrono::SecondsFormat;
+use csv::{ReaderBuilder, WriterBuilder};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+use super::sink::{Compression, Rotation, RotatingSink};
+use crate::model::CoinRow;
+
+#[derive(Debug, Default, Serialize)]
+pub struct AppendOutcome {
+    pub rows_written: usize,
+    /// Where the previous file was moved if this append rotated it
+    pub rotated_to: Option<PathBuf>,
+    /// Old generations deleted to stay within the `keep` limit
+    pub removed: Vec<PathBuf>,
+}
+
+/// Appends `CoinRow`s to a CSV file that may be shared with other writers.
+///
+/// The header is written only when the file is empty. An existing header must
+/// name exactly the `CoinRow` columns, in any order; rows are written in the
+/// file's order. Writes are locked, undone if they fail, and optionally
+/// compressed and rotated via [`RotatingSink`].
+#[derive(Debug, Clone)]
+pub struct CsvAppender {
+    sink: RotatingSink,
+}
+
+impl CsvAppender {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            sink: RotatingSink::new(path),
+        }
+    }
+
+    pub fn with_rotation(mut self, rotation: Rotation) -> Self {
+        self.sink = self.sink.with_rotation(rotation);
+        self
+    }
+
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.sink = self.sink.with_compression(compression);
+        self
+    }
+
+    pub fn with_keep(mut self, keep: Option<usize>) -> Self {
+        self.sink = self.sink.with_keep(keep);
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        self.sink.path()
+    }
+
+    pub fn append(&self, rows: &[CoinRow]) -> Result<AppendOutcome> {
+        let compression = self.sink.compression();
+        let outcome = self.sink.write(|out, existing| {
+            let order = match existing {
+                Some(path) => column_order(path, compression)?,
+                None => identity_order(),
+            };
+            let mut wtr = WriterBuilder::new().has_headers(false).from_writer(out);
+            if existing.is_none() {
+                wtr.write_record(CoinRow::FIELDS)?;
+            }
+            for row in rows {
+                let values = row_values(row);
+                wtr.write_record(order.iter().map(|&i| &values[i]))?;
+            }
+            wtr.flush()?;
+            Ok(())
+        })?;
+
+        Ok(AppendOutcome {
+            rows_written: rows.len(),
+            rotated_to: outcome.rotated_to,
+            removed: outcome.removed,
+        })
+    }
+}
+
+fn identity_order() -> [usize; 8] {
+    std::array::from_fn(|i| i)
+}
+
+/// Map the file's header onto `CoinRow::FIELDS`: entry `i` is the field
+/// written in column `i`.
+fn column_order(path: &Path, compression: Compression) -> Result<[usize; 8]> {
+    let mut rdr = ReaderBuilder::new().has_headers(false).from_reader(compression.reader(path)?);
+    let header = rdr.records().next().transpose()?.unwrap_or_default();
+    let columns: Vec<&str> = header.iter().map(str::trim).collect();
+
+    let missing: Vec<&str> = CoinRow::FIELDS.iter().copied().filter(|f| !columns.contains(f)).collect();
+    let unexpected: Vec<&str> = columns.iter().copied().filter(|c| !CoinRow::FIELDS.contains(c)).collect();
+    if !missing.is_empty() || !unexpected.is_empty() || columns.len() != CoinRow::FIELDS.len() {
+        bail!("{} has an incompatible header (missing: [{}], unexpected: [{}]); refusing to append",
+              path.display(), missing.join(", "), unexpected.join(", "));
+    }
+
+    let mut order = identity_order();
+    for (col, name) in columns.iter().enumerate() {
+        order[col] = CoinRow::FIELDS.iter().position(|f| f == name).unwrap();
+    }
+    Ok(order)
+}
+
+/// `CoinRow` values in `CoinRow::FIELDS` order, formatted like `serialize`.
+fn row_values(row: &CoinRow) -> [String; 8] {
+    let opt = |v: Option<f64>| v.map(|v| format!("{:?}", v)).unwrap_or_default();
+    [
+        row.id.to_string(),
+        row.rank.map(|r| r.to_string()).unwrap_or_default(),
+        row.name.clone(),
+        row.symbol.clone(),
+        opt(row.price_usd),
+        opt(row.market_cap_usd),
+        opt(row.chg24h_pct),
+        row.scraped_at.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::testing::coin;
+    use std::fs;
+
+    const HEADER: &str = "id,rank,name,symbol,price_usd,market_cap_usd,chg24h_pct,scraped_at";
+
+    fn btc() -> CoinRow {
+        coin(1, "BTC", 1, 100.0, "2026-01-01T00:00:00Z")
+    }
+
+    #[test]
+    fn writes_the_header_once() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let appender = CsvAppender::new(dir.path().join("coins.csv"));
+        appender.append(&[btc()])?;
+        appender.append(&[coin(1027, "ETH", 2, 10.0, "2026-01-01T00:00:00Z")])?;
+
+        assert_eq!(fs::read_to_string(appender.path())?, format!(
+            "{}\n1,1,btc,BTC,100.0,100000.0,0.0,2026-01-01T00:00:00Z\n1027,2,eth,ETH,10.0,10000.0,0.0,2026-01-01T00:00:00Z\n",
+            HEADER
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn appends_in_the_existing_column_order() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("coins.csv");
+        fs::write(&path, "symbol, id ,rank,name,price_usd,market_cap_usd,chg24h_pct,scraped_at\nETH,1027,2,eth,10.0,,,2026-01-01T00:00:00Z\n")?;
+
+        let outcome = CsvAppender::new(&path).append(&[btc()])?;
+        assert_eq!(outcome.rows_written, 1);
+        let text = fs::read_to_string(&path)?;
+        assert_eq!(text.lines().last(), Some("BTC,1,1,btc,100.0,100000.0,0.0,2026-01-01T00:00:00Z"));
+        assert_eq!(text.lines().count(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn refuses_an_incompatible_header() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("coins.csv");
+        for header in [
+            "id,rank,name,symbol,price_usd,market_cap_usd,chg24h_pct",
+            "id,rank,name,symbol,price_usd,market_cap_usd,chg24h_pct,scraped_at,volume",
+            "id,rank,name,symbol,price,market_cap_usd,chg24h_pct,scraped_at",
+            "id,id,name,symbol,price_usd,market_cap_usd,chg24h_pct,scraped_at",
+        ] {
+            let original = format!("{}\n1,1,Bitcoin,BTC,1,1,1,2026-01-01T00:00:00Z\n", header);
+            fs::write(&path, &original)?;
+            let err = CsvAppender::new(&path).append(&[btc()]).expect_err(header);
+            assert!(err.to_string().contains("incompatible header"), "{}: {}", header, err);
+            assert_eq!(fs::read_to_string(&path)?, original);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn ends_an_unterminated_last_line_first() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("coins.csv");
+        fs::write(&path, format!("{}\n1027,2,eth,ETH,10.0,,,2026-01-01T00:00:00Z", HEADER))?;
+
+        CsvAppender::new(&path).append(&[btc()])?;
+        let text = fs::read_to_string(&path)?;
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[1], "1027,2,eth,ETH,10.0,,,2026-01-01T00:00:00Z");
+        assert!(lines[2].starts_with("1,1,btc,BTC,"));
+        assert!(text.ends_with('\n'));
+        Ok(())
+    }
+
+    #[test]
+    fn rotation_keeps_n_generations_each_with_a_header() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let appender = CsvAppender::new(dir.path().join("coins.csv"))
+            .with_rotation(Rotation::MaxWrites(1))
+            .with_keep(Some(2));
+
+        let mut removed = Vec::new();
+        for _ in 0..5 {
+            removed.extend(appender.append(&[btc()])?.removed);
+        }
+        assert_eq!(removed.len(), 2);
+
+        let mut names: Vec<String> = fs::read_dir(dir.path())?
+            .map(|entry| Ok(entry?.file_name().to_string_lossy().into_owned()))
+            .filter(|name| !matches!(name, Ok(name) if name == "coins.csv.lock"))
+            .collect::<Result<_>>()?;
+        names.sort();
+        assert_eq!(names.len(), 3);
+        assert!(names.contains(&"coins.csv".to_string()));
+        for name in &names {
+            assert!(name == "coins.csv" || (name.starts_with("coins.2") && name.ends_with(".csv")), "{}", name);
+            let text = fs::read_to_string(dir.path().join(name))?;
+            assert_eq!(text.lines().next(), Some(HEADER));
+            assert_eq!(text.lines().count(), 2);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,167 @@
+pub mod append;
+pub mod parquet;
+pub mod sink;
+pub mod xlsx;
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use csv::{Writer, WriterBuilder};
+use futures::TryStreamExt;
+use std::fmt;
+use std::io::Write;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::database::{Database, SnapshotFilter};
+use crate::model::CoinRow;
+use sink::{AtomicFile, Compression};
+
+/// Write rows to a new CSV file, replacing it atomically. A `.gz` or `.zst`
+/// extension compresses the output.
+pub fn save_to_csv<P: AsRef<Path>>(data: &[CoinRow], file_path: P) -> Result<()> {
+    let path = file_path.as_ref();
+    let mut file = AtomicFile::create(path, Compression::from_path(path))?;
+    
+    let mut wtr = Writer::from_writer(&mut file);
+    
+    for row in data {
+        wtr.serialize(row)?;
+    }
+    
+    wtr.flush()?;
+    drop(wtr);
+    file.commit()
+}
+
+/// Append rows to a CSV file, writing the header only if the file is new or
+/// empty. See [`append::CsvAppender`] for header checks, locking and rotation.
+pub fn append_to_csv<P: AsRef<Path>>(data: &[CoinRow], file_path: P) -> Result<()> {
+    append::CsvAppender::new(file_path).append(data)?;
+    Ok(())
+}
+
+pub fn generate_filename() -> String {
+    generate_filename_at(Utc::now())
+}
+
+pub fn generate_filename_at(ts: DateTime<Utc>) -> String {
+    generate_filename_with("coinbase_data_", ts, ".csv")
+}
+
+/// A timestamped file name like `generate_filename_at`'s, around another
+/// prefix and suffix.
+pub fn generate_filename_with(prefix: &str, ts: DateTime<Utc>, suffix: &str) -> String {
+    format!("{}{}{}", prefix, ts.format("%Y%m%d_%H%M%S"), suffix)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    JsonLines,
+    Json,
+    Parquet,
+    Xlsx,
+}
+
+impl FromStr for ExportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "csv" => Ok(Self::Csv),
+            "jsonl" | "ndjson" => Ok(Self::JsonLines),
+            "json" => Ok(Self::Json),
+            "parquet" => Ok(Self::Parquet),
+            "xlsx" => Ok(Self::Xlsx),
+            other => bail!("unknown export format '{}', expected csv, jsonl, json, parquet or xlsx", other),
+        }
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Csv => "csv",
+            Self::JsonLines => "jsonl",
+            Self::Json => "json",
+            Self::Parquet => "parquet",
+            Self::Xlsx => "xlsx",
+        })
+    }
+}
+
+/// Writes `CoinRow`s one at a time in any of the row-oriented export formats.
+/// JSON output is a single array, written incrementally. Parquet and XLSX have
+/// their own writers in [`parquet`] and [`xlsx`].
+pub enum StreamWriter<W: Write> {
+    Csv(Box<Writer<W>>),
+    JsonLines(W),
+    Json { out: W, empty: bool },
+}
+
+impl<W: Write> StreamWriter<W> {
+    pub fn new(format: ExportFormat, mut out: W) -> Result<Self> {
+        Ok(match format {
+            ExportFormat::Csv => {
+                // Header is written up front so an empty export is still a valid CSV
+                let mut wtr = WriterBuilder::new().has_headers(false).from_writer(out);
+                wtr.write_record(CoinRow::FIELDS)?;
+                Self::Csv(Box::new(wtr))
+            }
+            ExportFormat::JsonLines => Self::JsonLines(out),
+            ExportFormat::Json => {
+                out.write_all(b"[")?;
+                Self::Json { out, empty: true }
+            }
+            ExportFormat::Parquet => bail!("parquet is not a row-oriented format, use export_parquet"),
+            ExportFormat::Xlsx => bail!("xlsx is not a row-oriented format, use export_xlsx"),
+        })
+    }
+
+    pub fn write(&mut self, row: &CoinRow) -> Result<()> {
+        match self {
+            Self::Csv(wtr) => wtr.serialize(row)?,
+            Self::JsonLines(out) => {
+                serde_json::to_writer(&mut *out, row)?;
+                out.write_all(b"\n")?;
+            }
+            Self::Json { out, empty } => {
+                if !*empty {
+                    out.write_all(b",")?;
+                }
+                out.write_all(b"\n  ")?;
+                serde_json::to_writer(&mut *out, row)?;
+                *empty = false;
+            }
+        }
+        Ok(())
+    }
+
+    /// Close the document and flush, returning the underlying writer.
+    pub fn finish(self) -> Result<W> {
+        let mut out = match self {
+            Self::Csv(wtr) => wtr.into_inner().map_err(|e| e.into_error())?,
+            Self::JsonLines(out) => out,
+            Self::Json { mut out, empty } => {
+                out.write_all(if empty { b"]\n" as &[u8] } else { b"\n]\n" })?;
+                out
+            }
+        };
+        out.flush()?;
+        Ok(out)
+    }
+}
+
+/// Stream snapshots matching `filter` from the database into `out`, returning
+/// the number of rows written.
+pub async fn export_snapshots<W: Write>(db: &Database, filter: &SnapshotFilter, format: ExportFormat, out: W) -> Result<u64> {
+    let mut writer = StreamWriter::new(format, out)?;
+    let mut rows = std::pin::pin!(db.stream_snapshots(filter));
+    let mut count = 0;
+    while let Some(row) = rows.try_next().await? {
+        writer.write(&row)?;
+        count += 1;
+    }
+    writer.finish()?;
+    Ok(count)
+}
@@ -0,0 +1,320 @@
+use anyhow::{bail, Context, Result};
+use arrow_array::builder::{Float64Builder, Int32Builder, Int64Builder, StringBuilder, TimestampMillisecondBuilder};
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use chrono::NaiveDate;
+use futures::TryStreamExt;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::{Compression, GzipLevel, ZstdLevel};
+use parquet::file::properties::WriterProperties;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use super::sink::{self, AtomicFile};
+use crate::database::{Database, SnapshotFilter};
+use crate::model::CoinRow;
+
+/// Rows buffered in memory before they are handed to the Parquet writer.
+const BATCH_SIZE: usize = 8192;
+
+/// Arrow schema for exported snapshots. Column names match `CoinRow::FIELDS`.
+pub fn coin_row_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int64, false),
+        Field::new("rank", DataType::Int32, true),
+        Field::new("name", DataType::Utf8, false),
+        Field::new("symbol", DataType::Utf8, false),
+        Field::new("price_usd", DataType::Float64, true),
+        Field::new("market_cap_usd", DataType::Float64, true),
+        Field::new("chg24h_pct", DataType::Float64, true),
+        Field::new("scraped_at", DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into())), false),
+    ]))
+}
+
+/// Parquet compression codec, parsed from `none`, `snappy`, `gzip[:level]`
+/// or `zstd[:level]`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParquetCompression(pub Compression);
+
+impl Default for ParquetCompression {
+    fn default() -> Self {
+        Self(Compression::SNAPPY)
+    }
+}
+
+impl FromStr for ParquetCompression {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (codec, level) = match s.split_once(':') {
+            Some((codec, level)) => {
+                let level: u32 = level.parse().with_context(|| format!("invalid compression level '{}'", level))?;
+                (codec, Some(level))
+            }
+            None => (s, None),
+        };
+        let compression = match (codec.to_ascii_lowercase().as_str(), level) {
+            ("none" | "uncompressed", None) => Compression::UNCOMPRESSED,
+            ("snappy", None) => Compression::SNAPPY,
+            ("gzip", level) => Compression::GZIP(GzipLevel::try_new(level.unwrap_or(6))?),
+            ("zstd", level) => Compression::ZSTD(ZstdLevel::try_new(level.unwrap_or(3) as i32)?),
+            (codec @ ("none" | "uncompressed" | "snappy"), Some(_)) => bail!("{} does not take a level", codec),
+            (other, _) => bail!("unknown compression '{}', expected none, snappy, gzip[:level] or zstd[:level]", other),
+        };
+        Ok(Self(compression))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ParquetOptions {
+    pub compression: ParquetCompression,
+    /// Maximum rows per row group
+    pub row_group_size: usize,
+    /// Write a `date=YYYY-MM-DD/part-0.parquet` tree instead of one file
+    pub partition_by_date: bool,
+}
+
+impl Default for ParquetOptions {
+    fn default() -> Self {
+        Self {
+            compression: ParquetCompression::default(),
+            row_group_size: 1024 * 1024,
+            partition_by_date: false,
+        }
+    }
+}
+
+impl ParquetOptions {
+    fn writer_properties(&self) -> Result<WriterProperties> {
+        if self.row_group_size == 0 {
+            bail!("row group size must be at least 1");
+        }
+        Ok(WriterProperties::builder()
+            .set_compression(self.compression.0)
+            .set_max_row_group_size(self.row_group_size)
+            .build())
+    }
+}
+
+/// Buffers `CoinRow`s into Arrow column builders and writes them to a
+/// Parquet file in batches.
+pub struct ParquetWriter<W: Write + Send> {
+    writer: ArrowWriter<W>,
+    schema: SchemaRef,
+    id: Int64Builder,
+    rank: Int32Builder,
+    name: StringBuilder,
+    symbol: StringBuilder,
+    price_usd: Float64Builder,
+    market_cap_usd: Float64Builder,
+    chg24h_pct: Float64Builder,
+    scraped_at: TimestampMillisecondBuilder,
+    buffered: usize,
+}
+
+impl<W: Write + Send> ParquetWriter<W> {
+    pub fn new(out: W, options: &ParquetOptions) -> Result<Self> {
+        let schema = coin_row_schema();
+        let writer = ArrowWriter::try_new(out, schema.clone(), Some(options.writer_properties()?))?;
+        Ok(Self {
+            writer,
+            schema,
+            id: Int64Builder::with_capacity(BATCH_SIZE),
+            rank: Int32Builder::with_capacity(BATCH_SIZE),
+            name: StringBuilder::new(),
+            symbol: StringBuilder::new(),
+            price_usd: Float64Builder::with_capacity(BATCH_SIZE),
+            market_cap_usd: Float64Builder::with_capacity(BATCH_SIZE),
+            chg24h_pct: Float64Builder::with_capacity(BATCH_SIZE),
+            scraped_at: TimestampMillisecondBuilder::with_capacity(BATCH_SIZE).with_timezone("UTC"),
+            buffered: 0,
+        })
+    }
+
+    pub fn write(&mut self, row: &CoinRow) -> Result<()> {
+        self.id.append_value(row.id as i64);
+        self.rank.append_option(row.rank.map(|r| r as i32));
+        self.name.append_value(&row.name);
+        self.symbol.append_value(&row.symbol);
+        self.price_usd.append_option(row.price_usd);
+        self.market_cap_usd.append_option(row.market_cap_usd);
+        self.chg24h_pct.append_option(row.chg24h_pct);
+        self.scraped_at.append_value(row.scraped_at.timestamp_millis());
+        self.buffered += 1;
+
+        if self.buffered >= BATCH_SIZE {
+            self.flush_batch()?;
+        }
+        Ok(())
+    }
+
+    fn flush_batch(&mut self) -> Result<()> {
+        if self.buffered == 0 {
+            return Ok(());
+        }
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(self.id.finish()),
+            Arc::new(self.rank.finish()),
+            Arc::new(self.name.finish()),
+            Arc::new(self.symbol.finish()),
+            Arc::new(self.price_usd.finish()),
+            Arc::new(self.market_cap_usd.finish()),
+            Arc::new(self.chg24h_pct.finish()),
+            Arc::new(self.scraped_at.finish()),
+        ];
+        let batch = RecordBatch::try_new(self.schema.clone(), columns)?;
+        self.writer.write(&batch)?;
+        self.buffered = 0;
+        Ok(())
+    }
+
+    /// Write remaining rows and the file footer, returning the underlying writer.
+    pub fn finish(mut self) -> Result<W> {
+        self.flush_batch()?;
+        let mut out = self.writer.into_inner()?;
+        out.flush()?;
+        Ok(out)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ParquetExport {
+    pub rows: u64,
+    pub files: Vec<PathBuf>,
+}
+
+/// Stream snapshots matching `filter` into Parquet at `path`. With
+/// `partition_by_date`, `path` is a directory receiving one Hive-style
+/// `date=YYYY-MM-DD` partition per UTC day. Each file is written atomically.
+pub async fn export_parquet(db: &Database, filter: &SnapshotFilter, path: &Path, options: &ParquetOptions) -> Result<ParquetExport> {
+    let mut export = ParquetExport::default();
+    let mut rows = std::pin::pin!(db.stream_snapshots(filter));
+
+    if !options.partition_by_date {
+        let mut writer = ParquetWriter::new(AtomicFile::create(path, sink::Compression::None)?, options)?;
+        while let Some(row) = rows.try_next().await? {
+            writer.write(&row)?;
+            export.rows += 1;
+        }
+        writer.finish()?.commit()?;
+        export.files.push(path.to_path_buf());
+        return Ok(export);
+    }
+
+    // Rows arrive ordered by timestamp, so each partition is written in one go
+    let mut current: Option<(NaiveDate, ParquetWriter<AtomicFile>)> = None;
+    while let Some(row) = rows.try_next().await? {
+        let date = row.scraped_at.date_naive();
+        if current.as_ref().is_none_or(|(d, _)| *d != date) {
+            if let Some((_, writer)) = current.take() {
+                writer.finish()?.commit()?;
+            }
+            let dir = path.join(format!("date={}", date.format("%Y-%m-%d")));
+            fs::create_dir_all(&dir)?;
+            let file = dir.join("part-0.parquet");
+            current = Some((date, ParquetWriter::new(AtomicFile::create(&file, sink::Compression::None)?, options)?));
+            export.files.push(file);
+        }
+        if let Some((_, writer)) = current.as_mut() {
+            writer.write(&row)?;
+        }
+        export.rows += 1;
+    }
+    if let Some((_, writer)) = current {
+        writer.finish()?.commit()?;
+    }
+    Ok(export)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::testing::{coin, temp_db, ts};
+    use arrow_array::cast::AsArray;
+    use arrow_array::Array;
+    use arrow_array::types::{Int32Type, Int64Type, TimestampMillisecondType};
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    fn read(path: &Path) -> Result<Vec<RecordBatch>> {
+        let reader = ParquetRecordBatchReaderBuilder::try_new(fs::File::open(path)?)?.build()?;
+        Ok(reader.collect::<Result<_, _>>()?)
+    }
+
+    #[tokio::test]
+    async fn round_trips_through_the_parquet_reader() -> Result<()> {
+        let (dir, db) = temp_db().await?;
+        let mut unranked = coin(1027, "ETH", 2, 10.0, "2026-01-01T00:00:00Z");
+        unranked.rank = None;
+        unranked.chg24h_pct = None;
+        db.save_coins(&[coin(1, "BTC", 1, 100.0, "2026-01-01T00:00:00Z"), unranked]).await?;
+
+        let path = dir.path().join("coins.parquet");
+        let export = export_parquet(&db, &SnapshotFilter::default(), &path, &ParquetOptions::default()).await?;
+        assert_eq!((export.rows, export.files), (2, vec![path.clone()]));
+
+        let batches = read(&path)?;
+        assert_eq!(batches.len(), 1);
+        let batch = &batches[0];
+        assert_eq!(batch.schema(), coin_row_schema());
+
+        let ids: Vec<i64> = batch.column(0).as_primitive::<Int64Type>().values().to_vec();
+        let eth = ids.iter().position(|id| *id == 1027).expect("ETH exported");
+        let ranks = batch.column(1).as_primitive::<Int32Type>();
+        assert!(ranks.is_null(eth));
+        assert_eq!(ranks.value(1 - eth), 1);
+        assert!(batch.column(6).is_null(eth));
+        assert_eq!(batch.column(3).as_string::<i32>().value(eth), "ETH");
+
+        let scraped_at = batch.column(7).as_primitive::<TimestampMillisecondType>();
+        assert_eq!(scraped_at.timezone(), Some("UTC"));
+        assert_eq!(scraped_at.value(eth), ts("2026-01-01T00:00:00Z").timestamp_millis());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn partitions_one_file_per_day() -> Result<()> {
+        let (dir, db) = temp_db().await?;
+        for at in ["2026-01-01T00:00:00Z", "2026-01-01T23:59:59Z", "2026-01-02T00:00:00Z", "2026-01-04T12:00:00Z"] {
+            db.save_coins(&[coin(1, "BTC", 1, 100.0, at)]).await?;
+        }
+
+        let out = dir.path().join("coins");
+        let options = ParquetOptions { partition_by_date: true, ..ParquetOptions::default() };
+        let export = export_parquet(&db, &SnapshotFilter::default(), &out, &options).await?;
+        assert_eq!(export.rows, 4);
+        let expected: Vec<PathBuf> = ["2026-01-01", "2026-01-02", "2026-01-04"]
+            .iter()
+            .map(|date| out.join(format!("date={}", date)).join("part-0.parquet"))
+            .collect();
+        assert_eq!(export.files, expected);
+
+        let rows: Vec<usize> = expected.iter()
+            .map(|file| Ok(read(file)?.iter().map(|b| b.num_rows()).sum()))
+            .collect::<Result<_>>()?;
+        assert_eq!(rows, vec![2, 1, 1]);
+        assert_eq!(fs::read_dir(&out)?.count(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn parses_compression_codecs() {
+        let parse = |s: &str| s.parse::<ParquetCompression>().map(|c| c.0);
+        assert_eq!(parse("none").unwrap(), Compression::UNCOMPRESSED);
+        assert_eq!(parse("SNAPPY").unwrap(), Compression::SNAPPY);
+        assert_eq!(parse("gzip").unwrap(), Compression::GZIP(GzipLevel::try_new(6).unwrap()));
+        assert_eq!(parse("zstd:9").unwrap(), Compression::ZSTD(ZstdLevel::try_new(9).unwrap()));
+
+        for (input, message) in [
+            ("snappy:3", "snappy does not take a level"),
+            ("brotli", "unknown compression 'brotli'"),
+            ("gzip:x", "invalid compression level 'x'"),
+        ] {
+            let err = parse(input).expect_err(input).to_string();
+            assert!(err.contains(message), "{}: {}", input, err);
+        }
+        assert!(parse("gzip:99").is_err());
+    }
+}
@@ -0,0 +1,610 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use super::generate_filename_with;
+
+/// Compression applied to a file sink.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip(u32),
+    Zstd(i32),
+}
+
+impl Compression {
+    /// Infer compression from a `.gz` or `.zst` extension.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("gz") => Self::Gzip(6),
+            Some("zst") => Self::Zstd(3),
+            _ => Self::None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::None => "",
+            Self::Gzip(_) => ".gz",
+            Self::Zstd(_) => ".zst",
+        }
+    }
+
+    /// Open `path` for reading, decompressing if needed.
+    pub fn reader(&self, path: &Path) -> Result<Box<dyn BufRead>> {
+        let file = File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+        Ok(match self {
+            Self::None => Box::new(BufReader::new(file)),
+            Self::Gzip(_) => Box::new(BufReader::new(MultiGzDecoder::new(file))),
+            Self::Zstd(_) => Box::new(BufReader::new(zstd::Decoder::new(file)?)),
+        })
+    }
+}
+
+impl FromStr for Compression {
+    type Err = anyhow::Error;
+
+    /// `none`, `gzip[:level]` or `zstd[:level]`.
+    fn from_str(s: &str) -> Result<Self> {
+        let (codec, level) = match s.split_once(':') {
+            Some((codec, level)) => (codec, Some(level.parse::<i32>().with_context(|| format!("invalid compression level '{}'", level))?)),
+            None => (s, None),
+        };
+        match (codec.to_ascii_lowercase().as_str(), level) {
+            ("none", None) => Ok(Self::None),
+            ("gzip" | "gz", level) => match level.unwrap_or(6) {
+                level @ 0..=9 => Ok(Self::Gzip(level as u32)),
+                level => bail!("gzip level must be 0-9, got {}", level),
+            },
+            ("zstd" | "zst", level) => match level.unwrap_or(3) {
+                level @ 1..=22 => Ok(Self::Zstd(level)),
+                level => bail!("zstd level must be 1-22, got {}", level),
+            },
+            ("none", Some(_)) => bail!("none does not take a level"),
+            (other, _) => bail!("unknown compression '{}', expected none, gzip[:level] or zstd[:level]", other),
+        }
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::None => f.write_str("none"),
+            Self::Gzip(level) => write!(f, "gzip:{}", level),
+            Self::Zstd(level) => write!(f, "zstd:{}", level),
+        }
+    }
+}
+
+/// When the active file of a [`RotatingSink`] is moved aside and a fresh one
+/// started.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Rotation {
+    #[default]
+    Never,
+    /// Once the file has grown to this many bytes (compressed size)
+    MaxBytes(u64),
+    /// After this many writes to the same file
+    MaxWrites(u64),
+    /// Once the file was last written on an earlier UTC day
+    Daily,
+}
+
+impl FromStr for Rotation {
+    type Err = anyhow::Error;
+
+    /// `never`, `daily`, `count:N`, or a size such as `500KB`, `100MB`, `1GB`.
+    fn from_str(s: &str) -> Result<Self> {
+        let lower = s.trim().to_ascii_lowercase();
+        match lower.as_str() {
+            "never" | "none" => return Ok(Self::Never),
+            "daily" => return Ok(Self::Daily),
+            _ => {}
+        }
+        if let Some(count) = lower.strip_prefix("count:") {
+            let count: u64 = count.parse().with_context(|| format!("invalid rotation count '{}'", count))?;
+            if count == 0 {
+                bail!("rotation count must be greater than zero");
+            }
+            return Ok(Self::MaxWrites(count));
+        }
+        let (digits, multiplier) = [("gb", 1 << 30), ("mb", 1 << 20), ("kb", 1 << 10), ("b", 1)]
+            .iter()
+            .find_map(|(suffix, m)| lower.strip_suffix(suffix).map(|d| (d, *m)))
+            .unwrap_or((lower.as_str(), 1));
+        let size: u64 = digits.trim().parse()
+            .with_context(|| format!("invalid rotation '{}', expected never, daily, count:N or a size like 100MB", s))?;
+        if size == 0 {
+            bail!("rotation size must be greater than zero");
+        }
+        Ok(Self::MaxBytes(size * multiplier))
+    }
+}
+
+impl fmt::Display for Rotation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Never => f.write_str("never"),
+            Self::Daily => f.write_str("daily"),
+            Self::MaxBytes(bytes) => write!(f, "{}B", bytes),
+            Self::MaxWrites(count) => write!(f, "count:{}", count),
+        }
+    }
+}
+
+enum Encoder {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+    Zstd(zstd::Encoder<'static, BufWriter<File>>),
+}
+
+impl Encoder {
+    fn new(out: BufWriter<File>, compression: Compression) -> io::Result<Self> {
+        Ok(match compression {
+            Compression::None => Self::Plain(out),
+            Compression::Gzip(level) => Self::Gzip(GzEncoder::new(out, flate2::Compression::new(level))),
+            Compression::Zstd(level) => Self::Zstd(zstd::Encoder::new(out, level)?),
+        })
+    }
+
+    fn finish(self) -> io::Result<BufWriter<File>> {
+        match self {
+            Self::Plain(out) => Ok(out),
+            Self::Gzip(enc) => enc.finish(),
+            Self::Zstd(enc) => enc.finish(),
+        }
+    }
+}
+
+impl Write for Encoder {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Plain(out) => out.write(buf),
+            Self::Gzip(enc) => enc.write(buf),
+            Self::Zstd(enc) => enc.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Plain(out) => out.flush(),
+            Self::Gzip(enc) => enc.flush(),
+            Self::Zstd(enc) => enc.flush(),
+        }
+    }
+}
+
+/// A file that only appears at its final path once fully written.
+///
+/// Output goes to a temporary file in the same directory; `commit` finishes
+/// compression, syncs to disk and renames it into place. Dropping without
+/// committing removes the temporary file, so readers never see a partial file.
+pub struct AtomicFile {
+    path: PathBuf,
+    tmp: PathBuf,
+    out: Option<Encoder>,
+}
+
+impl AtomicFile {
+    pub fn create<P: AsRef<Path>>(path: P, compression: Compression) -> Result<Self> {
+        let path = path.as_ref();
+        let name = path.file_name()
+            .with_context(|| format!("{} is not a file path", path.display()))?
+            .to_string_lossy();
+        let tmp = path.with_file_name(format!(".{}.{}.tmp", name, std::process::id()));
+        let file = File::create(&tmp).with_context(|| format!("failed to create {}", tmp.display()))?;
+        Ok(Self { path: path.to_path_buf(), tmp, out: Some(Encoder::new(BufWriter::new(file), compression)?) })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn commit(mut self) -> Result<()> {
+        let out = self.out.take().expect("AtomicFile committed twice");
+        let file = out.finish()?.into_inner().map_err(|e| e.into_error())?;
+        file.sync_all()?;
+        fs::rename(&self.tmp, &self.path)
+            .with_context(|| format!("failed to move {} into place", self.path.display()))?;
+        Ok(())
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.out.as_mut().expect("write after commit").write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.as_mut().expect("flush after commit").flush()
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if self.out.is_some() {
+            let _ = fs::remove_file(&self.tmp);
+        }
+    }
+}
+
+/// A file extended in place. Gzip members and zstd frames concatenate, so
+/// compressed files grow without recompressing what is already there.
+///
+/// `commit` finishes compression and syncs the new data. Dropping without
+/// committing truncates the file back to its old length, so a failed write
+/// leaves no partial output behind. Callers serialize appends themselves,
+/// e.g. with the lock a [`RotatingSink`] holds.
+pub struct AppendFile {
+    path: PathBuf,
+    start: u64,
+    out: Option<Encoder>,
+}
+
+impl AppendFile {
+    pub fn open<P: AsRef<Path>>(path: P, compression: Compression) -> Result<Self> {
+        let path = path.as_ref();
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .read(true)
+            .open(path)
+            .with_context(|| format!("failed to open {}", path.display()))?;
+        let start = file.metadata()?.len();
+        let mut out = BufWriter::new(file.try_clone()?);
+        // Plain text that doesn't end in a newline was cut short by an
+        // older non-atomic writer; never glue new output onto it
+        if compression == Compression::None && start > 0 {
+            let mut last = [0u8; 1];
+            file.seek(SeekFrom::End(-1))?;
+            file.read_exact(&mut last)?;
+            if last[0] != b'\n' {
+                out.write_all(b"\n")?;
+            }
+        }
+        Ok(Self { path: path.to_path_buf(), start, out: Some(Encoder::new(out, compression)?) })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn commit(mut self) -> Result<()> {
+        let out = self.out.take().expect("AppendFile committed twice");
+        let file = out.finish()?.into_inner().map_err(|e| e.into_error())?;
+        file.sync_data()?;
+        Ok(())
+    }
+}
+
+impl Write for AppendFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.out.as_mut().expect("write after commit").write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.as_mut().expect("flush after commit").flush()
+    }
+}
+
+impl Drop for AppendFile {
+    fn drop(&mut self) {
+        // Let the encoder write whatever it buffered before cutting it off
+        if let Some(out) = self.out.take() {
+            drop(out);
+            if let Ok(file) = OpenOptions::new().write(true).open(&self.path) {
+                let _ = file.set_len(self.start);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SinkOutcome {
+    /// Where the previous active file was moved if this write rotated it
+    pub rotated_to: Option<PathBuf>,
+    /// Old generations deleted to stay within `keep`
+    pub removed: Vec<PathBuf>,
+}
+
+/// An output file that grows across many writes, for long-running collection.
+///
+/// Each write is appended to the active file in place (see [`AppendFile`]).
+/// When the rotation policy is due, the active file is renamed to a
+/// timestamped generation next to it, `coins.csv.gz` becoming e.g.
+/// `coins.20260301_120000.csv.gz`, and only the newest `keep` generations of
+/// this file are kept. Writers serialize on an exclusive lock of a sidecar
+/// `<file>.lock`, which also records how many writes the active file has
+/// received.
+#[derive(Debug, Clone)]
+pub struct RotatingSink {
+    path: PathBuf,
+    compression: Compression,
+    rotation: Rotation,
+    keep: Option<usize>,
+}
+
+impl RotatingSink {
+    /// Compression is inferred from the file extension until overridden.
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref().to_path_buf();
+        Self {
+            compression: Compression::from_path(&path),
+            path,
+            rotation: Rotation::Never,
+            keep: None,
+        }
+    }
+
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    pub fn with_rotation(mut self, rotation: Rotation) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    /// Keep at most this many rotated generations besides the active file.
+    pub fn with_keep(mut self, keep: Option<usize>) -> Self {
+        self.keep = keep;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn compression(&self) -> Compression {
+        self.compression
+    }
+
+    /// Write one batch of output. `write` receives the destination and, if
+    /// the active file already has content, its path (to inspect e.g. an
+    /// existing header); `None` means the output starts a new file.
+    pub fn write<F>(&self, write: F) -> Result<SinkOutcome>
+    where
+        F: FnOnce(&mut dyn Write, Option<&Path>) -> Result<()>,
+    {
+        let mut lock = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .read(true)
+            .write(true)
+            .open(self.lock_path())
+            .with_context(|| format!("failed to open lock file for {}", self.path.display()))?;
+        lock.lock()?;
+        let mut state = String::new();
+        lock.read_to_string(&mut state)?;
+        let mut writes: u64 = state.trim().parse().unwrap_or(0);
+
+        let mut outcome = SinkOutcome::default();
+        if let Some(rotated) = self.rotate_if_due(writes, Utc::now())? {
+            outcome.rotated_to = Some(rotated);
+            outcome.removed = self.prune()?;
+            writes = 0;
+        }
+
+        let existing = fs::metadata(&self.path).is_ok_and(|m| m.len() > 0);
+        let mut out = AppendFile::open(&self.path, self.compression)?;
+        write(&mut out, existing.then_some(self.path.as_path()))?;
+        out.commit()?;
+
+        lock.set_len(0)?;
+        lock.seek(SeekFrom::Start(0))?;
+        writeln!(lock, "{}", writes + 1)?;
+        Ok(outcome)
+    }
+
+    fn lock_path(&self) -> PathBuf {
+        let mut name = self.path.file_name().unwrap_or_default().to_os_string();
+        name.push(".lock");
+        self.path.with_file_name(name)
+    }
+
+    fn dir(&self) -> &Path {
+        self.path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."))
+    }
+
+    fn rotate_if_due(&self, writes: u64, now: DateTime<Utc>) -> Result<Option<PathBuf>> {
+        let Ok(meta) = fs::metadata(&self.path) else {
+            return Ok(None);
+        };
+        let modified: DateTime<Utc> = meta.modified()?.into();
+        let due = meta.len() > 0 && match self.rotation {
+            Rotation::Never => false,
+            Rotation::MaxBytes(max) => meta.len() >= max,
+            Rotation::MaxWrites(max) => writes >= max,
+            Rotation::Daily => modified.date_naive() < now.date_naive(),
+        };
+        if !due {
+            return Ok(None);
+        }
+
+        // Name the generation after its last write so it sorts with its data
+        let (prefix, suffix) = self.generation_affixes();
+        let mut target = self.dir().join(generate_filename_with(&prefix, modified, &suffix));
+        let mut n = 1;
+        while target.exists() {
+            target = self.dir().join(generate_filename_with(&prefix, modified, &format!("_{}{}", n, suffix)));
+            n += 1;
+        }
+        fs::rename(&self.path, &target)?;
+        Ok(Some(target))
+    }
+
+    /// What goes before and after the timestamp in a generation's name:
+    /// `("coins.", ".csv.gz")` for `coins.csv.gz`.
+    fn generation_affixes(&self) -> (String, String) {
+        let name = self.path.file_name().unwrap_or_default().to_string_lossy();
+        let compressed = self.compression.extension();
+        let name = name.strip_suffix(compressed).unwrap_or(&name);
+        let (stem, ext) = match name.rfind('.') {
+            Some(dot) if dot > 0 => name.split_at(dot),
+            _ => (name, ""),
+        };
+        (format!("{}.", stem), format!("{}{}", ext, compressed))
+    }
+
+    fn prune(&self) -> Result<Vec<PathBuf>> {
+        let Some(keep) = self.keep else {
+            return Ok(Vec::new());
+        };
+        // Only names rotate_if_due produces for this file; anything else in
+        // the directory is not ours to delete
+        let (prefix, suffix) = self.generation_affixes();
+        let mut generations: Vec<PathBuf> = fs::read_dir(self.dir())?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| {
+                p.file_name()
+                    .and_then(|n| n.to_str())
+                    .and_then(|n| n.strip_prefix(&prefix)?.strip_suffix(&suffix))
+                    .is_some_and(|stamp| stamp.len() >= 15 && stamp.bytes().all(|b| b.is_ascii_digit() || b == b'_'))
+            })
+            .collect();
+        // Oldest last write first. Names alone can't tell: once a generation
+        // is pruned, a rotation within the same second reuses its name
+        generations.sort_by_cached_key(|p| (fs::metadata(p).and_then(|m| m.modified()).ok(), p.clone()));
+        let excess = generations.len().saturating_sub(keep);
+        let removed: Vec<PathBuf> = generations.into_iter().take(excess).collect();
+        for path in &removed {
+            fs::remove_file(path)?;
+        }
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use std::time::SystemTime;
+
+    fn write_line(sink: &RotatingSink, line: &str) -> Result<SinkOutcome> {
+        sink.write(|out, _| Ok(writeln!(out, "{}", line)?))
+    }
+
+    fn read(path: &Path) -> Result<String> {
+        let mut text = String::new();
+        Compression::from_path(path).reader(path)?.read_to_string(&mut text)?;
+        Ok(text)
+    }
+
+    fn names(dir: &Path) -> Result<Vec<String>> {
+        let mut names: Vec<String> = fs::read_dir(dir)?
+            .map(|entry| Ok(entry?.file_name().to_string_lossy().into_owned()))
+            .collect::<Result<_>>()?;
+        names.sort();
+        Ok(names)
+    }
+
+    #[test]
+    fn compressed_appends_decode_to_every_row() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        for name in ["rows.csv.gz", "rows.csv.zst"] {
+            let sink = RotatingSink::new(dir.path().join(name));
+            for i in 0..3 {
+                write_line(&sink, &format!("row{}", i))?;
+            }
+            assert_eq!(read(sink.path())?, "row0\nrow1\nrow2\n", "{}", name);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn failed_write_leaves_the_file_as_it_was() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let sink = RotatingSink::new(dir.path().join("rows.csv.gz"));
+        write_line(&sink, "row0")?;
+        let before = fs::read(sink.path())?;
+
+        let result = sink.write(|out, _| {
+            writeln!(out, "half a row")?;
+            bail!("scrape failed")
+        });
+        assert!(result.is_err());
+        assert_eq!(fs::read(sink.path())?, before);
+        write_line(&sink, "row1")?;
+        assert_eq!(read(sink.path())?, "row0\nrow1\n");
+        Ok(())
+    }
+
+    #[test]
+    fn rotates_every_n_writes() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let sink = RotatingSink::new(dir.path().join("coins.csv")).with_rotation(Rotation::MaxWrites(2));
+        let rotated: Vec<Option<PathBuf>> = (0..5)
+            .map(|i| Ok(write_line(&sink, &format!("row{}", i))?.rotated_to))
+            .collect::<Result<_>>()?;
+
+        assert_eq!(rotated.iter().map(Option::is_some).collect::<Vec<_>>(), [false, false, true, false, true]);
+        let first = rotated[2].as_deref().unwrap();
+        let second = rotated[4].as_deref().unwrap();
+        assert_eq!(read(first)?, "row0\nrow1\n");
+        assert_eq!(read(second)?, "row2\nrow3\n");
+        assert_eq!(read(sink.path())?, "row4\n");
+        Ok(())
+    }
+
+    #[test]
+    fn rotates_daily_into_a_generation_named_after_the_last_write() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let sink = RotatingSink::new(dir.path().join("coins.csv.gz")).with_rotation(Rotation::Daily);
+        write_line(&sink, "yesterday")?;
+        let yesterday = Utc::now() - Duration::days(1);
+        File::options().write(true).open(sink.path())?.set_modified(SystemTime::from(yesterday))?;
+
+        let rotated = write_line(&sink, "today")?.rotated_to.expect("rotated");
+        let expected = format!("coins.{}.csv.gz", yesterday.format("%Y%m%d_%H%M%S"));
+        assert_eq!(rotated.file_name().unwrap().to_string_lossy(), expected);
+        assert_eq!(read(&rotated)?, "yesterday\n");
+        assert_eq!(read(sink.path())?, "today\n");
+        Ok(())
+    }
+
+    #[test]
+    fn prune_only_removes_this_files_generations() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let unrelated = [
+            "coinbase_data_20260101_000000.csv",
+            "other.20260101_000000.csv",
+            "coins.backup.csv",
+            "coins.20260101_000000.csv.gz",
+        ];
+        for name in unrelated {
+            fs::write(dir.path().join(name), "keep me\n")?;
+        }
+        let sink = RotatingSink::new(dir.path().join("coins.csv"))
+            .with_rotation(Rotation::MaxWrites(1))
+            .with_keep(Some(1));
+
+        let mut removed = Vec::new();
+        let mut rotated = Vec::new();
+        for i in 0..4 {
+            let outcome = write_line(&sink, &format!("row{}", i))?;
+            rotated.extend(outcome.rotated_to);
+            removed.extend(outcome.removed);
+        }
+
+        assert_eq!(rotated.len(), 3);
+        assert_eq!(removed, rotated[..2]);
+        for name in unrelated {
+            assert_eq!(fs::read_to_string(dir.path().join(name))?, "keep me\n", "{} was touched", name);
+        }
+        let generation = rotated[2].file_name().unwrap().to_string_lossy().into_owned();
+        let mut expected: Vec<String> = unrelated.iter().map(|n| n.to_string()).collect();
+        expected.extend(["coins.csv".to_string(), "coins.csv.lock".to_string(), generation]);
+        expected.sort();
+        assert_eq!(names(dir.path())?, expected);
+        assert_eq!(read(&rotated[2])?, "row2\n");
+        Ok(())
+    }
+}
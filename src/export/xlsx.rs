@@ -0,0 +1,241 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use rust_xlsxwriter::{Format, FormatAlign, Workbook, Worksheet, XlsxError};
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::Path;
+
+use super::sink::{AtomicFile, Compression};
+use crate::database::{Database, SnapshotFilter};
+use crate::model::CoinRow;
+
+const USD_FORMAT: &str = "$#,##0.00######";
+const MARKET_CAP_FORMAT: &str = "$#,##0";
+const PERCENT_FORMAT: &str = "0.00%";
+const DATETIME_FORMAT: &str = "yyyy-mm-dd hh:mm:ss";
+
+const LATEST_HEADERS: [(&str, f64); 7] = [
+    ("Rank", 8.0),
+    ("Symbol", 10.0),
+    ("Name", 24.0),
+    ("Price (USD)", 16.0),
+    ("Market Cap (USD)", 20.0),
+    ("24h Change", 12.0),
+    ("Scraped At (UTC)", 20.0),
+];
+
+const HISTORY_HEADERS: [(&str, f64); 5] = [
+    ("Timestamp (UTC)", 20.0),
+    ("Rank", 8.0),
+    ("Price (USD)", 16.0),
+    ("Market Cap (USD)", 20.0),
+    ("24h Change", 12.0),
+];
+
+struct Formats {
+    header: Format,
+    usd: Format,
+    market_cap: Format,
+    percent: Format,
+    datetime: Format,
+}
+
+impl Formats {
+    fn new() -> Self {
+        Self {
+            header: Format::new().set_bold().set_align(FormatAlign::Center),
+            usd: Format::new().set_num_format(USD_FORMAT),
+            market_cap: Format::new().set_num_format(MARKET_CAP_FORMAT),
+            percent: Format::new().set_num_format(PERCENT_FORMAT),
+            datetime: Format::new().set_num_format(DATETIME_FORMAT),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct XlsxExport {
+    pub latest_rows: u64,
+    pub history_rows: u64,
+    pub sheets: Vec<String>,
+}
+
+/// Write a workbook with a "Latest" sheet holding the most recent scrape and
+/// one history sheet per symbol in `filter.symbols`. The rank filter applies
+/// to the Latest sheet; the time range applies to the history sheets.
+pub async fn export_xlsx(db: &Database, filter: &SnapshotFilter, path: &Path) -> Result<XlsxExport> {
+    let formats = Formats::new();
+    let mut workbook = Workbook::new();
+    let mut export = XlsxExport::default();
+
+    let latest_filter = SnapshotFilter {
+        min_rank: filter.min_rank,
+        max_rank: filter.max_rank,
+        latest_only: true,
+        ..SnapshotFilter::default()
+    };
+    let latest: Vec<CoinRow> = db.stream_snapshots(&latest_filter)
+        .try_filter(|coin| std::future::ready(coin.rank.is_some()))
+        .try_collect()
+        .await?;
+
+    let sheet = workbook.add_worksheet().set_name("Latest")?;
+    write_header(sheet, &LATEST_HEADERS, &formats)?;
+    for (i, coin) in latest.iter().enumerate() {
+        let row = i as u32 + 1;
+        write_opt(sheet, row, 0, coin.rank.map(|r| r as f64), None)?;
+        sheet.write_string(row, 1, &coin.symbol)?;
+        sheet.write_string(row, 2, &coin.name)?;
+        write_opt(sheet, row, 3, coin.price_usd, Some(&formats.usd))?;
+        write_opt(sheet, row, 4, coin.market_cap_usd, Some(&formats.market_cap))?;
+        write_opt(sheet, row, 5, coin.chg24h_pct.map(|c| c / 100.0), Some(&formats.percent))?;
+        write_timestamp(sheet, row, 6, coin.scraped_at, &formats)?;
+    }
+    finish_sheet(sheet, latest.len() as u32, LATEST_HEADERS.len() as u16)?;
+    export.latest_rows = latest.len() as u64;
+    export.sheets.push("Latest".to_string());
+
+    let mut seen = HashSet::new();
+    let symbols = filter.symbols.iter().flatten().map(|s| s.to_uppercase());
+    for symbol in symbols.filter(|s| seen.insert(s.clone())) {
+        let history_filter = SnapshotFilter {
+            symbols: Some(vec![symbol.clone()]),
+            min_rank: None,
+            max_rank: None,
+            latest_only: false,
+            ..filter.clone()
+        };
+        let name = sheet_name(&symbol);
+        // History sheets can run to a million rows, so stream them to disk
+        let sheet = workbook.add_worksheet_with_constant_memory().set_name(&name)
+            .with_context(|| format!("cannot use '{}' as a sheet name", name))?;
+        write_header(sheet, &HISTORY_HEADERS, &formats)?;
+
+        let mut rows = std::pin::pin!(db.stream_snapshots(&history_filter));
+        let mut count = 0u32;
+        while let Some(snapshot) = rows.try_next().await? {
+            let row = count + 1;
+            write_timestamp(sheet, row, 0, snapshot.scraped_at, &formats)
+                .with_context(|| format!("too many rows for sheet {}", name))?;
+            write_opt(sheet, row, 1, snapshot.rank.map(|r| r as f64), None)?;
+            write_opt(sheet, row, 2, snapshot.price_usd, Some(&formats.usd))?;
+            write_opt(sheet, row, 3, snapshot.market_cap_usd, Some(&formats.market_cap))?;
+            write_opt(sheet, row, 4, snapshot.chg24h_pct.map(|c| c / 100.0), Some(&formats.percent))?;
+            count += 1;
+        }
+        finish_sheet(sheet, count, HISTORY_HEADERS.len() as u16)?;
+        export.history_rows += count as u64;
+        export.sheets.push(name);
+    }
+
+    let mut file = AtomicFile::create(path, Compression::None)?;
+    file.write_all(&workbook.save_to_buffer()?)?;
+    file.commit()?;
+    Ok(export)
+}
+
+fn write_header(sheet: &mut Worksheet, headers: &[(&str, f64)], formats: &Formats) -> Result<(), XlsxError> {
+    for (col, (title, width)) in headers.iter().enumerate() {
+        sheet.write_string_with_format(0, col as u16, *title, &formats.header)?;
+        sheet.set_column_width(col as u16, *width)?;
+    }
+    Ok(())
+}
+
+/// Freeze the header row and add an autofilter over the written range.
+fn finish_sheet(sheet: &mut Worksheet, rows: u32, columns: u16) -> Result<(), XlsxError> {
+    sheet.set_freeze_panes(1, 0)?;
+    if let Some(last) = columns.checked_sub(1) {
+        sheet.autofilter(0, 0, rows, last)?;
+    }
+    Ok(())
+}
+
+fn write_opt(sheet: &mut Worksheet, row: u32, col: u16, value: Option<f64>, format: Option<&Format>) -> Result<(), XlsxError> {
+    match (value, format) {
+        (Some(value), Some(format)) => sheet.write_number_with_format(row, col, value, format)?,
+        (Some(value), None) => sheet.write_number(row, col, value)?,
+        (None, _) => sheet,
+    };
+    Ok(())
+}
+
+fn write_timestamp(sheet: &mut Worksheet, row: u32, col: u16, ts: DateTime<Utc>, formats: &Formats) -> Result<(), XlsxError> {
+    sheet.write_datetime_with_format(row, col, ts.naive_utc(), &formats.datetime)?;
+    Ok(())
+}
+
+/// Excel sheet names are at most 31 characters and may not contain `[]:*?/\`.
+fn sheet_name(symbol: &str) -> String {
+    symbol
+        .chars()
+        .map(|c| if "[]:*?/\\".contains(c) { '_' } else { c })
+        .take(31)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::testing::{coin, temp_db};
+
+    #[tokio::test]
+    async fn latest_sheet_filters_on_rank_not_position() -> Result<()> {
+        let (dir, db) = temp_db().await?;
+        let at = "2026-01-01T00:00:00Z";
+        // Rank 2 is missing, so the top two by position would include rank 3
+        db.save_coins(&[coin(1, "BTC", 1, 100.0, at), coin(3, "XRP", 3, 1.0, at), coin(4, "SOL", 4, 10.0, at)]).await?;
+
+        let filter = SnapshotFilter { max_rank: Some(2), ..SnapshotFilter::default() };
+        let export = export_xlsx(&db, &filter, &dir.path().join("latest.xlsx")).await?;
+        assert_eq!(export.latest_rows, 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn writes_one_history_sheet_per_distinct_symbol() -> Result<()> {
+        let (dir, db) = temp_db().await?;
+        for at in ["2026-01-01T00:00:00Z", "2026-01-02T00:00:00Z"] {
+            db.save_coins(&[coin(1, "BTC", 1, 100.0, at), coin(1027, "ETH", 2, 10.0, at), coin(9, "W/ETH", 3, 1.0, at)]).await?;
+        }
+
+        let filter = SnapshotFilter {
+            symbols: Some(vec!["btc".into(), "BTC".into(), "w/eth".into(), "ETH".into()]),
+            ..SnapshotFilter::default()
+        };
+        let path = dir.path().join("history.xlsx");
+        let export = export_xlsx(&db, &filter, &path).await?;
+        assert_eq!(export.sheets, vec!["Latest", "BTC", "W_ETH", "ETH"]);
+        assert_eq!(export.latest_rows, 3);
+        assert_eq!(export.history_rows, 6);
+        assert!(path.metadata()?.len() > 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn writes_an_empty_latest_sheet_without_data() -> Result<()> {
+        let (dir, db) = temp_db().await?;
+        let path = dir.path().join("empty.xlsx");
+        let export = export_xlsx(&db, &SnapshotFilter::default(), &path).await?;
+        assert_eq!((export.latest_rows, export.history_rows), (0, 0));
+        assert_eq!(export.sheets, vec!["Latest"]);
+        assert!(path.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn sheet_names_are_sanitized_and_truncated() {
+        assert_eq!(sheet_name("BTC"), "BTC");
+        assert_eq!(sheet_name("A[B]:C*D?E/F\\G"), "A_B__C_D_E_F_G");
+        let long = "X".repeat(40);
+        assert_eq!(sheet_name(&long), "X".repeat(31));
+        assert_eq!(sheet_name("Ωmega").chars().count(), 5);
+    }
+
+    #[test]
+    fn finishing_a_sheet_without_columns_is_harmless() {
+        let mut workbook = Workbook::new();
+        let sheet = workbook.add_worksheet();
+        assert!(finish_sheet(sheet, 0, 0).is_ok());
+    }
+}
@@ -1,9 +1,11 @@
 use anyhow::{anyhow, Context, Result};
 use reqwest::header::{HeaderMap, HeaderValue, USER_AGENT, ACCEPT, ACCEPT_LANGUAGE};
 use scraper::{Html, Selector};
+use serde::Serialize;
 use serde_json::Value;
 use std::collections::{BTreeMap, HashSet};
-use chrono::Utc;
+use std::fmt;
+use chrono::{DateTime, Utc};
 use futures::future::join_all;
 use tokio::time::{sleep, Duration};
 
@@ -35,8 +37,41 @@ pub async fn scrape_coins(pages: u32) -> Result<Vec<CoinRow>> {
 }
 
 pub async fn scrape_coins_concurrent(pages: u32, batch_size: u32, pause_ms: u64) -> Result<Vec<CoinRow>> {
+    let outcome = scrape_pages(pages, batch_size, pause_ms).await;
+    for err in &outcome.errors {
+        tracing::warn!("Scrape error: {}", err);
+    }
+    Ok(outcome.rows)
+}
+
+/// A page that could not be fetched or parsed.
+#[derive(Debug, Clone, Serialize)]
+pub struct PageError {
+    pub page: u32,
+    pub error: String,
+}
+
+impl fmt::Display for PageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The error already names the page
+        f.write_str(&self.error)
+    }
+}
+
+/// Rows from every page that succeeded, plus the pages that didn't.
+#[derive(Debug)]
+pub struct ScrapeOutcome {
+    pub scraped_at: DateTime<Utc>,
+    pub rows: Vec<CoinRow>,
+    pub errors: Vec<PageError>,
+}
+
+/// Scrape `pages` pages, `batch_size` at a time. Failed pages are collected
+/// rather than aborting the run.
+pub async fn scrape_pages(pages: u32, batch_size: u32, pause_ms: u64) -> ScrapeOutcome {
     let mut seen = HashSet::new();
     let mut rows = Vec::new();
+    let mut errors = Vec::new();
     let scraped_at = Utc::now();
 
     let page_numbers: Vec<u32> = (1..=pages).collect();
@@ -44,7 +79,6 @@ pub async fn scrape_coins_concurrent(pages: u32, batch_size: u32, pause_ms: u64)
     for chunk in page_numbers.chunks(batch_size as usize) {
         let tasks: Vec<_> = chunk.iter()
             .map(|&page| {
-                let scraped_at = scraped_at;
                 tokio::spawn(async move {
                     let url = if page == 1 {
                         "https://coinmarketcap.com/".to_string()
@@ -63,7 +97,7 @@ pub async fn scrape_coins_concurrent(pages: u32, batch_size: u32, pause_ms: u64)
 
         let results = join_all(tasks).await;
         
-        for res in results {
+        for (&page, res) in chunk.iter().zip(results) {
             match res {
                 Ok(Ok(page_rows)) => {
                     for r in page_rows {
@@ -72,8 +106,8 @@ pub async fn scrape_coins_concurrent(pages: u32, batch_size: u32, pause_ms: u64)
                         }
                     }
                 }
-                Ok(Err(e)) => eprintln!("Scrape error: {}", e),
-                Err(e) => eprintln!("Task join error: {}", e),
+                Ok(Err(e)) => errors.push(PageError { page, error: format!("{:#}", e) }),
+                Err(e) => errors.push(PageError { page, error: format!("task failed: {}", e) }),
             }
         }
 
@@ -83,7 +117,7 @@ pub async fn scrape_coins_concurrent(pages: u32, batch_size: u32, pause_ms: u64)
     }
 
     rows.sort_by_key(|r| r.rank.unwrap_or(u64::MAX));
-    Ok(rows)
+    ScrapeOutcome { scraped_at, rows, errors }
 }
 
 async fn fetch_html(url: &str) -> Result<String> {
@@ -155,20 +189,19 @@ fn parse_coin_object(v: &Value, scraped_at: chrono::DateTime<chrono::Utc>) -> Op
         chg24 = qusd.get("percentChange24h").and_then(Value::as_f64);
     }
 
-    if price_usd.is_none() {
-        if let Some(quotes) = v.get("quotes").and_then(Value::as_array) {
-            let mut map = BTreeMap::new();
-            for q in quotes {
-                if let Some(k) = q.get("name").and_then(Value::as_str) {
-                    map.insert(k.to_string(), q);
-                }
-            }
-            if let Some(usd) = map.get("USD") {
-                price_usd = usd.get("price").and_then(Value::as_f64);
-                mcap_usd  = usd.get("marketCap").and_then(Value::as_f64);
-                chg24     = usd.get("percentChange24h").and_then(Value::as_f64);
+    if price_usd.is_none()
+        && let Some(quotes) = v.get("quotes").and_then(Value::as_array) {
+        let mut map = BTreeMap::new();
+        for q in quotes {
+            if let Some(k) = q.get("name").and_then(Value::as_str) {
+                map.insert(k.to_string(), q);
             }
         }
+        if let Some(usd) = map.get("USD") {
+            price_usd = usd.get("price").and_then(Value::as_f64);
+            mcap_usd  = usd.get("marketCap").and_then(Value::as_f64);
+            chg24     = usd.get("percentChange24h").and_then(Value::as_f64);
+        }
     }
 
     Some(CoinRow {
@@ -0,0 +1,245 @@
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use csv::{ReaderBuilder, StringRecord};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::database::{Database, Source};
+use crate::export::sink::Compression;
+use crate::model::CoinRow;
+
+const MAX_REPORTED_ERRORS: usize = 20;
+
+/// Which CSV column feeds each `CoinRow` field. Defaults to the column names
+/// our own exports use; override individual fields for foreign files.
+#[derive(Debug, Clone)]
+pub struct ColumnMapping {
+    columns: [String; 8],
+}
+
+impl Default for ColumnMapping {
+    fn default() -> Self {
+        Self {
+            columns: CoinRow::FIELDS.map(str::to_string),
+        }
+    }
+}
+
+impl ColumnMapping {
+    /// Read `field` from the CSV column named `column`.
+    pub fn map(&mut self, field: &str, column: &str) -> Result<()> {
+        let idx = CoinRow::FIELDS.iter().position(|f| *f == field)
+            .ok_or_else(|| anyhow!("unknown field '{}', expected one of: {}", field, CoinRow::FIELDS.join(", ")))?;
+        self.columns[idx] = column.to_string();
+        Ok(())
+    }
+
+    /// Parse `field=column` overrides as given on the command line.
+    pub fn from_overrides<S: AsRef<str>>(overrides: &[S]) -> Result<Self> {
+        let mut mapping = Self::default();
+        for spec in overrides {
+            let spec = spec.as_ref();
+            let (field, column) = spec.split_once('=')
+                .ok_or_else(|| anyhow!("invalid mapping '{}', expected field=column", spec))?;
+            mapping.map(field.trim(), column.trim())?;
+        }
+        Ok(mapping)
+    }
+
+    fn resolve(&self, headers: &StringRecord) -> Result<[Option<usize>; 8]> {
+        let mut positions = [None; 8];
+        for (i, column) in self.columns.iter().enumerate() {
+            positions[i] = headers.iter().position(|h| h.trim() == column);
+        }
+        for required in ["id", "name", "symbol", "scraped_at"] {
+            let i = CoinRow::FIELDS.iter().position(|f| *f == required).unwrap();
+            if positions[i].is_none() {
+                bail!("missing column '{}' for field '{}'", self.columns[i], required);
+            }
+        }
+        Ok(positions)
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ImportReport {
+    pub files: usize,
+    pub rows_read: u64,
+    pub inserted: u64,
+    pub skipped: u64,
+    pub invalid: u64,
+    /// Rows outside the time ranges being backfilled
+    pub out_of_range: u64,
+    /// Scrape runs (distinct `scraped_at` values) that received new rows
+    pub runs: u64,
+    /// First few validation errors, as `file:line: message`
+    pub errors: Vec<String>,
+    /// Times behind `runs`, so a run split across chunks or files counts once
+    #[serde(skip)]
+    run_times: HashSet<DateTime<Utc>>,
+}
+
+impl ImportReport {
+    fn invalid(&mut self, path: &Path, line: u64, err: anyhow::Error) {
+        self.invalid += 1;
+        if self.errors.len() < MAX_REPORTED_ERRORS {
+            self.errors.push(format!("{}:{}: {:#}", path.display(), line, err));
+        }
+    }
+}
+
+/// Import every file into the database, accumulating one report.
+pub async fn import_csv_files<P: AsRef<Path>>(db: &Database, paths: &[P], mapping: &ColumnMapping) -> Result<ImportReport> {
+    let mut report = ImportReport::default();
+    for path in paths {
+        import_csv(db, path.as_ref(), mapping, &mut report).await?;
+    }
+    Ok(report)
+}
+
+/// Import one CSV file, which may be gzip or zstd compressed. Rows are
+/// grouped into scrape runs by `scraped_at`; snapshots that already exist for
+/// a coin at that timestamp are skipped, so re-importing a file is a no-op.
+pub async fn import_csv(db: &Database, path: &Path, mapping: &ColumnMapping, report: &mut ImportReport) -> Result<()> {
+    replay_csv(db, path, mapping, report, |_| true, Source::Import).await
+}
+
+/// Like `import_csv`, but only rows whose `scraped_at` passes `keep` are
+/// saved, tagged with `source`.
+pub async fn replay_csv<F>(db: &Database, path: &Path, mapping: &ColumnMapping, report: &mut ImportReport, keep: F, source: Source) -> Result<()>
+where
+    F: Fn(DateTime<Utc>) -> bool,
+{
+    let mut rdr = ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(Compression::from_path(path).reader(path)?);
+    let headers = rdr.headers()?.clone();
+    let positions = mapping.resolve(&headers)
+        .with_context(|| format!("cannot import {}", path.display()))?;
+    report.files += 1;
+
+    let mut run: Vec<CoinRow> = Vec::new();
+    let mut record = StringRecord::new();
+    while rdr.read_record(&mut record)? {
+        let line = record.position().map(|p| p.line()).unwrap_or_default();
+        // Older append_to_csv output repeats the header inside the file
+        if record == headers {
+            continue;
+        }
+        report.rows_read += 1;
+
+        let row = match parse_row(&record, &positions) {
+            Ok(row) => row,
+            Err(err) => {
+                report.invalid(path, line, err);
+                continue;
+            }
+        };
+        if !keep(row.scraped_at) {
+            report.out_of_range += 1;
+            continue;
+        }
+        if run.first().is_some_and(|r| r.scraped_at != row.scraped_at) {
+            save_run(db, std::mem::take(&mut run), report, source).await?;
+        }
+        run.push(row);
+    }
+    save_run(db, run, report, source).await?;
+
+    Ok(())
+}
+
+async fn save_run(db: &Database, mut run: Vec<CoinRow>, report: &mut ImportReport, source: Source) -> Result<()> {
+    let Some(first) = run.first() else {
+        return Ok(());
+    };
+    let mut present = db.coin_ids_at(first.scraped_at).await?;
+    let total = run.len() as u64;
+    // `insert` also drops coins listed twice within the same run
+    run.retain(|row| present.insert(row.id));
+
+    report.skipped += total - run.len() as u64;
+    if !run.is_empty() {
+        db.save_coins_from(&run, source).await?;
+        report.inserted += run.len() as u64;
+        if report.run_times.insert(run[0].scraped_at) {
+            report.runs += 1;
+        }
+    }
+    Ok(())
+}
+
+fn parse_row(record: &StringRecord, positions: &[Option<usize>; 8]) -> Result<CoinRow> {
+    let field = |i: usize| -> Option<&str> {
+        positions[i].and_then(|p| record.get(p)).map(str::trim).filter(|v| !v.is_empty())
+    };
+    let required = |i: usize| field(i).ok_or_else(|| anyhow!("{} is empty", CoinRow::FIELDS[i]));
+    let float = |i: usize, allow_negative: bool| -> Result<Option<f64>> {
+        field(i).map(|v| {
+            let n: f64 = v.parse().with_context(|| format!("{} '{}' is not a number", CoinRow::FIELDS[i], v))?;
+            if !n.is_finite() || (n < 0.0 && !allow_negative) {
+                bail!("{} {} is out of range", CoinRow::FIELDS[i], n);
+            }
+            Ok(n)
+        }).transpose()
+    };
+
+    let id = required(0)?.parse::<u64>().context("id is not a positive integer")?;
+    let rank = field(1)
+        .map(|v| v.parse::<u64>().context("rank is not a positive integer"))
+        .transpose()?;
+    if rank == Some(0) {
+        bail!("rank must be at least 1");
+    }
+
+    Ok(CoinRow {
+        id,
+        rank,
+        name: required(2)?.to_string(),
+        symbol: required(3)?.to_uppercase(),
+        price_usd: float(4, false)?,
+        market_cap_usd: float(5, false)?,
+        chg24h_pct: float(6, true)?,
+        scraped_at: parse_timestamp(required(7)?)?,
+    })
+}
+
+fn parse_timestamp(value: &str) -> Result<DateTime<Utc>> {
+    if let Ok(ts) = DateTime::parse_from_rfc3339(value) {
+        return Ok(ts.with_timezone(&Utc));
+    }
+    // Naive timestamps are taken to be UTC
+    for format in ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"] {
+        if let Ok(ts) = NaiveDateTime::parse_from_str(value, format) {
+            return Ok(ts.and_utc());
+        }
+    }
+    bail!("scraped_at '{}' is not a valid timestamp", value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::testing::temp_db;
+
+    #[tokio::test]
+    async fn runs_count_distinct_timestamps() -> Result<()> {
+        let (dir, db) = temp_db().await?;
+        let header = "id,rank,name,symbol,price_usd,market_cap_usd,chg24h_pct,scraped_at\n";
+        let first = dir.path().join("first.csv");
+        std::fs::write(&first, format!(
+            "{}1,1,Bitcoin,BTC,100,,,2026-01-01T00:00:00Z\n\
+             1,1,Bitcoin,BTC,101,,,2026-01-01T01:00:00Z\n\
+             1027,2,Ethereum,ETH,10,,,2026-01-01T00:00:00Z\n",
+            header
+        ))?;
+        let second = dir.path().join("second.csv");
+        std::fs::write(&second, format!("{}1027,2,Ethereum,ETH,11,,,2026-01-01T01:00:00Z\n", header))?;
+
+        let report = import_csv_files(&db, &[first, second], &ColumnMapping::default()).await?;
+        assert_eq!(report.inserted, 4);
+        assert_eq!(report.runs, 2);
+        Ok(())
+    }
+}
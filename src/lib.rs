@@ -2,10 +2,29 @@ pub mod model;
 pub mod fetch;
 pub mod export;
 pub mod database;
+pub mod import;
 pub mod api;
+pub mod daemon;
+pub mod config;
+pub mod scrape;
+pub mod render;
+pub mod watch;
+pub mod backfill;
 
-pub use model::{CoinRow, CoinResponse, HistoryResponse, HealthResponse};
-pub use fetch::{scrape_coins, scrape_coins_concurrent};
-pub use export::{save_to_csv, append_to_csv, generate_filename};
-pub use database::{Database, CoinSummary, HistoryPoint};
-pub use api::start_server;
\ No newline at end of file
+pub use model::{CoinRow, CoinResponse, CoinsDiff, CompareResponse, CompareSeries, HistoryResponse, HealthResponse, LatestCoinsResponse, MarketSummary, Mover, MoversResponse, RankChange};
+pub use fetch::{scrape_coins, scrape_coins_concurrent, scrape_pages, PageError, ScrapeOutcome};
+pub use export::{save_to_csv, append_to_csv, generate_filename, generate_filename_at, generate_filename_with, export_snapshots, ExportFormat, StreamWriter};
+pub use export::append::{AppendOutcome, CsvAppender};
+pub use export::sink::{AppendFile, AtomicFile, Compression, Rotation, RotatingSink, SinkOutcome};
+pub use export::parquet::{export_parquet, ParquetCompression, ParquetOptions, ParquetWriter};
+pub use export::xlsx::{export_xlsx, XlsxExport};
+pub use database::{Database, CoinQuery, CoinSummary, HistoryPoint, SnapshotFilter, CompactReport, RetentionPolicy, EpochMillis, RunStatus, ScrapeRun, Source, Finding, RepairReport, BackupReport, RestoreReport, MoverDirection, MoversFilter, MoverWindow};
+pub use database::backup::restore;
+pub use scrape::{run_scrape, CsvSummary, ScrapeSummary};
+pub use import::{import_csv, import_csv_files, replay_csv, ColumnMapping, ImportReport};
+pub use backfill::{backfill, find_gaps, Gap};
+pub use api::{serve, start_server};
+pub use config::{BackupConfig, Config, DatabaseConfig, FetchConfig, ScheduleConfig, ServerConfig};
+pub use daemon::{Backoff, Daemon, DaemonOptions};
+pub use daemon::schedule::Schedule;
+pub use watch::{watch, WatchOptions};
\ No newline at end of file
@@ -1,12 +1,24 @@
 use anyhow::Result;
-use coinbase_scraper::{scrape_coins_concurrent, Database, start_server};
-use std::time::Instant;
-use clap::{Parser, Subcommand};
+use coinbase_scraper::daemon::schedule::parse_duration;
+use coinbase_scraper::database::backup::backup_filename;
+use coinbase_scraper::render::{render, RenderFormat};
+use coinbase_scraper::{backfill, restore, CoinQuery, BackupConfig, find_gaps, Finding, Gap, Source, watch, WatchOptions, run_scrape, HistoryResponse, Mover, MoverDirection, MoversFilter, MoversResponse, MoverWindow, serve, RunStatus, ScrapeSummary, Backoff, Daemon, DaemonOptions, Schedule, Config, DatabaseConfig, FetchConfig, ServerConfig, import_csv_files, export_snapshots, export_parquet, export_xlsx, AtomicFile, ColumnMapping, Compression, CsvAppender, Database, ExportFormat, ParquetCompression, ParquetOptions, RetentionPolicy, Rotation, SnapshotFilter};
+use chrono::{DateTime, NaiveDate, SecondsFormat, Utc};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use clap::{Args, Parser, Subcommand};
 
 #[derive(Parser)]
 #[command(name = "coinbase_scraper")]
 #[command(about = "A high-performance cryptocurrency data scraper with database storage and REST API")]
 struct Cli {
+    /// Config file (default: $COINBASE_SCRAPER_CONFIG, then ./coinbase_scraper.toml if present)
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
     #[command(subcommand)]
     command: Commands,
 }
@@ -15,68 +27,599 @@ struct Cli {
 enum Commands {
     /// Scrape cryptocurrency data and save to database
     Scrape {
-        /// Number of pages to scrape
-        #[arg(short, long, default_value_t = 10)]
-        pages: u32,
-        /// Database path
-        #[arg(short, long, default_value = "sqlite:cmc.db")]
-        db: String,
+        /// Number of pages to scrape [config: fetch.pages]
+        #[arg(short, long)]
+        pages: Option<u32>,
+        /// Database path [config: database.url]
+        #[arg(short, long)]
+        db: Option<String>,
+        /// Report format: human, json (run summary on stdout) or quiet (errors only)
+        #[arg(short, long, default_value = "human")]
+        output: OutputMode,
+        #[command(flatten)]
+        csv: CsvArgs,
+    },
+    /// Scrape on a schedule, optionally serving the API from the same process
+    Daemon {
+        /// Number of pages to scrape [config: fetch.pages]
+        #[arg(short, long)]
+        pages: Option<u32>,
+        /// Database path [config: database.url]
+        #[arg(short, long)]
+        db: Option<String>,
+        /// Scrape every interval, aligned to the clock: 90s, 15m, 1h, 1d [config: schedule.interval]
+        #[arg(long, value_parser = parse_duration, conflicts_with = "cron")]
+        interval: Option<Duration>,
+        /// Scrape on a cron expression in UTC, e.g. "*/15 * * * *" [config: schedule.cron]
+        #[arg(long)]
+        cron: Option<String>,
+        /// Also serve the API from this process
+        #[arg(long)]
+        serve: bool,
+        /// Port for the embedded API server [config: server.bind]
+        #[arg(long, requires = "serve")]
+        port: Option<u16>,
+        /// Longest delay between retries after failed runs [config: schedule.max_backoff]
+        #[arg(long, value_parser = parse_duration)]
+        max_backoff: Option<Duration>,
+        /// Compact the database on this interval, e.g. 1d [config: schedule.compact_every]
+        #[arg(long, value_parser = parse_duration)]
+        compact_every: Option<Duration>,
+        /// TOML file with a [retention] table for scheduled compaction [config: retention]
+        #[arg(long)]
+        policy: Option<PathBuf>,
+        /// Back up the database on this interval, e.g. 1d [config: schedule.backup_every]
+        #[arg(long, value_parser = parse_duration)]
+        backup_every: Option<Duration>,
+        /// Directory for scheduled backups [config: backup.dir]
+        #[arg(long)]
+        backup_dir: Option<PathBuf>,
+        /// Number of scheduled backups to keep [config: backup.keep]
+        #[arg(long)]
+        backup_keep: Option<usize>,
+        /// Compress scheduled backups: none, gzip[:level] or zstd[:level] [config: backup.compress]
+        #[arg(long)]
+        backup_compress: Option<Compression>,
+        #[command(flatten)]
+        csv: CsvArgs,
     },
     /// Start the REST API server
     Serve {
-        /// Port to run the server on
-        #[arg(short, long, default_value_t = 8080)]
-        port: u16,
-        /// Database path
-        #[arg(short, long, default_value = "sqlite:cmc.db")]
-        db: String,
+        /// Port to run the server on [config: server.bind]
+        #[arg(short, long)]
+        port: Option<u16>,
+        /// Database path [config: database.url]
+        #[arg(short, long)]
+        db: Option<String>,
+    },
+    /// Import historical CSV files into the database
+    Import {
+        /// CSV files to import
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        /// Database path [config: database.url]
+        #[arg(short, long)]
+        db: Option<String>,
+        /// Read a field from a differently named column, e.g. --map chg24h_pct=percent_change_24h
+        #[arg(short, long = "map", value_name = "FIELD=COLUMN")]
+        mappings: Vec<String>,
+    },
+    /// Find scrape runs missed by the schedule and fill them from CSV archives
+    Backfill {
+        /// Database path [config: database.url]
+        #[arg(short, long)]
+        db: Option<String>,
+        /// Expected scrape interval, e.g. 15m [config: schedule.interval]
+        #[arg(long, value_parser = parse_duration, conflicts_with = "cron")]
+        interval: Option<Duration>,
+        /// Expected cron schedule in UTC [config: schedule.cron]
+        #[arg(long)]
+        cron: Option<String>,
+        /// Start of the range to check (RFC 3339 or YYYY-MM-DD; default: 7 days ago, or the first snapshot if later)
+        #[arg(long, value_parser = parse_time)]
+        since: Option<DateTime<Utc>>,
+        /// End of the range to check (RFC 3339 or YYYY-MM-DD; default: now)
+        #[arg(long, value_parser = parse_time)]
+        until: Option<DateTime<Utc>>,
+        /// CSV archive files or directories to replay (.csv, .csv.gz, .csv.zst); without any, gaps are only listed
+        #[arg(short, long)]
+        archive: Vec<PathBuf>,
+        /// Read a field from a differently named column, e.g. --map chg24h_pct=percent_change_24h
+        #[arg(short, long = "map", value_name = "FIELD=COLUMN")]
+        mappings: Vec<String>,
+    },
+    /// Export snapshots from the database to CSV, JSON Lines or JSON
+    Export {
+        /// Database path [config: database.url]
+        #[arg(short, long)]
+        db: Option<String>,
+        /// Output format: csv, jsonl, json, parquet or xlsx
+        #[arg(short, long, default_value = "csv")]
+        format: ExportFormat,
+        /// Output file; writes to stdout if omitted or "-" (parquet and xlsx require a path)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Compress the output file: none, gzip[:level] or zstd[:level] (default: from extension)
+        #[arg(long)]
+        compress: Option<Compression>,
+        /// Parquet compression: none, snappy, gzip[:level] or zstd[:level]
+        #[arg(long, default_value = "snappy")]
+        compression: ParquetCompression,
+        /// Maximum rows per Parquet row group
+        #[arg(long, default_value_t = 1024 * 1024, value_parser = clap::value_parser!(u64).range(1..))]
+        row_group_size: u64,
+        /// Write Parquet as a directory with one date=YYYY-MM-DD partition per day
+        #[arg(long)]
+        partition_by_date: bool,
+        /// Only these symbols, comma separated
+        #[arg(short, long, value_delimiter = ',')]
+        symbols: Option<Vec<String>>,
+        /// Lowest rank to include
+        #[arg(long)]
+        min_rank: Option<u64>,
+        /// Highest rank to include
+        #[arg(long)]
+        max_rank: Option<u64>,
+        /// Start of the time range (RFC 3339 or YYYY-MM-DD), inclusive
+        #[arg(long, value_parser = parse_time)]
+        since: Option<DateTime<Utc>>,
+        /// End of the time range (RFC 3339 or YYYY-MM-DD), exclusive
+        #[arg(long, value_parser = parse_time)]
+        until: Option<DateTime<Utc>>,
+        /// Only the most recent scrape run
+        #[arg(long)]
+        latest: bool,
+        /// Only raw snapshots from this source: scrape, import or backfill
+        #[arg(long)]
+        source: Option<Source>,
+    },
+    /// Show the latest snapshot of the top coins by rank
+    Top {
+        /// Number of coins to show, at most 500
+        #[arg(short, long, default_value_t = CoinQuery::DEFAULT_LIMIT)]
+        limit: u32,
+        /// Database path [config: database.url]
+        #[arg(short, long)]
+        db: Option<String>,
+        /// Output format: table, json or csv
+        #[arg(short, long, default_value = "table")]
+        format: RenderFormat,
+    },
+    /// Show the latest snapshot of one coin
+    Coin {
+        /// Coin symbol, e.g. BTC
+        symbol: String,
+        /// Database path [config: database.url]
+        #[arg(short, long)]
+        db: Option<String>,
+        /// Output format: table, json or csv
+        #[arg(short, long, default_value = "table")]
+        format: RenderFormat,
+    },
+    /// Show the price history of one coin, oldest first
+    History {
+        /// Coin symbol, e.g. BTC
+        symbol: String,
+        /// Start of the time range (RFC 3339 or YYYY-MM-DD), inclusive
+        #[arg(long, value_parser = parse_time)]
+        since: Option<DateTime<Utc>>,
+        /// End of the time range (RFC 3339 or YYYY-MM-DD), exclusive
+        #[arg(long, value_parser = parse_time)]
+        until: Option<DateTime<Utc>>,
+        /// Maximum number of points
+        #[arg(short, long, default_value_t = 500)]
+        limit: u32,
+        /// Database path [config: database.url]
+        #[arg(short, long)]
+        db: Option<String>,
+        /// Output format: table, json or csv
+        #[arg(short, long, default_value = "table")]
+        format: RenderFormat,
+    },
+    /// Show the biggest gainers and losers of the latest scrape over a window
+    Movers {
+        /// Window the change is measured over: 1h, 24h or 7d
+        #[arg(short, long, default_value = "24h")]
+        window: MoverWindow,
+        /// Only gainers (up) or only losers (down)
+        #[arg(long)]
+        direction: Option<MoverDirection>,
+        /// Number of coins on each side
+        #[arg(short, long, default_value_t = 10)]
+        limit: u32,
+        /// Leave out coins with a smaller market cap (USD)
+        #[arg(long)]
+        min_market_cap: Option<f64>,
+        /// Only consider the N largest coins by market cap
+        #[arg(long)]
+        top: Option<u32>,
+        /// Database path [config: database.url]
+        #[arg(short, long)]
+        db: Option<String>,
+        /// Output format: table, json or csv
+        #[arg(short, long, default_value = "table")]
+        format: RenderFormat,
+    },
+    /// Show recent scrape runs, newest first
+    Runs {
+        /// Number of runs to show
+        #[arg(short, long, default_value_t = 20)]
+        limit: i64,
+        /// Database path [config: database.url]
+        #[arg(short, long)]
+        db: Option<String>,
+        /// Output format: table, json or csv
+        #[arg(short, long, default_value = "table")]
+        format: RenderFormat,
+    },
+    /// Full-screen dashboard of the latest scrape that updates as new runs land
+    Watch {
+        /// Number of coins to show, by rank
+        #[arg(short, long, default_value_t = 50)]
+        limit: i64,
+        /// Database path [config: database.url]
+        #[arg(short, long)]
+        db: Option<String>,
+        /// How often to check the database for a new scrape
+        #[arg(long, value_parser = parse_duration, default_value = "5s")]
+        refresh: Duration,
+        /// How far back the price sparklines go
+        #[arg(long, value_parser = parse_duration, default_value = "1d")]
+        window: Duration,
+    },
+    /// Database maintenance
+    Db {
+        #[command(subcommand)]
+        command: DbCommands,
+    },
+    /// Inspect the configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputMode {
+    Human,
+    Json,
+    Quiet,
+}
+
+impl FromStr for OutputMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "human" => Ok(Self::Human),
+            "json" => Ok(Self::Json),
+            "quiet" => Ok(Self::Quiet),
+            other => anyhow::bail!("unknown output mode '{}', expected human, json or quiet", other),
+        }
+    }
+}
+
+#[derive(Args)]
+struct CsvArgs {
+    /// Also append the scraped rows to this CSV file
+    #[arg(long)]
+    csv: Option<PathBuf>,
+    /// Rotate the CSV file: never, daily, count:N (runs), or a size like 100MB
+    #[arg(long, default_value = "never", requires = "csv")]
+    csv_rotate: Rotation,
+    /// Keep only this many rotated CSV files
+    #[arg(long, requires = "csv")]
+    csv_keep: Option<usize>,
+    /// Compress the CSV file: none, gzip[:level] or zstd[:level] (default: from extension)
+    #[arg(long, requires = "csv")]
+    csv_compress: Option<Compression>,
+}
+
+impl CsvArgs {
+    fn appender(self) -> Option<CsvAppender> {
+        let appender = CsvAppender::new(self.csv?).with_rotation(self.csv_rotate).with_keep(self.csv_keep);
+        Some(match self.csv_compress {
+            Some(compression) => appender.with_compression(compression),
+            None => appender,
+        })
+    }
+}
+
+#[derive(Subcommand)]
+enum DbCommands {
+    /// Roll old snapshots up into hourly/daily buckets and delete the raw rows
+    Compact {
+        /// Database path [config: database.url]
+        #[arg(short, long)]
+        db: Option<String>,
+        /// TOML file with a [retention] table [config: retention]
+        #[arg(long)]
+        policy: Option<PathBuf>,
+        /// Report what would be compacted without changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Check for orphaned, duplicate and invalid rows and for missed runs
+    Doctor {
+        /// Database path [config: database.url]
+        #[arg(short, long)]
+        db: Option<String>,
+        /// Move bad and duplicate snapshots to snapshots_quarantine
+        #[arg(long)]
+        repair: bool,
+        /// Expected scrape interval for the gap check [config: schedule.interval]
+        #[arg(long, value_parser = parse_duration, conflicts_with = "cron")]
+        interval: Option<Duration>,
+        /// Expected cron schedule for the gap check [config: schedule.cron]
+        #[arg(long)]
+        cron: Option<String>,
+    },
+    /// Copy the database to a file; safe while the server and daemon are running
+    Backup {
+        /// Database path [config: database.url]
+        #[arg(short, long)]
+        db: Option<String>,
+        /// Backup file, or an existing directory to write a timestamped backup into [config: backup.dir]
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Compress the backup: none, gzip[:level] or zstd[:level] (default: from extension) [config: backup.compress]
+        #[arg(long)]
+        compress: Option<Compression>,
     },
+    /// Replace the database with a backup; stop the server and daemon first
+    Restore {
+        /// Backup file written by `db backup`
+        backup: PathBuf,
+        /// Database path [config: database.url]
+        #[arg(short, long)]
+        db: Option<String>,
+        /// Replace an existing database, keeping it as <name>.before-restore
+        #[arg(long)]
+        force: bool,
+        /// Decompress the backup: none, gzip or zstd (default: from extension)
+        #[arg(long)]
+        compress: Option<Compression>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommands {
+    /// Print the effective configuration after merging the file and environment
+    Show,
 }
 
+/// Exit status of `scrape` when some pages failed but the rest were saved.
+/// Full success is 0 and failure is 1.
+const EXIT_PARTIAL: u8 = 2;
+
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> Result<ExitCode> {
     // Initialize tracing
     tracing_subscriber::fmt::init();
     
     let cli = Cli::parse();
+    let config = Config::load(cli.config.as_deref())?;
 
     match cli.command {
-        Commands::Scrape { pages, db } => {
-            scrape_command(pages, &db).await?;
+        Commands::Scrape { pages, db, output, csv } => {
+            let fetch = FetchConfig { pages: pages.unwrap_or(config.fetch.pages), ..config.fetch.clone() };
+            return scrape_command(&fetch, &database(&config, db), csv.appender().as_ref(), output).await;
+        },
+        Commands::Daemon { pages, db, interval, cron, serve, port, max_backoff, compact_every, policy, backup_every, backup_dir, backup_keep, backup_compress, csv } => {
+            let schedule = schedule(&config, interval, cron)?;
+            let compaction = match compact_every.or(config.schedule.compact_every) {
+                Some(every) => Some((Schedule::interval(every)?, retention(&config, policy)?)),
+                None => None,
+            };
+            let backup = match backup_every.or(config.schedule.backup_every) {
+                Some(every) => Some((Schedule::interval(every)?, BackupConfig {
+                    dir: backup_dir.unwrap_or_else(|| config.backup.dir.clone()),
+                    compress: backup_compress.unwrap_or(config.backup.compress),
+                    keep: backup_keep.or(config.backup.keep),
+                })),
+                None => None,
+            };
+            let options = DaemonOptions {
+                schedule,
+                fetch: FetchConfig { pages: pages.unwrap_or(config.fetch.pages), ..config.fetch.clone() },
+                csv: csv.appender(),
+                backoff: Backoff { max: max_backoff.unwrap_or(config.schedule.max_backoff), ..Backoff::default() },
+                compaction,
+                backup,
+            };
+            let server = serve.then(|| server(&config, port));
+            daemon_command(&database(&config, db), options, server).await?;
         },
         Commands::Serve { port, db } => {
-            serve_command(port, &db).await?;
+            serve_command(&server(&config, port), &database(&config, db)).await?;
+        },
+        Commands::Import { files, db, mappings } => {
+            import_command(&files, &database(&config, db), &mappings).await?;
+        },
+        Commands::Backfill { db, interval, cron, since, until, archive, mappings } => {
+            let schedule = schedule(&config, interval, cron)?;
+            backfill_command(&database(&config, db), &schedule, since, until, &archive, &mappings).await?;
+        },
+        Commands::Export { db, format, output, compress, compression, row_group_size, partition_by_date, symbols, min_rank, max_rank, since, until, latest, source } => {
+            let db = database(&config, db);
+            let filter = SnapshotFilter {
+                symbols,
+                min_rank,
+                max_rank,
+                since,
+                until,
+                latest_only: latest,
+                source,
+            };
+            match format {
+                ExportFormat::Parquet => {
+                    let options = ParquetOptions { compression, row_group_size: row_group_size as usize, partition_by_date };
+                    parquet_command(&db, output, &filter, &options).await?;
+                }
+                ExportFormat::Xlsx => xlsx_command(&db, output, &filter).await?,
+                _ => export_command(&db, format, output, compress, &filter).await?,
+            }
+        },
+        Commands::Top { limit, db, format } => {
+            let db = Database::connect(&database(&config, db)).await?;
+            // The same listing as GET /v1/coins?limit=N
+            let query = CoinQuery { limit: limit.min(500), ..CoinQuery::default() };
+            let coins = db.list_coins(&query).await?.coins;
+            render(&coins, &coins, format, io::stdout().lock())?;
+        },
+        Commands::Coin { symbol, db, format } => {
+            let db = Database::connect(&database(&config, db)).await?;
+            let coin = db.get_coin_latest_api(&symbol).await?
+                .ok_or_else(|| anyhow::anyhow!("symbol not found: {}", symbol))?;
+            render(std::slice::from_ref(&coin), &coin, format, io::stdout().lock())?;
+        },
+        Commands::History { symbol, since, until, limit, db, format } => {
+            let db = Database::connect(&database(&config, db)).await?;
+            let history = HistoryResponse::new(&symbol, db.get_coin_history(&symbol, since, until, Some(limit)).await?);
+            render(&history.series, &history, format, io::stdout().lock())?;
+        },
+        Commands::Movers { window, direction, limit, min_market_cap, top, db, format } => {
+            let db = Database::connect(&database(&config, db)).await?;
+            let filter = MoversFilter { window, direction, limit, min_market_cap, top };
+            movers_command(&db.get_movers(&filter).await?, direction, format)?;
+        },
+        Commands::Runs { limit, db, format } => {
+            let db = Database::connect(&database(&config, db)).await?;
+            let runs = db.recent_runs(limit).await?;
+            render(&runs, &runs, format, io::stdout().lock())?;
+        },
+        Commands::Watch { limit, db, refresh, window } => {
+            let db = Database::connect(&database(&config, db)).await?;
+            watch(&db, &WatchOptions { limit, refresh, window }).await?;
+        },
+        Commands::Db { command } => match command {
+            DbCommands::Compact { db, policy, dry_run } => {
+                compact_command(&database(&config, db), retention(&config, policy)?, dry_run).await?;
+            },
+            DbCommands::Doctor { db, repair, interval, cron } => {
+                // The gap check is skipped rather than failing when no schedule is known
+                let schedule = if interval.is_some() || cron.is_some() || config.schedule.scrape_schedule()?.is_some() {
+                    Some(schedule(&config, interval, cron)?)
+                } else {
+                    None
+                };
+                return doctor_command(&database(&config, db), repair, schedule.as_ref()).await;
+            },
+            DbCommands::Backup { db, output, compress } => {
+                backup_command(&database(&config, db), &config.backup, output, compress).await?;
+            },
+            DbCommands::Restore { backup, db, force, compress } => {
+                let compression = compress.unwrap_or_else(|| Compression::from_path(&backup));
+                restore_command(&backup, compression, &database(&config, db), force).await?;
+            },
+        },
+        Commands::Config { command } => match command {
+            ConfigCommands::Show => print!("{}", config.to_toml()?),
         },
     }
 
-    Ok(())
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Database settings from the config, with the URL overridden by `--db`.
+fn database(config: &Config, url: Option<String>) -> DatabaseConfig {
+    DatabaseConfig {
+        url: url.unwrap_or_else(|| config.database.url.clone()),
+        ..config.database.clone()
+    }
+}
+
+/// Server settings from the config, with the port overridden by `--port`.
+fn server(config: &Config, port: Option<u16>) -> ServerConfig {
+    let mut server = config.server.clone();
+    if let Some(port) = port {
+        server.bind.set_port(port);
+    }
+    server
 }
 
-async fn scrape_command(pages: u32, db_url: &str) -> Result<()> {
+/// Scrape schedule from `--interval` or `--cron`, or the config's `[schedule]`.
+fn schedule(config: &Config, interval: Option<Duration>, cron: Option<String>) -> Result<Schedule> {
+    match (interval, cron) {
+        (Some(every), _) => Schedule::interval(every),
+        (None, Some(expr)) => Schedule::cron(&expr),
+        (None, None) => config.schedule.scrape_schedule()?
+            .ok_or_else(|| anyhow::anyhow!("no schedule: pass --interval or --cron, or set schedule.interval or schedule.cron")),
+    }
+}
+
+/// Retention policy from `--policy`, or the config's `[retention]` table.
+fn retention(config: &Config, policy: Option<PathBuf>) -> Result<RetentionPolicy> {
+    match policy {
+        Some(path) => RetentionPolicy::from_file(path),
+        None => Ok(config.retention.clone()),
+    }
+}
+
+async fn scrape_command(fetch: &FetchConfig, db_config: &DatabaseConfig, csv: Option<&CsvAppender>, output: OutputMode) -> Result<ExitCode> {
+    let human = output == OutputMode::Human;
+
     // Initialize database
-    println!("=== Database Setup ===");
-    let db = Database::new(db_url).await?;
+    if human {
+        println!("=== Database Setup ===");
+    }
+    let db = Database::connect(db_config).await?;
     
     // Scrape data concurrently
-    println!("\n=== Scraping Data ===");
-    println!("Scraping {} pages concurrently...", pages);
-    let start = Instant::now();
-    let rows = scrape_coins_concurrent(pages, 10, 300).await?;
-    let scrape_duration = start.elapsed();
-    
-    println!("Scraped {} coins in {:.2}s", rows.len(), scrape_duration.as_secs_f64());
+    if human {
+        println!("\n=== Scraping Data ===");
+        println!("Scraping {} pages concurrently...", fetch.pages);
+    }
+    let summary = run_scrape(&db, fetch, csv).await?;
+
+    match output {
+        OutputMode::Human => print_scrape_report(&db, &summary).await?,
+        OutputMode::Json => println!("{}", serde_json::to_string_pretty(&summary)?),
+        OutputMode::Quiet => {
+            for err in &summary.errors {
+                eprintln!("{}", err);
+            }
+        }
+    }
+
+    Ok(match summary.run.status {
+        RunStatus::Ok => ExitCode::SUCCESS,
+        RunStatus::Partial => ExitCode::from(EXIT_PARTIAL),
+        RunStatus::Running | RunStatus::Failed => ExitCode::FAILURE,
+    })
+}
+
+async fn print_scrape_report(db: &Database, summary: &ScrapeSummary) -> Result<()> {
+    println!("Scraped {} coins in {:.2}s", summary.coins_scraped, summary.scrape_secs);
+    if summary.run.pages_failed > 0 {
+        println!("{} of {} pages failed", summary.run.pages_failed, summary.run.pages_requested);
+    }
     
     // Save to database
     println!("\n=== Database Storage ===");
-    let start = Instant::now();
-    db.save_coins(&rows).await?;
-    let db_duration = start.elapsed();
-    
-    println!("Saved {} coins to database in {:.3}s", rows.len(), db_duration.as_secs_f64());
+    println!("Saved {} coins to database in {:.3}s", summary.run.coins_saved, summary.save_secs);
+
+    if let Some(csv) = &summary.csv {
+        if let Some(rotated) = &csv.outcome.rotated_to {
+            println!("Rotated previous CSV to {}", rotated.display());
+        }
+        for removed in &csv.outcome.removed {
+            println!("Removed old CSV {}", removed.display());
+        }
+        println!("Appended {} rows to {}", csv.outcome.rows_written, csv.path.display());
+    }
     
     // Get database stats
     let total_snapshots = db.get_snapshot_count().await?;
     println!("Total snapshots in database: {}", total_snapshots);
+
+    println!("\n=== Run #{}: {} ===", summary.run.id, summary.run.status);
+    for err in &summary.errors {
+        println!("  {}", err);
+    }
+    if summary.run.coins_saved == 0 {
+        return Ok(());
+    }
     
     // Demo: Show latest top 10 from database
     println!("\n=== Latest Top 10 from Database ===");
@@ -92,7 +635,7 @@ async fn scrape_command(pages: u32, db_url: &str) -> Result<()> {
     
     // Demo: Show BTC history if available
     println!("\n=== BTC Price History ===");
-    let btc_history = db.get_coin_history("BTC").await?;
+    let btc_history = db.get_coin_history("BTC", None, None, None).await?;
     for point in btc_history.iter().take(5) {
         println!("{}: ${:.2}", 
                  point.timestamp.format("%Y-%m-%d %H:%M:%S UTC"),
@@ -105,12 +648,322 @@ async fn scrape_command(pages: u32, db_url: &str) -> Result<()> {
     Ok(())
 }
 
-async fn serve_command(port: u16, db_url: &str) -> Result<()> {
+fn movers_command(movers: &MoversResponse, direction: Option<MoverDirection>, format: RenderFormat) -> Result<()> {
+    let mut out = io::stdout().lock();
+    if format != RenderFormat::Table {
+        let rows: Vec<(&str, &Mover)> = movers.gainers.iter().map(|c| ("gainer", c))
+            .chain(movers.losers.iter().map(|c| ("loser", c)))
+            .collect();
+        return render(&rows, movers, format, out);
+    }
+
+    if direction != Some(MoverDirection::Down) {
+        writeln!(out, "=== Gainers ({}) ===", movers.window)?;
+        render(&movers.gainers, &movers.gainers, format, &mut out)?;
+    }
+    if direction.is_none() {
+        writeln!(out)?;
+    }
+    if direction != Some(MoverDirection::Up) {
+        writeln!(out, "=== Losers ({}) ===", movers.window)?;
+        render(&movers.losers, &movers.losers, format, &mut out)?;
+    }
+    Ok(())
+}
+
+async fn daemon_command(db_config: &DatabaseConfig, options: DaemonOptions, server: Option<ServerConfig>) -> Result<()> {
+    let db = Arc::new(Database::connect(db_config).await?);
+    let daemon = Daemon::new(db.clone(), options);
+
+    // The server shares the daemon's connection pool and stops with it
+    let server = async {
+        match &server {
+            Some(server) => serve(db, server).await,
+            None => std::future::pending().await,
+        }
+    };
+    tokio::select! {
+        result = daemon.run() => result,
+        result = server => result,
+    }
+}
+
+async fn serve_command(server: &ServerConfig, db_config: &DatabaseConfig) -> Result<()> {
     println!("=== Starting API Server ===");
-    let db = Database::new(db_url).await?;
+    let db = Database::connect(db_config).await?;
     
     println!("Database connected. Starting server...");
-    start_server(db, port).await?;
+    serve(Arc::new(db), server).await?;
     
     Ok(())
 }
+
+async fn import_command(files: &[PathBuf], db_config: &DatabaseConfig, mappings: &[String]) -> Result<()> {
+    let mapping = ColumnMapping::from_overrides(mappings)?;
+    let db = Database::connect(db_config).await?;
+
+    println!("=== Importing {} file(s) ===", files.len());
+    let start = Instant::now();
+    let report = import_csv_files(&db, files, &mapping).await?;
+
+    println!("Read {} rows from {} file(s) in {:.2}s", report.rows_read, report.files, start.elapsed().as_secs_f64());
+    println!("Inserted {} snapshots across {} scrape runs", report.inserted, report.runs);
+    println!("Skipped {} snapshots already in the database", report.skipped);
+    println!("Rejected {} invalid rows", report.invalid);
+    for err in &report.errors {
+        println!("  {}", err);
+    }
+    if report.invalid > report.errors.len() as u64 {
+        println!("  ... and {} more", report.invalid - report.errors.len() as u64);
+    }
+
+    Ok(())
+}
+
+async fn backfill_command(db_config: &DatabaseConfig, schedule: &Schedule, since: Option<DateTime<Utc>>, until: Option<DateTime<Utc>>, archives: &[PathBuf], mappings: &[String]) -> Result<()> {
+    let mapping = ColumnMapping::from_overrides(mappings)?;
+    let files = backfill::archive_files(archives)?;
+    let db = Database::connect(db_config).await?;
+
+    let until = until.unwrap_or_else(Utc::now);
+    let since = match since {
+        Some(since) => since,
+        None => {
+            let week_ago = until - chrono::TimeDelta::days(7);
+            db.earliest_snapshot_at().await?.map_or(week_ago, |first| first.max(week_ago))
+        }
+    };
+
+    println!("=== Checking Schedule ===");
+    println!("Expected a scrape {} from {} to {}", schedule, since.to_rfc3339_opts(SecondsFormat::Secs, true), until.to_rfc3339_opts(SecondsFormat::Secs, true));
+    let gaps = find_gaps(&db, schedule, since, until).await?;
+    let missed: u64 = gaps.iter().map(|gap| gap.missed_slots).sum();
+    println!("Found {} gap(s) covering {} missed run(s)", gaps.len(), missed);
+    print_gaps(&gaps);
+    if gaps.is_empty() {
+        return Ok(());
+    }
+    if files.is_empty() {
+        println!("\nPass --archive to fill them from CSV archives");
+        return Ok(());
+    }
+
+    println!("\n=== Backfilling from {} file(s) ===", files.len());
+    let start = Instant::now();
+    let report = backfill(&db, &gaps, &files, &mapping).await?;
+    println!("Read {} rows in {:.2}s, {} of them outside the gaps", report.rows_read, start.elapsed().as_secs_f64(), report.out_of_range);
+    println!("Inserted {} snapshots across {} scrape runs", report.inserted, report.runs);
+    println!("Skipped {} snapshots already in the database", report.skipped);
+    println!("Rejected {} invalid rows", report.invalid);
+    for err in &report.errors {
+        println!("  {}", err);
+    }
+
+    let remaining = find_gaps(&db, schedule, since, until).await?;
+    let still_missed: u64 = remaining.iter().map(|gap| gap.missed_slots).sum();
+    println!("\n=== Remaining Gaps ===");
+    println!("Filled {} of {} missed run(s), {} gap(s) remain", missed - still_missed, missed, remaining.len());
+    print_gaps(&remaining);
+
+    Ok(())
+}
+
+fn print_gaps(gaps: &[Gap]) {
+    const SHOWN: usize = 20;
+    for gap in gaps.iter().take(SHOWN) {
+        println!("  {} to {} ({} missed)", gap.start.to_rfc3339_opts(SecondsFormat::Secs, true), gap.end.to_rfc3339_opts(SecondsFormat::Secs, true), gap.missed_slots);
+    }
+    if gaps.len() > SHOWN {
+        println!("  ... and {} more", gaps.len() - SHOWN);
+    }
+}
+
+async fn export_command(db_config: &DatabaseConfig, format: ExportFormat, output: Option<PathBuf>, compress: Option<Compression>, filter: &SnapshotFilter) -> Result<()> {
+    let db = Database::connect(db_config).await?;
+
+    // Progress goes to stderr so stdout stays clean for piping
+    let start = Instant::now();
+    let count = match output.filter(|path| path.as_os_str() != "-") {
+        Some(path) => {
+            let mut file = AtomicFile::create(&path, compress.unwrap_or_else(|| Compression::from_path(&path)))?;
+            let count = export_snapshots(&db, filter, format, &mut file).await?;
+            file.commit()?;
+            eprintln!("Exported {} snapshots to {} in {:.2}s", count, path.display(), start.elapsed().as_secs_f64());
+            count
+        }
+        None => {
+            if compress.is_some_and(|c| c != Compression::None) {
+                anyhow::bail!("--compress needs --output");
+            }
+            let count = export_snapshots(&db, filter, format, BufWriter::new(io::stdout().lock())).await?;
+            eprintln!("Exported {} snapshots in {:.2}s", count, start.elapsed().as_secs_f64());
+            count
+        }
+    };
+    if count == 0 {
+        eprintln!("No snapshots matched the filters");
+    }
+
+    Ok(())
+}
+
+async fn parquet_command(db_config: &DatabaseConfig, output: Option<PathBuf>, filter: &SnapshotFilter, options: &ParquetOptions) -> Result<()> {
+    let Some(path) = output.filter(|path| path.as_os_str() != "-") else {
+        anyhow::bail!("parquet export needs --output (a file, or a directory with --partition-by-date)");
+    };
+    let db = Database::connect(db_config).await?;
+
+    let start = Instant::now();
+    let export = export_parquet(&db, filter, &path, options).await?;
+    eprintln!("Exported {} snapshots to {} Parquet file(s) under {} in {:.2}s",
+              export.rows, export.files.len(), path.display(), start.elapsed().as_secs_f64());
+
+    Ok(())
+}
+
+async fn xlsx_command(db_config: &DatabaseConfig, output: Option<PathBuf>, filter: &SnapshotFilter) -> Result<()> {
+    let Some(path) = output.filter(|path| path.as_os_str() != "-") else {
+        anyhow::bail!("xlsx export needs --output");
+    };
+    let db = Database::connect(db_config).await?;
+
+    let start = Instant::now();
+    let export = export_xlsx(&db, filter, &path).await?;
+    eprintln!("Exported {} latest and {} history rows to {} ({}) in {:.2}s",
+              export.latest_rows, export.history_rows, path.display(), export.sheets.join(", "), start.elapsed().as_secs_f64());
+
+    Ok(())
+}
+
+async fn compact_command(db_config: &DatabaseConfig, policy: RetentionPolicy, dry_run: bool) -> Result<()> {
+    let db = Database::connect(db_config).await?;
+
+    println!("=== Compacting Database{} ===", if dry_run { " (dry run)" } else { "" });
+    println!("Keeping raw snapshots for {} days, hourly rollups for {} days, daily rollups {}",
+             policy.raw_days,
+             policy.hourly_days,
+             policy.daily_days.map(|d| format!("for {} days", d)).unwrap_or_else(|| "forever".to_string()));
+
+    let start = Instant::now();
+    let report = db.compact(&policy, Utc::now(), dry_run).await?;
+
+    println!("Rolled {} raw snapshots into {} hourly buckets", report.raw_rows_rolled_up, report.hourly_buckets_written);
+    println!("Rolled {} hourly buckets into {} daily buckets", report.hourly_rows_rolled_up, report.daily_buckets_written);
+    if policy.daily_days.is_some() {
+        println!("Deleted {} expired daily buckets", report.daily_rows_deleted);
+    }
+    println!("Done in {:.3}s", start.elapsed().as_secs_f64());
+
+    Ok(())
+}
+
+async fn doctor_command(db_config: &DatabaseConfig, repair: bool, schedule: Option<&Schedule>) -> Result<ExitCode> {
+    let db = Database::connect(db_config).await?;
+
+    println!("=== Database Doctor ===");
+    let mut findings = db.check_integrity().await?;
+    print_findings(&findings);
+
+    println!("\n=== Gaps ===");
+    match schedule {
+        Some(schedule) => {
+            let until = Utc::now();
+            let week_ago = until - chrono::TimeDelta::days(7);
+            let since = db.earliest_snapshot_at().await?.map_or(week_ago, |first| first.max(week_ago));
+            let gaps = find_gaps(&db, schedule, since, until).await?;
+            let missed: u64 = gaps.iter().map(|gap| gap.missed_slots).sum();
+            println!("Found {} gap(s) covering {} missed run(s) in the last 7 days ({})", gaps.len(), missed, schedule);
+            print_gaps(&gaps);
+            if !gaps.is_empty() {
+                println!("Run `backfill` to fill them from CSV archives");
+            }
+        }
+        None => println!("Skipped: pass --interval or --cron, or set schedule.interval or schedule.cron"),
+    }
+
+    if repair && findings.iter().any(|f| f.repairable) {
+        println!("\n=== Repair ===");
+        let report = db.repair().await?;
+        for (reason, rows) in &report.quarantined {
+            println!("Quarantined {} snapshots: {}", rows, reason);
+        }
+        findings = db.check_integrity().await?;
+        if !findings.is_empty() {
+            println!("Still needs manual repair:");
+            print_findings(&findings);
+        }
+    }
+
+    // Gaps are reported but don't fail the check; only bad rows do
+    Ok(if findings.is_empty() { ExitCode::SUCCESS } else { ExitCode::FAILURE })
+}
+
+async fn backup_command(db_config: &DatabaseConfig, backup: &BackupConfig, output: Option<PathBuf>, compress: Option<Compression>) -> Result<()> {
+    let db = Database::connect(db_config).await?;
+
+    // A file path picks its compression from the extension; directories use the config's
+    let output = output.unwrap_or_else(|| backup.dir.clone());
+    let (path, compression) = if output.is_dir() || output == backup.dir {
+        std::fs::create_dir_all(&output)?;
+        let compression = compress.unwrap_or(backup.compress);
+        (output.join(backup_filename(Utc::now(), compression)), compression)
+    } else {
+        let compression = compress.unwrap_or_else(|| Compression::from_path(&output));
+        (output, compression)
+    };
+
+    println!("=== Backing Up Database ===");
+    let start = Instant::now();
+    let report = db.backup(&path, compression).await?;
+    println!("Wrote {} ({} bytes, compression: {}, schema version {})",
+             report.path.display(),
+             report.bytes,
+             compression,
+             report.schema_version.unwrap_or_default());
+    println!("Done in {:.3}s", start.elapsed().as_secs_f64());
+
+    Ok(())
+}
+
+async fn restore_command(backup: &Path, compression: Compression, db_config: &DatabaseConfig, force: bool) -> Result<()> {
+    println!("=== Restoring Database ===");
+    println!("Restoring {} into {}", backup.display(), db_config.url);
+    let report = restore(backup, compression, &db_config.url, force).await?;
+
+    match (report.backup_version, report.schema_version) {
+        (Some(from), Some(to)) if from != to => println!("Migrated backup from schema version {} to {}", from, to),
+        (_, version) => println!("Schema version {}", version.unwrap_or_default()),
+    }
+    if let Some(previous) = &report.previous {
+        println!("Previous database kept as {}", previous.display());
+    }
+
+    Ok(())
+}
+
+fn print_findings(findings: &[Finding]) {
+    if findings.is_empty() {
+        println!("No problems found");
+        return;
+    }
+    for finding in findings {
+        let examples: Vec<String> = finding.examples.iter().map(i64::to_string).collect();
+        println!("{}: {} ({} rows{}{})",
+                 finding.table,
+                 finding.problem,
+                 finding.rows,
+                 if examples.is_empty() { String::new() } else { format!(", e.g. rowid {}", examples.join(", ")) },
+                 if finding.repairable { "" } else { ", not repairable" });
+    }
+}
+
+/// Parse an RFC 3339 timestamp, or a bare date taken as midnight UTC.
+fn parse_time(value: &str) -> Result<DateTime<Utc>> {
+    if let Ok(ts) = DateTime::parse_from_rfc3339(value) {
+        return Ok(ts.with_timezone(&Utc));
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| anyhow::anyhow!("expected an RFC 3339 timestamp or YYYY-MM-DD, got '{}'", value))?;
+    Ok(date.and_hms_opt(0, 0, 0).unwrap().and_utc())
+}
+
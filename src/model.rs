@@ -1,5 +1,8 @@
 use chrono::{DateTime, Utc};
 use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::database::HistoryPoint;
 
 #[derive(Debug, Clone, Serialize)]
 pub struct CoinRow {
@@ -13,8 +16,15 @@ pub struct CoinRow {
     pub scraped_at: DateTime<Utc>,
 }
 
+impl CoinRow {
+    /// Column names in the order `CoinRow` serializes, as used for CSV headers.
+    pub const FIELDS: [&'static str; 8] = [
+        "id", "rank", "name", "symbol", "price_usd", "market_cap_usd", "chg24h_pct", "scraped_at",
+    ];
+}
+
 // API Response DTOs
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize, ToSchema)]
 pub struct CoinResponse {
     pub id: u64,
     pub symbol: String,
@@ -26,13 +36,164 @@ pub struct CoinResponse {
     pub ts_utc: DateTime<Utc>,
 }
 
-#[derive(Debug, Serialize)]
+impl From<&CoinRow> for CoinResponse {
+    /// The coin as the API returns it once saved, with the timestamp cut to
+    /// the database's millisecond precision.
+    fn from(row: &CoinRow) -> Self {
+        Self {
+            id: row.id,
+            symbol: row.symbol.clone(),
+            name: row.name.clone(),
+            rank: row.rank,
+            price_usd: row.price_usd,
+            market_cap_usd: row.market_cap_usd,
+            change_24h: row.chg24h_pct,
+            ts_utc: DateTime::from_timestamp_millis(row.scraped_at.timestamp_millis()).unwrap_or(row.scraped_at),
+        }
+    }
+}
+
+/// A scrape run that was just saved, as published to stream subscribers.
+#[derive(Debug, Clone)]
+pub struct ScrapeUpdate {
+    /// `None` when the run was found in the database rather than saved here
+    pub run_id: Option<i64>,
+    pub ts_utc: DateTime<Utc>,
+    /// Ranked coins of the run, by rank
+    pub coins: Vec<CoinResponse>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
 pub struct HistoryResponse {
     pub symbol: String,
+    /// `[timestamp, price_usd]` pairs, oldest first
     pub series: Vec<(DateTime<Utc>, Option<f64>)>,
 }
 
-#[derive(Debug, Serialize)]
+impl HistoryResponse {
+    pub fn new(symbol: &str, history: Vec<HistoryPoint>) -> Self {
+        Self {
+            symbol: symbol.to_uppercase(),
+            series: history.into_iter().map(|point| (point.timestamp, point.price_usd)).collect(),
+        }
+    }
+}
+
+/// A coin of the latest scrape with its price change over a movers window.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Mover {
+    pub id: u64,
+    pub symbol: String,
+    pub name: String,
+    pub rank: Option<u64>,
+    pub price_usd: Option<f64>,
+    pub market_cap_usd: Option<f64>,
+    pub change_24h: Option<f64>,
+    pub ts_utc: DateTime<Utc>,
+    /// Price change over the window, in percent
+    pub change_pct: f64,
+    /// Stored price the change is measured from; null when `change_pct` is
+    /// the scraped `change_24h` because no snapshot is old enough
+    pub base_price_usd: Option<f64>,
+    pub base_ts_utc: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct MoversResponse {
+    /// Window the changes are measured over: `1h`, `24h` or `7d`
+    pub window: String,
+    /// Biggest risers first; empty when only losers were asked for
+    pub gainers: Vec<Mover>,
+    /// Biggest fallers first; empty when only gainers were asked for
+    pub losers: Vec<Mover>,
+}
+
+/// Latest snapshots of several coins at once.
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct LatestCoinsResponse {
+    /// Found coins, by rank
+    pub coins: Vec<CoinResponse>,
+    /// Requested symbols that match no coin
+    pub unresolved: Vec<String>,
+    /// Requested ids that match no coin
+    pub unresolved_ids: Vec<u64>,
+}
+
+/// Whole-market figures of one scrape.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct MarketSummary {
+    pub ts_utc: DateTime<Utc>,
+    /// Sum of the market caps of every coin in the scrape
+    pub total_market_cap_usd: Option<f64>,
+    /// Change of the total market cap over 24h in percent, implied by each
+    /// coin's `change_24h`
+    pub change_24h_pct: Option<f64>,
+    /// Coins in the scrape
+    pub coins: u64,
+    /// Bitcoin's share of the total market cap, in percent
+    pub btc_dominance_pct: Option<f64>,
+    /// Ethereum's share of the total market cap, in percent
+    pub eth_dominance_pct: Option<f64>,
+    /// Stablecoins' share of the total market cap, in percent
+    pub stablecoin_share_pct: Option<f64>,
+    /// Coins up over 24h
+    pub advancers: u64,
+    /// Coins down over 24h
+    pub decliners: u64,
+    /// Coins flat over 24h
+    pub unchanged: u64,
+}
+
+/// Price series of several coins on a common time grid.
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct CompareResponse {
+    /// Bucket width, e.g. `1d`
+    pub interval: String,
+    /// What the values are: `price`, `normalized`, `returns` or `ratio`
+    pub mode: String,
+    /// Coin the ratios are against, in `ratio` mode
+    pub base: Option<String>,
+    /// Start of each bucket; every series has one value per bucket
+    pub timestamps: Vec<DateTime<Utc>>,
+    pub series: Vec<CompareSeries>,
+    /// Requested symbols that match no coin
+    pub unresolved: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct CompareSeries {
+    pub symbol: String,
+    /// One value per bucket; null where there was no data
+    pub values: Vec<Option<f64>>,
+}
+
+/// How the top of the ranking changed between two scrapes.
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct CoinsDiff {
+    /// Scrape compared from; null when there was none by then
+    pub from_ts_utc: Option<DateTime<Utc>>,
+    /// Scrape compared to; null when there was none by then
+    pub to_ts_utc: Option<DateTime<Utc>>,
+    /// Coins in the later top N but not the earlier, as of the later scrape
+    pub entries: Vec<CoinResponse>,
+    /// Coins in the earlier top N but not the later, as of the earlier scrape
+    pub exits: Vec<CoinResponse>,
+    /// Coins in both whose rank changed, by later rank
+    pub rank_changes: Vec<RankChange>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RankChange {
+    pub id: u64,
+    pub symbol: String,
+    pub name: String,
+    pub from_rank: u64,
+    pub to_rank: u64,
+    /// Places gained; negative when the coin fell
+    pub change: i64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
 pub struct HealthResponse {
     pub ok: bool,
 }
\ No newline at end of file
@@ -0,0 +1,184 @@
+use anyhow::{bail, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use csv::WriterBuilder;
+use serde::Serialize;
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+
+use crate::database::ScrapeRun;
+use crate::model::{CoinResponse, Mover};
+
+/// How the query subcommands print their results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderFormat {
+    /// Aligned columns for terminals
+    Table,
+    /// The same JSON body the API returns
+    Json,
+    Csv,
+}
+
+impl FromStr for RenderFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "table" => Ok(Self::Table),
+            "json" => Ok(Self::Json),
+            "csv" => Ok(Self::Csv),
+            other => bail!("unknown format '{}', expected table, json or csv", other),
+        }
+    }
+}
+
+impl fmt::Display for RenderFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Table => "table",
+            Self::Json => "json",
+            Self::Csv => "csv",
+        })
+    }
+}
+
+/// A value that renders as one table or CSV row. Headers double as CSV
+/// column names and match the JSON field names.
+pub trait Tabular {
+    const HEADERS: &'static [&'static str];
+
+    fn cells(&self) -> Vec<String>;
+}
+
+/// Write `rows` as a table or CSV, or `json` as pretty-printed JSON. `json`
+/// is passed separately so it can keep the exact shape of the API response.
+pub fn render<T: Tabular, J: Serialize + ?Sized, W: Write>(rows: &[T], json: &J, format: RenderFormat, mut out: W) -> Result<()> {
+    match format {
+        RenderFormat::Json => {
+            serde_json::to_writer_pretty(&mut out, json)?;
+            writeln!(out)?;
+        }
+        RenderFormat::Csv => {
+            let mut wtr = WriterBuilder::new().from_writer(&mut out);
+            wtr.write_record(T::HEADERS)?;
+            for row in rows {
+                wtr.write_record(row.cells())?;
+            }
+            wtr.flush()?;
+        }
+        RenderFormat::Table => write_table(rows, &mut out)?,
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Numeric columns are right-aligned, everything else left-aligned.
+fn write_table<T: Tabular, W: Write>(rows: &[T], out: &mut W) -> Result<()> {
+    let headers: Vec<String> = T::HEADERS.iter().map(|h| h.to_uppercase()).collect();
+    let cells: Vec<Vec<String>> = rows.iter().map(Tabular::cells).collect();
+
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.chars().count()).collect();
+    let mut numeric = vec![true; headers.len()];
+    for row in &cells {
+        for (i, cell) in row.iter().enumerate() {
+            widths[i] = widths[i].max(cell.chars().count());
+            numeric[i] &= cell.is_empty() || cell.parse::<f64>().is_ok();
+        }
+    }
+
+    let line = |values: &[String]| -> String {
+        let padded: Vec<String> = values.iter().enumerate().map(|(i, v)| {
+            if numeric[i] {
+                format!("{:>width$}", v, width = widths[i])
+            } else {
+                format!("{:<width$}", v, width = widths[i])
+            }
+        }).collect();
+        padded.join("  ").trim_end().to_string()
+    };
+
+    writeln!(out, "{}", line(&headers))?;
+    for row in &cells {
+        writeln!(out, "{}", line(row))?;
+    }
+    Ok(())
+}
+
+fn opt<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+fn timestamp(ts: DateTime<Utc>) -> String {
+    ts.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}
+
+impl Tabular for CoinResponse {
+    const HEADERS: &'static [&'static str] = &["rank", "symbol", "name", "price_usd", "market_cap_usd", "change_24h", "ts_utc"];
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            opt(self.rank),
+            self.symbol.clone(),
+            self.name.clone(),
+            opt(self.price_usd),
+            opt(self.market_cap_usd),
+            opt(self.change_24h),
+            timestamp(self.ts_utc),
+        ]
+    }
+}
+
+/// One point of a `HistoryResponse` series.
+impl Tabular for (DateTime<Utc>, Option<f64>) {
+    const HEADERS: &'static [&'static str] = &["ts_utc", "price_usd"];
+
+    fn cells(&self) -> Vec<String> {
+        vec![timestamp(self.0), opt(self.1)]
+    }
+}
+
+impl Tabular for Mover {
+    const HEADERS: &'static [&'static str] = &["rank", "symbol", "name", "price_usd", "change_pct", "base_price_usd", "base_ts_utc", "market_cap_usd", "ts_utc"];
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            opt(self.rank),
+            self.symbol.clone(),
+            self.name.clone(),
+            opt(self.price_usd),
+            format!("{:.2}", self.change_pct),
+            opt(self.base_price_usd),
+            opt(self.base_ts_utc.map(timestamp)),
+            opt(self.market_cap_usd),
+            timestamp(self.ts_utc),
+        ]
+    }
+}
+
+/// A mover tagged with the list it came from, gainers or losers.
+impl Tabular for (&str, &Mover) {
+    const HEADERS: &'static [&'static str] = &["side", "rank", "symbol", "name", "price_usd", "change_pct", "base_price_usd", "base_ts_utc", "market_cap_usd", "ts_utc"];
+
+    fn cells(&self) -> Vec<String> {
+        let mut cells = vec![self.0.to_string()];
+        cells.extend(self.1.cells());
+        cells
+    }
+}
+
+impl Tabular for ScrapeRun {
+    const HEADERS: &'static [&'static str] = &["run_id", "started_at", "finished_at", "status", "pages_requested", "pages_failed", "coins_saved", "error"];
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.id.to_string(),
+            timestamp(self.started_at),
+            opt(self.finished_at.map(timestamp)),
+            self.status.to_string(),
+            self.pages_requested.to_string(),
+            self.pages_failed.to_string(),
+            self.coins_saved.to_string(),
+            self.error.clone().unwrap_or_default(),
+        ]
+    }
+}
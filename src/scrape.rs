@@ -0,0 +1,109 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::path::PathBuf;
+use std::time::Instant;
+
+use crate::config::FetchConfig;
+use crate::database::{Database, RunStatus, ScrapeRun};
+use crate::export::append::{AppendOutcome, CsvAppender};
+use crate::fetch::scrape_pages;
+use crate::model::{CoinResponse, ScrapeUpdate};
+
+/// What a scrape run did, as recorded in `scrape_runs` plus timings and
+/// every error encountered.
+#[derive(Debug, Serialize)]
+pub struct ScrapeSummary {
+    #[serde(flatten)]
+    pub run: ScrapeRun,
+    pub coins_scraped: usize,
+    pub scrape_secs: f64,
+    pub save_secs: f64,
+    pub csv: Option<CsvSummary>,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CsvSummary {
+    pub path: PathBuf,
+    #[serde(flatten)]
+    pub outcome: AppendOutcome,
+}
+
+/// Scrape, save and optionally append to CSV, recording the run in
+/// `scrape_runs`. Page, save and CSV failures end up in the summary's status
+/// and errors; only failing to record the run itself is returned as an error.
+pub async fn run_scrape(db: &Database, fetch: &FetchConfig, csv: Option<&CsvAppender>) -> Result<ScrapeSummary> {
+    let started_at = Utc::now();
+    let id = db.begin_run(started_at, fetch.pages).await?;
+
+    let start = Instant::now();
+    let outcome = scrape_pages(fetch.pages, fetch.batch_size, fetch.pause_ms).await;
+    let scrape_secs = start.elapsed().as_secs_f64();
+
+    let mut errors: Vec<String> = outcome.errors.iter().map(ToString::to_string).collect();
+    let mut run = ScrapeRun {
+        id,
+        started_at,
+        finished_at: None,
+        ts_utc: None,
+        status: RunStatus::Running,
+        pages_requested: fetch.pages,
+        pages_failed: outcome.errors.len() as u32,
+        coins_saved: 0,
+        error: None,
+    };
+
+    let mut save_secs = 0.0;
+    let mut csv_summary = None;
+    if outcome.rows.is_empty() {
+        if errors.is_empty() {
+            errors.push("no coins found".to_string());
+        }
+    } else {
+        let start = Instant::now();
+        match db.save_coins(&outcome.rows).await {
+            Ok(()) => {
+                run.coins_saved = outcome.rows.len() as u64;
+                run.ts_utc = Some(outcome.scraped_at);
+            }
+            Err(err) => errors.push(format!("failed to save snapshots: {:#}", err)),
+        }
+        save_secs = start.elapsed().as_secs_f64();
+
+        if let Some(csv) = csv.filter(|_| run.coins_saved > 0) {
+            match csv.append(&outcome.rows) {
+                Ok(outcome) => csv_summary = Some(CsvSummary { path: csv.path().to_path_buf(), outcome }),
+                Err(err) => errors.push(format!("failed to append to {}: {:#}", csv.path().display(), err)),
+            }
+        }
+    }
+
+    run.status = match (run.coins_saved, errors.is_empty()) {
+        (0, _) => RunStatus::Failed,
+        (_, true) => RunStatus::Ok,
+        (_, false) => RunStatus::Partial,
+    };
+    run.error = errors.first().cloned();
+    run.finished_at = Some(Utc::now());
+    db.finish_run(&run).await?;
+
+    if run.coins_saved > 0 {
+        let mut coins: Vec<CoinResponse> = outcome.rows.iter().filter(|row| row.rank.is_some()).map(CoinResponse::from).collect();
+        coins.sort_by_key(|coin| coin.rank);
+        db.publish(ScrapeUpdate {
+            run_id: Some(run.id),
+            ts_utc: DateTime::from_timestamp_millis(outcome.scraped_at.timestamp_millis()).unwrap_or(outcome.scraped_at),
+            coins,
+        });
+    }
+
+    Ok(ScrapeSummary {
+        run,
+        coins_scraped: outcome.rows.len(),
+        scrape_secs,
+        save_secs,
+        csv: csv_summary,
+        errors,
+    })
+}
@@ -0,0 +1,191 @@
+use chrono::{DateTime, Utc};
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::widgets::TableState;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+use crate::model::CoinResponse;
+
+/// Everything the dashboard shows, loaded in one go from the database.
+#[derive(Debug, Default)]
+pub struct Snapshot {
+    pub coins: Vec<CoinResponse>,
+    /// Coin id to rank in the previous scrape
+    pub previous_ranks: HashMap<u64, u64>,
+    /// Coin ids among the biggest 24h gainers and losers
+    pub movers: HashSet<u64>,
+    /// Coin id to recent prices, oldest first
+    pub history: HashMap<u64, Vec<f64>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    Rank,
+    Change,
+    Price,
+    MarketCap,
+    Symbol,
+}
+
+impl SortKey {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Rank => "rank",
+            Self::Change => "24h %",
+            Self::Price => "price",
+            Self::MarketCap => "market cap",
+            Self::Symbol => "symbol",
+        }
+    }
+
+    fn next(self) -> Self {
+        match self {
+            Self::Rank => Self::Change,
+            Self::Change => Self::Price,
+            Self::Price => Self::MarketCap,
+            Self::MarketCap => Self::Symbol,
+            Self::Symbol => Self::Rank,
+        }
+    }
+
+    /// Ascending order; coins missing the value sort last.
+    fn compare(&self, a: &CoinResponse, b: &CoinResponse) -> Ordering {
+        fn missing_last<T: PartialOrd>(a: Option<T>, b: Option<T>) -> Ordering {
+            match (a, b) {
+                (Some(a), Some(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            }
+        }
+        match self {
+            Self::Rank => missing_last(a.rank, b.rank),
+            Self::Change => missing_last(a.change_24h, b.change_24h),
+            Self::Price => missing_last(a.price_usd, b.price_usd),
+            Self::MarketCap => missing_last(a.market_cap_usd, b.market_cap_usd),
+            Self::Symbol => a.symbol.cmp(&b.symbol),
+        }
+    }
+}
+
+/// What the event loop should do after a key press.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    None,
+    Refresh,
+    Quit,
+}
+
+pub struct App {
+    pub data: Snapshot,
+    pub sort: SortKey,
+    pub descending: bool,
+    pub filter: String,
+    /// Typing into the filter rather than navigating
+    pub editing_filter: bool,
+    /// Symbol shown in the detail view
+    pub detail: Option<String>,
+    pub table: TableState,
+    /// Last refresh error, shown in the footer
+    pub error: Option<String>,
+}
+
+impl Default for App {
+    fn default() -> Self {
+        Self {
+            data: Snapshot::default(),
+            sort: SortKey::Rank,
+            descending: false,
+            filter: String::new(),
+            editing_filter: false,
+            detail: None,
+            table: TableState::default().with_selected(Some(0)),
+            error: None,
+        }
+    }
+}
+
+impl App {
+    /// Replace the data, keeping the same coin selected if it is still listed.
+    pub fn update(&mut self, data: Snapshot) {
+        let selected = self.selected().map(|coin| coin.id);
+        self.data = data;
+        self.error = None;
+        let index = selected.and_then(|id| self.visible().iter().position(|coin| coin.id == id));
+        self.table.select(Some(index.unwrap_or(0)));
+    }
+
+    /// Coins matching the filter, in display order.
+    pub fn visible(&self) -> Vec<&CoinResponse> {
+        let filter = self.filter.to_uppercase();
+        let mut coins: Vec<&CoinResponse> = self.data.coins.iter()
+            .filter(|coin| coin.symbol.to_uppercase().contains(&filter))
+            .collect();
+        coins.sort_by(|a, b| {
+            let order = self.sort.compare(a, b);
+            if self.descending { order.reverse() } else { order }
+        });
+        coins
+    }
+
+    pub fn selected(&self) -> Option<&CoinResponse> {
+        self.visible().get(self.table.selected()?).copied()
+    }
+
+    /// Places moved since the previous scrape; positive means up.
+    pub fn rank_change(&self, coin: &CoinResponse) -> Option<i64> {
+        let previous = *self.data.previous_ranks.get(&coin.id)?;
+        Some(previous as i64 - coin.rank? as i64)
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> Action {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return Action::Quit;
+        }
+        if self.editing_filter {
+            match key.code {
+                KeyCode::Enter => self.editing_filter = false,
+                KeyCode::Esc => {
+                    self.editing_filter = false;
+                    self.filter.clear();
+                }
+                KeyCode::Backspace => {
+                    self.filter.pop();
+                }
+                KeyCode::Char(c) => self.filter.push(c),
+                _ => return Action::None,
+            }
+            self.table.select(Some(0));
+            return Action::None;
+        }
+        if self.detail.is_some() {
+            match key.code {
+                KeyCode::Char('q') => return Action::Quit,
+                KeyCode::Esc | KeyCode::Enter | KeyCode::Backspace => self.detail = None,
+                KeyCode::Char('r') => return Action::Refresh,
+                _ => {}
+            }
+            return Action::None;
+        }
+
+        let len = self.visible().len();
+        let current = self.table.selected().unwrap_or(0);
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Action::Quit,
+            KeyCode::Char('r') => return Action::Refresh,
+            KeyCode::Down | KeyCode::Char('j') => self.table.select(Some((current + 1).min(len.saturating_sub(1)))),
+            KeyCode::Up | KeyCode::Char('k') => self.table.select(Some(current.saturating_sub(1))),
+            KeyCode::PageDown => self.table.select(Some((current + 10).min(len.saturating_sub(1)))),
+            KeyCode::PageUp => self.table.select(Some(current.saturating_sub(10))),
+            KeyCode::Home | KeyCode::Char('g') => self.table.select(Some(0)),
+            KeyCode::End | KeyCode::Char('G') => self.table.select(Some(len.saturating_sub(1))),
+            KeyCode::Enter => self.detail = self.selected().map(|coin| coin.symbol.clone()),
+            KeyCode::Char('/') => self.editing_filter = true,
+            KeyCode::Char('s') => self.sort = self.sort.next(),
+            KeyCode::Char('S') => self.descending = !self.descending,
+            _ => {}
+        }
+        Action::None
+    }
+}
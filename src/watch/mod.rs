@@ -0,0 +1,115 @@
+pub mod app;
+mod ui;
+
+use anyhow::Result;
+use chrono::Utc;
+use ratatui::crossterm::event::{self, Event, KeyEventKind};
+use ratatui::DefaultTerminal;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use crate::database::{Database, MoversFilter};
+use app::{Action, App, Snapshot};
+
+/// Number of top gainers and losers highlighted in the table.
+const MOVERS: u32 = 5;
+
+#[derive(Debug, Clone)]
+pub struct WatchOptions {
+    /// Number of coins to show, by rank
+    pub limit: i64,
+    /// How often to check for a new scrape run
+    pub refresh: Duration,
+    /// How far back the sparklines go
+    pub window: Duration,
+}
+
+impl Default for WatchOptions {
+    fn default() -> Self {
+        Self {
+            limit: 50,
+            refresh: Duration::from_secs(5),
+            window: Duration::from_secs(24 * 60 * 60),
+        }
+    }
+}
+
+/// Full-screen dashboard of the latest scrape, reloaded whenever a new
+/// scrape lands in the database. Returns when the user quits.
+pub async fn watch(db: &Database, options: &WatchOptions) -> Result<()> {
+    let mut app = App::default();
+    app.update(load(db, options).await?);
+
+    let mut terminal = ratatui::init();
+    let result = run(&mut terminal, db, options, &mut app).await;
+    ratatui::restore();
+    result
+}
+
+async fn run(terminal: &mut DefaultTerminal, db: &Database, options: &WatchOptions, app: &mut App) -> Result<()> {
+    // crossterm only offers blocking reads, so they happen on their own thread
+    let (tx, mut events) = mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        while let Ok(event) = event::read() {
+            if tx.send(event).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut tick = tokio::time::interval(options.refresh);
+    tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+    loop {
+        terminal.draw(|frame| ui::draw(frame, app))?;
+
+        let reload = tokio::select! {
+            event = events.recv() => match event {
+                Some(Event::Key(key)) if key.kind == KeyEventKind::Press => match app.handle_key(key) {
+                    Action::Quit => return Ok(()),
+                    Action::Refresh => true,
+                    Action::None => false,
+                },
+                Some(_) => false,
+                None => return Ok(()),
+            },
+            _ = tick.tick() => match db.latest_snapshot_at().await {
+                Ok(latest) => latest != app.data.updated_at,
+                Err(err) => {
+                    app.error = Some(format!("{:#}", err));
+                    false
+                }
+            },
+        };
+
+        // Errors are shown in the footer; the last good data stays on screen
+        if reload {
+            match load(db, options).await {
+                Ok(data) => app.update(data),
+                Err(err) => app.error = Some(format!("{:#}", err)),
+            }
+        }
+    }
+}
+
+async fn load(db: &Database, options: &WatchOptions) -> Result<Snapshot> {
+    let updated_at = db.latest_snapshot_at().await?;
+    let coins = db.get_latest_coins_api(options.limit).await?;
+    let previous_ranks = db.get_previous_ranks().await?;
+    let movers = db.get_movers(&MoversFilter { limit: MOVERS, ..MoversFilter::default() }).await?;
+
+    let since = Utc::now() - chrono::TimeDelta::from_std(options.window)?;
+    let ids: Vec<u64> = coins.iter().map(|coin| coin.id).collect();
+    let history = db.get_coin_histories(&ids, since).await?
+        .into_iter()
+        .map(|(id, points)| (id, points.into_iter().filter_map(|p| p.price_usd).collect()))
+        .collect();
+
+    Ok(Snapshot {
+        coins,
+        previous_ranks,
+        movers: movers.gainers.iter().chain(&movers.losers).map(|coin| coin.id).collect(),
+        history,
+        updated_at,
+    })
+}
@@ -0,0 +1,198 @@
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Cell, Paragraph, Row, Sparkline, Table};
+use ratatui::Frame;
+
+use super::app::App;
+use crate::model::CoinResponse;
+
+const SPARK_WIDTH: usize = 24;
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+pub fn draw(frame: &mut Frame, app: &mut App) {
+    let [header, body, footer] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Min(0),
+        Constraint::Length(1),
+    ]).areas(frame.area());
+
+    frame.render_widget(header_line(app), header);
+    let detail = app.detail.clone()
+        .and_then(|symbol| app.data.coins.iter().find(|coin| coin.symbol == symbol).cloned());
+    match detail {
+        Some(coin) => draw_detail(frame, app, &coin, body),
+        None => draw_table(frame, app, body),
+    }
+    frame.render_widget(footer_line(app), footer);
+}
+
+fn header_line(app: &App) -> Paragraph<'static> {
+    let updated = app.data.updated_at
+        .map(|ts| ts.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+        .unwrap_or_else(|| "no data".to_string());
+    let mut spans = vec![
+        Span::from(" coinbase_scraper watch ").bold().reversed(),
+        Span::from(format!("  last scrape {}  sort {} {}", updated, app.sort.label(), if app.descending { "↓" } else { "↑" })),
+    ];
+    if !app.filter.is_empty() || app.editing_filter {
+        spans.push(Span::from(format!("  filter /{}", app.filter)).yellow());
+    }
+    Paragraph::new(Line::from(spans))
+}
+
+fn footer_line(app: &App) -> Paragraph<'static> {
+    if let Some(err) = &app.error {
+        return Paragraph::new(Line::from(format!(" refresh failed: {}", err)).red());
+    }
+    let help = if app.editing_filter {
+        " type a symbol  enter keep filter  esc clear"
+    } else if app.detail.is_some() {
+        " esc back  r refresh  q quit"
+    } else {
+        " ↑↓ move  enter details  / filter  s sort  S reverse  r refresh  q quit"
+    };
+    Paragraph::new(Line::from(help).dark_gray())
+}
+
+fn draw_table(frame: &mut Frame, app: &mut App, area: Rect) {
+    let rows: Vec<Row> = app.visible().into_iter().map(|coin| {
+        let mover = app.data.movers.contains(&coin.id);
+        let history = app.data.history.get(&coin.id).map(Vec::as_slice).unwrap_or_default();
+        Row::new(vec![
+            Cell::from(opt(coin.rank)),
+            rank_change_cell(app.rank_change(coin)),
+            Cell::from(coin.symbol.clone()).bold(),
+            Cell::from(coin.name.clone()),
+            Cell::from(Line::from(price(coin.price_usd)).right_aligned()),
+            change_cell(coin.change_24h, mover),
+            Cell::from(Line::from(compact_usd(coin.market_cap_usd)).right_aligned()),
+            Cell::from(sparkline(history, SPARK_WIDTH)).style(change_style(coin.change_24h)),
+        ])
+    }).collect();
+
+    let header = Row::new(["#", "Δ", "Symbol", "Name", "Price", "24h %", "Market cap", "Trend"])
+        .style(Style::new().bold().underlined());
+    let widths = [
+        Constraint::Length(5),
+        Constraint::Length(4),
+        Constraint::Length(8),
+        Constraint::Fill(1),
+        Constraint::Length(16),
+        Constraint::Length(9),
+        Constraint::Length(11),
+        Constraint::Length(SPARK_WIDTH as u16),
+    ];
+    let table = Table::new(rows, widths)
+        .header(header)
+        .row_highlight_style(Style::new().reversed())
+        .block(Block::bordered().title(format!(" Top {} ", app.data.coins.len())));
+    frame.render_stateful_widget(table, area, &mut app.table);
+}
+
+fn draw_detail(frame: &mut Frame, app: &App, coin: &CoinResponse, area: Rect) {
+    let history = app.data.history.get(&coin.id).map(Vec::as_slice).unwrap_or_default();
+    let block = Block::bordered().title(format!(" {} ({}) ", coin.name, coin.symbol));
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let [stats, chart] = Layout::vertical([Constraint::Length(6), Constraint::Min(0)]).areas(inner);
+    let rank = match app.rank_change(coin) {
+        Some(change) if change != 0 => format!("{} ({:+} since last scrape)", opt(coin.rank), change),
+        _ => opt(coin.rank),
+    };
+    let (low, high) = history.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), p| (lo.min(*p), hi.max(*p)));
+    let lines = vec![
+        Line::from(format!("Rank        {}", rank)),
+        Line::from(format!("Price       {}", price(coin.price_usd))),
+        Line::from(vec![
+            Span::from("24h change  "),
+            Span::styled(opt(coin.change_24h.map(|c| format!("{:+.2}%", c))), change_style(coin.change_24h)),
+        ]),
+        Line::from(format!("Market cap  {}", compact_usd(coin.market_cap_usd))),
+        Line::from(format!("Range       {} – {} over {} points",
+                           price(Some(low).filter(|l| l.is_finite())),
+                           price(Some(high).filter(|h| h.is_finite())),
+                           history.len())),
+        Line::from(format!("Updated     {}", coin.ts_utc.format("%Y-%m-%d %H:%M:%S UTC"))),
+    ];
+    frame.render_widget(Paragraph::new(lines), stats);
+
+    // Scale into integer bars, keeping the lowest price just above the baseline
+    let span = (high - low).max(f64::EPSILON);
+    let bars = resample(history, chart.width as usize).into_iter()
+        .map(|p| 1 + ((p - low) / span * 1000.0) as u64);
+    frame.render_widget(
+        Sparkline::default().data(bars.collect::<Vec<_>>()).style(change_style(coin.change_24h)),
+        chart,
+    );
+}
+
+fn rank_change_cell(change: Option<i64>) -> Cell<'static> {
+    match change {
+        Some(up) if up > 0 => Cell::from(format!("▲{}", up)).green(),
+        Some(down) if down < 0 => Cell::from(format!("▼{}", -down)).red(),
+        _ => Cell::from(""),
+    }
+}
+
+/// Top movers are shown in reverse video so they stand out from the rest.
+fn change_cell(change: Option<f64>, mover: bool) -> Cell<'static> {
+    let mut style = change_style(change);
+    if mover {
+        style = style.add_modifier(Modifier::BOLD | Modifier::REVERSED);
+    }
+    Cell::from(Line::from(opt(change.map(|c| format!("{:+.2}", c)))).right_aligned()).style(style)
+}
+
+fn change_style(change: Option<f64>) -> Style {
+    match change {
+        Some(c) if c > 0.0 => Style::new().fg(Color::Green),
+        Some(c) if c < 0.0 => Style::new().fg(Color::Red),
+        _ => Style::new(),
+    }
+}
+
+fn opt<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+fn price(value: Option<f64>) -> String {
+    match value {
+        Some(p) if p >= 1.0 => format!("${:.2}", p),
+        Some(p) => format!("${:.6}", p),
+        None => String::new(),
+    }
+}
+
+fn compact_usd(value: Option<f64>) -> String {
+    let Some(v) = value else { return String::new() };
+    match v {
+        v if v >= 1e12 => format!("${:.2}T", v / 1e12),
+        v if v >= 1e9 => format!("${:.2}B", v / 1e9),
+        v if v >= 1e6 => format!("${:.2}M", v / 1e6),
+        v => format!("${:.0}", v),
+    }
+}
+
+/// At most `width` points, taking the last price in each bucket.
+fn resample(values: &[f64], width: usize) -> Vec<f64> {
+    if values.len() <= width || width == 0 {
+        return values.to_vec();
+    }
+    (1..=width).map(|i| values[i * values.len() / width - 1]).collect()
+}
+
+/// Text sparkline scaled between the lowest and highest price.
+fn sparkline(values: &[f64], width: usize) -> String {
+    let values = resample(values, width);
+    let (low, high) = values.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), p| (lo.min(*p), hi.max(*p)));
+    let span = high - low;
+    values.iter().map(|p| {
+        if span > 0.0 {
+            BARS[(((p - low) / span) * (BARS.len() - 1) as f64).round() as usize]
+        } else {
+            BARS[BARS.len() / 2]
+        }
+    }).collect()
+}
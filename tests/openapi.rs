@@ -0,0 +1,232 @@
+//! Checks the served OpenAPI document against the running handlers: every
+//! documented operation must exist, answer with a documented status, and
+//! return bodies that match the documented schema.
+
+use anyhow::{bail, Context, Result};
+use chrono::{Duration, Utc};
+use coinbase_scraper::api::{create_router, openapi};
+use coinbase_scraper::{CoinRow, Database};
+use serde_json::Value;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+#[tokio::test]
+async fn spec_matches_handlers() -> Result<()> {
+    let (_dir, base) = start_server().await?;
+    let client = reqwest::Client::new();
+
+    let served: Value = serde_json::from_str(&client.get(format!("{}/openapi.json", base)).send().await?.text().await?)?;
+    assert_eq!(served, serde_json::to_value(openapi())?, "/openapi.json differs from api::openapi()");
+    let docs = client.get(format!("{}/docs", base)).send().await?;
+    assert!(docs.status().is_success(), "/docs returned {}", docs.status());
+
+    let paths = served["paths"].as_object().context("spec has no paths")?;
+    assert!(!paths.is_empty(), "spec documents no operations");
+    for (template, item) in paths {
+        for (method, operation) in item.as_object().context("path item is not an object")? {
+            let name = format!("{} {}", method.to_uppercase(), template);
+            let params = operation["parameters"].as_array().cloned().unwrap_or_default();
+            // Required query parameters go on every request
+            let required = params.iter()
+                .filter(|p| p["in"] == "query" && p["required"] == true)
+                .map(|p| Ok((p["name"].as_str().unwrap_or_default(), sample(p, &served)?)))
+                .collect::<Result<Vec<_>>>()?;
+            let url = reqwest::Url::parse_with_params(&format!("{}{}", base, fill_path(template, &params, "BTC")), &required)?.to_string();
+
+            // Bodies are posted as documented in their example
+            if method == "post" {
+                let body = example_body(operation, &served).with_context(|| format!("{}: no request body example", name))?;
+                check(&client, &served, operation, &name, &url, Some(&body), 200).await?;
+                continue;
+            }
+            assert_eq!(method, "get", "{}: only GET and POST operations are exercised", name);
+
+            // Known symbol, no optional query parameters
+            check(&client, &served, operation, &name, &url, None, 200).await?;
+
+            // Every documented query parameter must be accepted; one at a
+            // time, since some exclude each other
+            for param in params.iter().filter(|p| p["in"] == "query" && p["required"] != true) {
+                let query = [(param["name"].as_str().unwrap_or_default(), sample(param, &served)?)];
+                let url = reqwest::Url::parse_with_params(&url, &query)?;
+                check(&client, &served, operation, &name, url.as_str(), None, 200).await?;
+            }
+
+            // Unknown symbols are a documented 404 wherever there is a symbol
+            if params.iter().any(|p| p["in"] == "path") {
+                let url = reqwest::Url::parse_with_params(&format!("{}{}", base, fill_path(template, &params, "NOPE")), &required)?.to_string();
+                let status = if operation["responses"].get("404").is_some() { 404 } else { 200 };
+                check(&client, &served, operation, &name, &url, None, status).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Request `url`, with a GET or, given a body, a JSON POST; expect `status`,
+/// and validate the body against the schema documented for that status.
+async fn check(client: &reqwest::Client, spec: &Value, operation: &Value, name: &str, url: &str, body: Option<&Value>, status: u16) -> Result<()> {
+    let request = match body {
+        Some(body) => client.post(url).header("content-type", "application/json").body(body.to_string()),
+        None => client.get(url),
+    };
+    let mut response = request.send().await?;
+    let actual = response.status().as_u16();
+    let content_type = response.headers().get("content-type").and_then(|v| v.to_str().ok()).unwrap_or_default().to_string();
+    let media = if content_type.starts_with("text/event-stream") { "text/event-stream" } else { "application/json" };
+    let text = if media == "text/event-stream" {
+        first_event(&mut response).await?
+    } else {
+        response.text().await?
+    };
+    let body: Value = serde_json::from_str(&text).with_context(|| format!("{}: body is not JSON: {}", url, text))?;
+    assert_eq!(actual, status, "{}: {} returned {}: {}", name, url, actual, body);
+
+    let documented = &operation["responses"][status.to_string()];
+    assert!(documented.is_object(), "{}: status {} is not documented", name, status);
+    let schema = &documented["content"][media]["schema"];
+    assert!(schema.is_object(), "{}: status {} has no JSON schema", name, status);
+    if let Err(err) = conforms(&body, schema, spec, "$") {
+        panic!("{}: {} does not match the spec: {:#}\n{}", name, url, err, body);
+    }
+    Ok(())
+}
+
+/// The data of the first Server-Sent Event; streams never end, so only read
+/// that far.
+async fn first_event(response: &mut reqwest::Response) -> Result<String> {
+    let mut buffer = String::new();
+    while !buffer.contains("\n\n") {
+        let chunk = response.chunk().await?.context("event stream ended before the first event")?;
+        buffer.push_str(std::str::from_utf8(&chunk)?);
+    }
+    let event = buffer.split("\n\n").next().unwrap_or_default();
+    Ok(event.lines().filter_map(|line| line.strip_prefix("data:")).map(str::trim_start).collect::<Vec<_>>().join("\n"))
+}
+
+/// Structural JSON Schema check covering what utoipa emits: `$ref`, `type`
+/// (including nullable type lists), object properties, arrays and tuples.
+/// Properties missing from the schema count as drift.
+fn conforms(value: &Value, schema: &Value, spec: &Value, at: &str) -> Result<()> {
+    if let Some(reference) = schema["$ref"].as_str() {
+        let pointer = reference.strip_prefix('#').context("external $ref")?;
+        let target = spec.pointer(pointer).with_context(|| format!("{}: dangling $ref {}", at, reference))?;
+        return conforms(value, target, spec, at);
+    }
+    if let Some(options) = schema["oneOf"].as_array().or(schema["anyOf"].as_array()) {
+        if options.iter().any(|option| conforms(value, option, spec, at).is_ok()) {
+            return Ok(());
+        }
+        bail!("{}: matches none of the alternatives", at);
+    }
+
+    let types: Vec<&str> = match &schema["type"] {
+        Value::String(t) => vec![t.as_str()],
+        Value::Array(ts) => ts.iter().filter_map(Value::as_str).collect(),
+        _ => return Ok(()),
+    };
+    let actual = match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_i64() || n.is_u64() => "integer",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    };
+    if !types.iter().any(|t| *t == actual || (*t == "number" && actual == "integer")) {
+        bail!("{}: expected {}, got {}", at, types.join(" or "), actual);
+    }
+
+    match value {
+        Value::Object(fields) => {
+            let properties = schema["properties"].as_object().cloned().unwrap_or_default();
+            for required in schema["required"].as_array().into_iter().flatten().filter_map(Value::as_str) {
+                if !fields.contains_key(required) {
+                    bail!("{}: missing required property '{}'", at, required);
+                }
+            }
+            for (key, field) in fields {
+                let property = properties.get(key).with_context(|| format!("{}: undocumented property '{}'", at, key))?;
+                conforms(field, property, spec, &format!("{}.{}", at, key))?;
+            }
+        }
+        Value::Array(items) => {
+            let prefix = schema["prefixItems"].as_array();
+            for (i, item) in items.iter().enumerate() {
+                let item_schema = match prefix {
+                    Some(prefix) => prefix.get(i).with_context(|| format!("{}: tuple has more than {} items", at, prefix.len()))?,
+                    None => &schema["items"],
+                };
+                conforms(item, item_schema, spec, &format!("{}[{}]", at, i))?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// The example of an operation's JSON request body, given on the body or on
+/// its schema.
+fn example_body(operation: &Value, spec: &Value) -> Option<Value> {
+    let content = &operation["requestBody"]["content"]["application/json"];
+    let mut schema = &content["schema"];
+    if let Some(reference) = schema["$ref"].as_str() {
+        schema = spec.pointer(reference.trim_start_matches('#'))?;
+    }
+    [&content["example"], &schema["example"]].into_iter().find(|example| !example.is_null()).cloned()
+}
+
+fn fill_path(template: &str, params: &[Value], symbol: &str) -> String {
+    params.iter()
+        .filter(|p| p["in"] == "path")
+        .fold(template.to_string(), |path, p| path.replace(&format!("{{{}}}", p["name"].as_str().unwrap_or_default()), symbol))
+}
+
+/// A valid value for a query parameter: its documented example, or one
+/// made up from its schema.
+fn sample(param: &Value, spec: &Value) -> Result<String> {
+    let mut schema = &param["schema"];
+    if let Some(reference) = schema["$ref"].as_str() {
+        schema = spec.pointer(reference.trim_start_matches('#')).with_context(|| format!("dangling $ref {}", reference))?;
+    }
+    if let Some(example) = param["example"].as_str().or(schema["example"].as_str()) {
+        return Ok(example.to_string());
+    }
+    Ok(match (schema["type"].as_str(), schema["format"].as_str()) {
+        (_, Some("date-time")) => (Utc::now() - Duration::days(1)).to_rfc3339(),
+        (Some("integer"), _) | (Some("number"), _) => "5".to_string(),
+        (Some("boolean"), _) => "true".to_string(),
+        _ => schema["enum"][0].as_str().unwrap_or("BTC").to_string(),
+    })
+}
+
+/// Serve the API on a random port from a database holding two scrapes. The
+/// database lives in the returned directory, which is deleted when dropped.
+async fn start_server() -> Result<(TempDir, String)> {
+    let dir = tempfile::tempdir()?;
+    let db = Database::new(&format!("sqlite:{}?mode=rwc", dir.path().join("openapi_test.db").display())).await?;
+
+    let coins = [(1, "Bitcoin", "BTC", 60_000.0, 2.5), (1027, "Ethereum", "ETH", 3_000.0, -1.5)];
+    for (run, scraped_at) in [Utc::now() - Duration::hours(1), Utc::now()].into_iter().enumerate() {
+        let rows: Vec<CoinRow> = coins.iter().enumerate()
+            .map(|(i, (id, name, symbol, price, change))| CoinRow {
+                id: *id,
+                rank: Some(i as u64 + 1),
+                name: name.to_string(),
+                symbol: symbol.to_string(),
+                price_usd: Some(price + run as f64),
+                market_cap_usd: Some(price * 1e7),
+                chg24h_pct: Some(*change),
+                scraped_at,
+            })
+            .collect();
+        db.save_coins(&rows).await?;
+    }
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let app = create_router(Arc::new(db));
+    tokio::spawn(async move { axum::serve(listener, app).await });
+    Ok((dir, format!("http://{}", addr)))
+}